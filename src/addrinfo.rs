@@ -10,6 +10,10 @@ use std::io;
 use std::fmt;
 use std::error::Error;
 use std::thread;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::collections::HashMap;
 
 use chan;
 use futures::sync::oneshot;
@@ -19,6 +23,10 @@ pub const AI_CANONNAME: libc::c_int = 0x0002;
 pub const AI_NUMERICHOST: libc::c_int = 0x0004;
 pub const AI_NUMERICSERV: libc::c_int = 0x0400;
 
+/// Flags used by getaddrinfo if caller does not specify any,
+/// only address families configured on the host are returned.
+pub const AI_DEFAULT: libc::c_int = libc::AI_V4MAPPED | libc::AI_ADDRCONFIG;
+
 
 #[derive(Copy, Clone, Debug)]
 /// Address family
@@ -274,7 +282,11 @@ pub fn lookup_addrinfo(
         let lres = libc::getaddrinfo(c_host, c_srv, &hints, &mut res);
         match lres {
             0 => Ok(LookupAddrInfo { orig: res, cur: res }),
-            _ => Err(LookupError::Generic),
+            libc::EAI_SYSTEM => Err(LookupError::IOError(io::Error::last_os_error())),
+            code => {
+                let msg = CStr::from_ptr(libc::gai_strerror(code)).to_string_lossy();
+                Err(LookupError::Gai(code, msg.into_owned()))
+            }
         }
     }
 }
@@ -317,6 +329,8 @@ pub enum LookupError {
     NulError(NulError),
     /// Other error
     Other(String),
+    /// getaddrinfo error, EAI_* code and message
+    Gai(libc::c_int, String),
     /// An unspecific error
     Generic
 }
@@ -340,11 +354,24 @@ impl<'a> From<&'a str> for LookupError {
     }
 }
 
+impl LookupError {
+
+    // resolution errors are cacheable, io errors are not
+    fn resolution_error(&self) -> Option<LookupError> {
+        match *self {
+            LookupError::Other(ref err) => Some(LookupError::Other(err.clone())),
+            LookupError::Gai(code, ref err) => Some(LookupError::Gai(code, err.clone())),
+            _ => None,
+        }
+    }
+}
+
 impl Error for LookupError {
     fn description(&self) -> &str {
         match *self {
             LookupError::IOError(_) => "IO Error",
             LookupError::Other(ref err_str) => &err_str,
+            LookupError::Gai(_, ref err_str) => &err_str,
             LookupError::NulError(_) => "nil pointer",
             LookupError::Generic => "generic error",
         }
@@ -375,39 +402,205 @@ impl fmt::Debug for LookupError {
 pub type LookupResultSender = oneshot::Sender<Result<Vec<AddrInfo>, LookupError>>;
 pub type LookupResultReceiver = oneshot::Receiver<Result<Vec<AddrInfo>, LookupError>>;
 
-pub type LookupWorkerSender = chan::Sender<(LookupParams, LookupResultSender)>;
 pub type LookupWorkerReceiver = chan::Receiver<(LookupParams, LookupResultSender)>;
 
+/// Default number of lookup worker threads
+pub const DEFAULT_WORKERS: usize = 3;
+
+/// Sender part of lookup workers pool. Lookups are queued without limit
+/// and picked up by first available worker. Workers exit when all
+/// senders are dropped and queue is drained.
+#[derive(Clone)]
+pub struct LookupWorkerSender {
+    tx: chan::Sender<(LookupParams, LookupResultSender)>,
+    workers: usize,
+    pending: Arc<AtomicUsize>,
+}
+
+impl LookupWorkerSender {
+
+    /// Number of worker threads
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Number of queued and in-progress lookups
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    fn send(&self, params: LookupParams, tx: LookupResultSender) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.tx.send((params, tx));
+    }
+}
 
 pub fn start_workers(num: usize) -> LookupWorkerSender {
     let (tx, rx) = chan::async();
+    let pending = Arc::new(AtomicUsize::new(0));
 
     for _ in 0..num {
         let r: LookupWorkerReceiver = rx.clone();
+        let pending = pending.clone();
         thread::spawn(move || {
             loop {
                 match r.recv() {
                     None => return,
                     Some((params, tx)) => {
-                        match lookup_addrinfo(params.host, params.port,
-                                              params.family, params.flags, params.socktype) {
-                            Err(err) => {
-                                let _ = tx.send(Err(err));
-                            },
-                            Ok(lookup) => {
-                                if let Err(_) = tx.send(Ok(lookup.collect())) {
-                                    // event loop is gone
-                                    return
-                                }
-                            },
-                        };
+                        // lookup is cancelled, result receiver is dropped
+                        if tx.is_canceled() {
+                            pending.fetch_sub(1, Ordering::SeqCst);
+                            continue
+                        }
+
+                        let result = lookup_addrinfo(
+                            params.host, params.port,
+                            params.family, params.flags, params.socktype)
+                            .map(|lookup| lookup.collect());
+
+                        // lookup is not pending anymore once caller sees result
+                        pending.fetch_sub(1, Ordering::SeqCst);
+
+                        // receiver could be dropped by cancellation,
+                        // worker exits only when channel is closed
+                        let _ = tx.send(result);
                     }
                 }
             }
         });
     }
 
-    tx
+    LookupWorkerSender {
+        tx: tx,
+        workers: num,
+        pending: pending,
+    }
+}
+
+/// Key of cached lookup result
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct LookupKey {
+    host: Option<String>,
+    port: Option<String>,
+    family: libc::c_int,
+    flags: libc::c_int,
+    socktype: libc::c_int,
+}
+
+impl LookupKey {
+    pub fn new(host: &Option<String>, port: &Option<String>,
+               family: libc::c_int, flags: libc::c_int, socktype: SocketType) -> LookupKey {
+        LookupKey {
+            host: host.clone(),
+            port: port.clone(),
+            family: family,
+            flags: flags,
+            socktype: socktype.to_int(),
+        }
+    }
+}
+
+// negative results are cached for short period only
+const NEGATIVE_TTL: u64 = 1;
+
+// max number of cached lookups
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+struct LookupCacheInner {
+    entries: HashMap<LookupKey, (Duration, Result<Vec<AddrInfo>, LookupError>)>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Cache of lookup results, entries expire after ttl.
+///
+/// `now` is time of event loop's clock, expired entries are evicted
+/// when cache is full.
+#[derive(Clone)]
+pub struct LookupCache {
+    ttl: Duration,
+    inner: Arc<Mutex<LookupCacheInner>>,
+}
+
+impl LookupCache {
+
+    pub fn new(ttl: Duration) -> LookupCache {
+        LookupCache {
+            ttl: ttl,
+            inner: Arc::new(Mutex::new(LookupCacheInner {
+                entries: HashMap::new(),
+                hits: 0,
+                misses: 0,
+            })),
+        }
+    }
+
+    pub fn get(&self, key: &LookupKey, now: Duration)
+               -> Option<Result<Vec<AddrInfo>, LookupError>> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let cached = match inner.entries.get(key) {
+            Some(&(expires, ref res)) if expires > now => Some(match *res {
+                Ok(ref addrs) => Ok(addrs.clone()),
+                Err(ref err) => Err(err.resolution_error().unwrap()),
+            }),
+            _ => None,
+        };
+
+        match cached {
+            Some(res) => {
+                inner.hits += 1;
+                Some(res)
+            },
+            None => {
+                // drop expired entry
+                inner.entries.remove(key);
+                inner.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: LookupKey,
+                  result: &Result<Vec<AddrInfo>, LookupError>, now: Duration) {
+        let entry = match *result {
+            Ok(ref addrs) => (now + self.ttl, Ok(addrs.clone())),
+            // cache resolution errors only, not io errors
+            Err(ref err) => match err.resolution_error() {
+                Some(err) => {
+                    let ttl = ::std::cmp::min(self.ttl, Duration::from_secs(NEGATIVE_TTL));
+                    (now + ttl, Err(err))
+                },
+                None => return,
+            },
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.entries.len() >= MAX_CACHE_ENTRIES && !inner.entries.contains_key(&key) {
+            inner.entries.retain(|_, &mut (expires, _)| expires > now);
+
+            // still full, drop entry which expires first
+            if inner.entries.len() >= MAX_CACHE_ENTRIES {
+                let first = inner.entries.iter()
+                    .min_by_key(|&(_, &(expires, _))| expires)
+                    .map(|(key, _)| key.clone());
+                if let Some(first) = first {
+                    inner.entries.remove(&first);
+                }
+            }
+        }
+        inner.entries.insert(key, entry);
+    }
+
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().entries.clear()
+    }
+
+    /// Number of cache hits and misses
+    pub fn stats(&self) -> (u64, u64) {
+        let inner = self.inner.lock().unwrap();
+        (inner.hits, inner.misses)
+    }
 }
 
 pub fn lookup(sender: &LookupWorkerSender,
@@ -418,7 +611,7 @@ pub fn lookup(sender: &LookupWorkerSender,
     let params = LookupParams::new(host, port, family, flags, socktype);
 
     let (tx, rx) = oneshot::channel();
-    sender.send((params, tx));
+    sender.send(params, tx);
 
     rx
 }
@@ -1,7 +1,12 @@
 use std::io;
+use std::fmt;
 use std::net;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::error::Error;
+use std::time::Duration;
 use pyo3::*;
-use futures::{future, Future};
+use futures::{future, unsync, Future};
 use net2::TcpBuilder;
 use tokio_core::net::TcpStream;
 
@@ -10,92 +15,200 @@ use addrinfo::AddrInfo;
 use fut::{for_each, Until, UntilError};
 use pyunsafe::{GIL, Handle};
 use transport::{InitializedTransport, tcp_transport_factory};
+use utils::{Classes, ToPyErr};
 
 
+/// Pre-resolved addresses of remote host, `create_connection` accepts it
+/// with `endpoint` keyword and connects without address resolution
+#[py::class]
+pub struct ResolvedEndpoint {
+    addrs: Vec<AddrInfo>,
+    token: PyToken,
+}
+
+#[py::ptr(ResolvedEndpoint)]
+pub struct ResolvedEndpointPtr(PyPtr);
+
+impl ResolvedEndpoint {
+
+    pub fn new(py: Python, addrs: Vec<AddrInfo>) -> PyResult<ResolvedEndpointPtr> {
+        py.init(|t| ResolvedEndpoint {addrs: addrs, token: t})
+    }
+
+    /// Resolved addresses, family and protocol are preserved
+    pub fn addrs(&self) -> &Vec<AddrInfo> {
+        &self.addrs
+    }
+}
+
+#[py::proto]
+impl PyObjectProtocol for ResolvedEndpoint {
+
+    fn __repr__(&self, py: Python) -> PyResult<PyString> {
+        let addrs: Vec<String> = self.addrs.iter()
+            .map(|info| format!("{}", info.sockaddr))
+            .collect();
+        Ok(PyString::new(py, &format!("<ResolvedEndpoint [{}]>", addrs.join(", "))))
+    }
+}
+
+
+/// Errors of all failed connection attempts
+#[derive(Debug)]
+pub struct ConnectErrors(pub Vec<io::Error>);
+
+impl fmt::Display for ConnectErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl Error for ConnectErrors {
+    fn description(&self) -> &str {
+        "create_connection failed"
+    }
+}
+
+/// Convert connection error to python exception,
+/// errors of all connection attempts are raised as ExceptionGroup
+pub fn connect_error_to_pyerr(py: Python, err: &io::Error) -> PyErr {
+    let errors = match err.get_ref().and_then(|e| e.downcast_ref::<ConnectErrors>()) {
+        Some(errors) => errors,
+        None => return err.to_pyerr(py),
+    };
+    let mut exceptions: Vec<PyObject> = errors.0.iter()
+        .map(|err| err.to_pyerr(py).instance(py))
+        .collect();
+
+    // python 3.11+ only, looked up on each call
+    let group = Classes.Builtins.get(py, "ExceptionGroup").ok()
+        .and_then(|tp| PyType::extract(py, &tp).ok());
+    match group {
+        Some(tp) => PyErr::new_err(
+            py, &tp, (errors.description(), PyList::new(py, &exceptions))),
+        None => {
+            let msg = exceptions.iter_mut()
+                .map(|exc| format!("{}", exc))
+                .collect::<Vec<_>>()
+                .join(", ");
+            PyErr::new::<exc::OSError, _>(py, format!("Multiple exceptions: {}", msg))
+        }
+    }
+}
+
+
+// ssl handshake timeout fails connection, transport is aborted already
+fn handshake_result(result: io::Result<InitializedTransport>,
+                    waiter: Result<PyResult<PyObject>, unsync::oneshot::Canceled>)
+                    -> io::Result<InitializedTransport> {
+    let py = GIL::python();
+    match waiter {
+        Ok(Err(ref err)) if err.matches(py, py.get_type::<exc::TimeoutError>()) => {
+            let mut err = err.clone_ref(py);
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut, format!("{}", err.instance(py))))
+        },
+        _ => result,
+    }
+}
+
 pub fn create_sock_connection(
     factory: PyObject, evloop: TokioEventLoopPtr,
     stream: TcpStream, addr: AddrInfo,
-    ssl: Option<PyObject>, hostname: Option<PyObject>, waiter: PyFuturePtr)
-    -> Box<Future<Item=InitializedTransport, Error=io::Error>> {
+    ssl: Option<PyObject>, hostname: Option<PyObject>, handshake_timeout: Option<Duration>,
+    waiter: PyFuturePtr) -> Box<Future<Item=InitializedTransport, Error=io::Error>> {
 
     let peer = stream.peer_addr().expect("should never happen");
 
     let result = tcp_transport_factory(
-        evloop, false, &factory, &ssl,
-        hostname, stream, Some(&addr), Some(peer), Some(waiter.clone_ref(GIL::python())));
+        evloop, false, &factory, &ssl, hostname, handshake_timeout,
+        stream, Some(&addr), Some(peer), Some(waiter.clone_ref(GIL::python())));
 
     Box::new(
-        waiter.then(move |_| match result {
-            Ok(transport) => future::ok(transport),
-            Err(err) => future::err(err)
-        }))
+        waiter.then(move |res| handshake_result(result, res)))
 }
 
+/// Connect to first reachable address, if all addresses fail then
+/// error of last attempt is returned or errors of all attempts
+/// if `all_errors` is set
 pub fn create_connection(
     factory: PyObject, evloop: TokioEventLoopPtr, addrs: Vec<AddrInfo>,
-    ssl: Option<PyObject>, hostname: Option<PyObject>, waiter: PyFuturePtr)
-                         -> Box<Future<Item=InitializedTransport, Error=io::Error>> {
+    ssl: Option<PyObject>, hostname: Option<PyObject>, handshake_timeout: Option<Duration>,
+    waiter: PyFuturePtr, all_errors: bool)
+    -> Box<Future<Item=InitializedTransport, Error=io::Error>> {
 
     let handle = evloop.as_ref(GIL::python()).get_handle();
-    let conn = connect(addrs, handle.clone());
+    let conn = connect(addrs, handle.clone(), all_errors);
 
     let transport = conn.and_then(
         move |(socket, addr)| {
             let peer = socket.peer_addr().expect("should never happen");
             let result = tcp_transport_factory(
-                evloop, false, &factory, &ssl, hostname,
+                evloop, false, &factory, &ssl, hostname, handshake_timeout,
                 socket, Some(&addr), Some(peer), Some(waiter.clone_ref(GIL::python())));
 
-            waiter.then(move |_| match result {
-                Ok(transport) => future::ok(transport),
-                Err(err) => future::err(err)
-            })
+            waiter.then(move |res| handshake_result(result, res))
         });
     Box::new(transport)
 }
 
-fn connect(addrs: Vec<AddrInfo>, handle: Handle)
+fn connect(addrs: Vec<AddrInfo>, handle: Handle, all_errors: bool)
            -> Box<Future<Item=(TcpStream, AddrInfo), Error=io::Error>>
 {
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let errors2 = errors.clone();
+
     let fut = for_each(addrs).until::<_, _, _, ()>(move |info| {
         let builder = match info.sockaddr {
-            net::SocketAddr::V4(_) =>
-                if let Ok(b) = TcpBuilder::new_v4() {
-                    b
-                } else {
-                    return future::Either::A(future::ok(None))
-                },
-            net::SocketAddr::V6(_) => {
-                if let Ok(b) = TcpBuilder::new_v6() {
-                    let _ = b.only_v6(true);
-                    b
-                } else {
-                    return future::Either::A(future::ok(None))
-                }
-            },
+            net::SocketAddr::V4(_) => TcpBuilder::new_v4(),
+            net::SocketAddr::V6(_) => TcpBuilder::new_v6().map(|b| {
+                let _ = b.only_v6(true);
+                b
+            }),
+        };
+        let builder = match builder {
+            Ok(b) => b,
+            Err(err) => {
+                errors.borrow_mut().push(err);
+                return future::Either::A(future::ok(None))
+            }
         };
 
         let info: AddrInfo = info.clone();
+        let errors = errors.clone();
 
         // convert to tokio TcpStream and connect
         match builder.to_tcp_stream() {
             Ok(stream) =>
                 future::Either::B(
                     TcpStream::connect_stream(stream, &info.sockaddr, &handle)
-                        .then(|res| match res {
+                        .then(move |res| match res {
                             Ok(conn) => future::ok(Some((conn, info))),
-                            Err(_) => future::ok(None)
+                            Err(err) => {
+                                errors.borrow_mut().push(err);
+                                future::ok(None)
+                            }
                         })
                 ),
-            Err(_err) => {
-                //self.errors.push(with_py(|py| err.to_pyerr(py)));
+            Err(err) => {
+                errors.borrow_mut().push(err);
                 future::Either::A(future::ok(None))
             }
         }
-    }).map_err(|e| {
+    }).map_err(move |e| {
         match e {
-            UntilError::NoResult => io::Error::new(
-                io::ErrorKind::ConnectionRefused, "Can not connect to host"),
+            UntilError::NoResult => {
+                let mut errors = errors2.borrow_mut();
+                if all_errors && !errors.is_empty() {
+                    let errors = errors.drain(..).collect();
+                    io::Error::new(io::ErrorKind::Other, ConnectErrors(errors))
+                } else if let Some(err) = errors.pop() {
+                    err
+                } else {
+                    io::Error::new(
+                        io::ErrorKind::ConnectionRefused, "Can not connect to host")
+                }
+            },
             _ => unreachable!(),
         }
     });
@@ -1,18 +1,24 @@
 #![allow(unused_variables)]
 
 use std::io;
+use std::cmp;
+use std::mem;
 use std::net;
+use std::ptr;
 use std::borrow::{Borrow, BorrowMut};
 use std::cell::Cell;
 use std::error::Error;
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
+use std::sync::Mutex;
 use std::fmt::Write;
 use std::str::FromStr;
 use std::path::Path;
 use std::os::raw::c_int;
 use std::os::unix;
 use std::os::unix::io::{RawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::ffi::OsStr;
 
 use libc;
 use pyo3::*;
@@ -23,51 +29,254 @@ use tokio_signal;
 use tokio_signal::unix::Signal;
 use tokio_core::net::TcpStream;
 use tokio_uds::{UnixStream, UnixListener};
+use boxfnonce::SendBoxFnOnce;
 
 use ::{PyFuture, PyFuturePtr, PyTask, PyTaskPtr};
 use addrinfo;
 use client;
-use handle::PyHandle;
+use handle::{PyHandle, PyHandlePtr, PendingTimers, ReadyQueue};
 use fd;
 use fut::{Until, UntilError};
 use http;
 use signals;
+use process;
 use server;
-use utils::{self, with_py, ToPyErr, Classes};
+use utils::{self, with_py, ToPyErr, PyLogger, Classes};
 use pyunsafe::{GIL, Core, Handle, OneshotSender};
 use transport;
 
 
+// subprocess module constants
+const SUBPROCESS_PIPE: c_int = -1;
+const SUBPROCESS_STDOUT: c_int = -2;
+const SUBPROCESS_DEVNULL: c_int = -3;
+
+// socket.type flags on linux
+#[cfg(target_os = "linux")]
+const SOCK_NONBLOCK: i32 = libc::SOCK_NONBLOCK;
+#[cfg(target_os = "linux")]
+const SOCK_CLOEXEC: i32 = libc::SOCK_CLOEXEC;
+#[cfg(not(target_os = "linux"))]
+const SOCK_NONBLOCK: i32 = 0;
+#[cfg(not(target_os = "linux"))]
+const SOCK_CLOEXEC: i32 = 0;
+
+// delay before accept retry on fd exhaustion, in seconds
+const ACCEPT_RETRY_DELAY: u64 = 1;
+
+// one day, in milliseconds
+const MAX_DNS_CACHE_TTL: u64 = 86_400_000;
+
 thread_local!(
     pub static ID: Cell<Option<CoreId>> = Cell::new(None);
 );
 
-pub fn new_event_loop(py: Python) -> PyResult<TokioEventLoopPtr> {
+lazy_static! {
+    // dispositions installed by tokio-signal for SIGTERM and SIGHUP
+    static ref STOP_SIGNAL_ACTIONS: Mutex<HashMap<c_int, libc::sigaction>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Create event loop, `workers` is number of address resolution threads
+pub fn new_event_loop(py: Python, workers: usize) -> PyResult<TokioEventLoopPtr> {
+    check_resolver_workers(py, workers)?;
+
     let core = reactor::Core::new().unwrap();
     let handle = core.handle();
     let remote = core.remote();
     let signals = signals::Signals::new(&handle);
+    let asyncgens = Classes.WeakSet.call(py, NoArgs, None)?;
+    let tasks = Classes.WeakSet.call(py, NoArgs, None)?;
 
     py.init(|t| TokioEventLoop{
         token: t,
         id: Some(core.id()),
+        core_id: core.id(),
         core: Some(Core::new(core)),
         handle: Handle::new(handle),
         remote: remote,
         instant: Instant::now(),
-        lookup: addrinfo::start_workers(3),
+        lookup: addrinfo::start_workers(workers),
+        dns_cache: None,
         runner: None,
+        running: false,
         executor: None,
+        executor_max_workers: None,
         exception_handler: py.None(),
         slow_callback_duration: 100,
         debug: false,
+        debug_stats: DebugStats::default(),
+        handle_stop_signals: false,
         current_task: None,
+        task_counter: Cell::new(0),
+        tasks: tasks,
+        ready: ReadyQueue::new(),
+        timers: PendingTimers::new(),
         signals: signals,
+        signal_handlers: signals::SignalHandlers::default(),
         readers: HashMap::new(),
         writers: HashMap::new(),
+        fd_cache: fd::PyFdCache::new(),
+        children: process::ChildWatcher::new(),
+        asyncgens: asyncgens,
+        asyncgens_shutdown_called: false,
     })
 }
 
+/// Check if loop with `id` is running in current thread
+pub fn is_current_loop(id: &Option<CoreId>) -> bool {
+    match *id {
+        Some(id) => ID.with(|cell| cell.get() == Some(id)),
+        None => false,
+    }
+}
+
+/// Make loop visible to `asyncio.get_running_loop()` in current thread,
+/// returns previously running loop
+fn set_running_loop(py: Python, evloop: PyObject) -> PyObject {
+    let old = Classes.Events.call(py, "_get_running_loop", NoArgs, None)
+        .unwrap_or_else(|_| py.None());
+    let _ = Classes.Events.call(py, "_set_running_loop", (evloop,), None);
+    old
+}
+
+/// Replace asynchronous generator hooks of current thread,
+/// returns previous hooks
+fn set_asyncgen_hooks(py: Python, hooks: PyTuple) -> Option<PyTuple> {
+    let old = Classes.Sys.call(py, "get_asyncgen_hooks", NoArgs, None).ok()
+        .and_then(|old| PyTuple::downcast_into(py, old).ok())?;
+    Classes.Sys.call(py, "set_asyncgen_hooks", hooks, None).ok()?;
+    Some(old)
+}
+
+/// Install loop's asynchronous generator hooks for the time loop runs
+fn set_loop_asyncgen_hooks(py: Python, evloop: &PyObject) -> Option<PyTuple> {
+    let firstiter = evloop.getattr(py, "_asyncgen_firstiter_hook").ok()?;
+    let finalizer = evloop.getattr(py, "_asyncgen_finalizer_hook").ok()?;
+    set_asyncgen_hooks(py, PyTuple::new(py, &[firstiter, finalizer]))
+}
+
+/// Resolver pool needs at least one worker
+fn check_resolver_workers(py: Python, num: usize) -> PyResult<()> {
+    if num == 0 {
+        Err(PyErr::new::<exc::ValueError, _>(
+            py, "number of resolver workers must be greater than zero"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Submit a coroutine object to the event loop, asyncio's run_coroutine_threadsafe.
+///
+/// Can be called from any thread. Return a concurrent.futures.Future
+/// to access the result. Cancellation of either future cancels the other.
+pub fn run_coroutine_threadsafe(py: Python, coro: PyObject, evloop: PyObject)
+                                -> PyResult<PyObject> {
+    let evloop = TokioEventLoopPtr::downcast_into(py, evloop)?;
+    if !utils::iscoroutine(&coro) {
+        return Err(PyErr::new::<exc::TypeError, _>(py, "A coroutine object is required"))
+    }
+    if evloop.as_ref(py).id.is_none() {
+        return Err(PyErr::new::<exc::RuntimeError, _>(py, "Event loop is closed"))
+    }
+
+    let fut = Classes.ConcurrentFuture.call(py, NoArgs, None)?;
+    let waiter = fut.clone_ref(py);
+    let ev = evloop.clone_ref(py);
+
+    // create task on the loop thread
+    evloop.as_ref(py).remote.spawn(move |_| {
+        let gil = Python::acquire_gil();
+        let py = gil.python();
+
+        // future got cancelled before task started
+        match waiter.call_method(py, "cancelled", NoArgs, None)
+            .and_then(|cancelled| cancelled.is_true(py))
+        {
+            Ok(false) => (),
+            Ok(true) => return future::ok(()),
+            Err(err) => {
+                err.into_log(py, "run_coroutine_threadsafe error");
+                return future::ok(())
+            }
+        }
+
+        match PyTask::new(py, coro, ev.as_ref(py)) {
+            Ok(task) => {
+                // cancellation of concurrent future cancels task
+                Classes.Helpers.call(
+                    py, "cancel_on_concurrent_cancel",
+                    (ev.clone_ref(py), task.clone_ref(py), waiter.clone_ref(py)), None)
+                    .into_log(py, "run_coroutine_threadsafe error");
+
+                let t = task.clone_ref(py);
+                task.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |result| {
+                    let gil = Python::acquire_gil();
+                    let py = gil.python();
+
+                    let cancelled = t.as_ref(py).is_cancelled();
+                    set_concurrent_future_state(py, &waiter, cancelled, result)
+                        .into_log(py, "run_coroutine_threadsafe error");
+                    py.release(t);
+                }));
+            },
+            Err(err) => {
+                set_concurrent_future_state(py, &waiter, false, Err(err))
+                    .into_log(py, "run_coroutine_threadsafe error");
+            }
+        }
+        future::ok(())
+    });
+
+    Ok(fut)
+}
+
+/// Copy task state to concurrent.futures.Future, cancelled task cancels future
+fn set_concurrent_future_state(py: Python, fut: &PyObject,
+                               cancelled: bool, result: PyResult<PyObject>) -> PyResult<()> {
+    if cancelled {
+        fut.call_method(py, "cancel", NoArgs, None)?;
+    }
+    if !fut.call_method(py, "set_running_or_notify_cancel", NoArgs, None)?.is_true(py)? {
+        return Ok(())
+    }
+    match result {
+        Ok(res) => fut.call_method(py, "set_result", (res,), None)?,
+        Err(mut err) => fut.call_method(py, "set_exception", (err.instance(py),), None)?,
+    };
+    Ok(())
+}
+
+/// Another loop must not be running in current thread
+fn check_running_loop(py: Python) -> PyResult<()> {
+    let running = Classes.Events.call(py, "_get_running_loop", NoArgs, None)?;
+    if running != py.None() {
+        Err(PyErr::new::<exc::RuntimeError, _>(
+            py, "Cannot run the event loop while another loop is running"))
+    } else {
+        Ok(())
+    }
+}
+
+// inet address with host name instead of ip address literal
+fn needs_resolution(py: Python, sock: &PyObject, address: &PyObject) -> bool {
+    let family = match sock.getattr(py, "family").and_then(|f| f.extract::<c_int>(py)) {
+        Ok(family) => family,
+        Err(_) => return false,
+    };
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        return false
+    }
+    let host = match PyTuple::downcast_from(py, address) {
+        Ok(address) if address.len(py) > 0 => address.get_item(py, 0),
+        _ => return false,
+    };
+    match PyString::downcast_from(py, &host) {
+        Ok(host) => net::IpAddr::from_str(&host.to_string_lossy(py)).is_err(),
+        Err(_) => false,
+    }
+}
+
 pub fn thread_safe_check(py: Python, id: &Option<CoreId>) -> Option<PyErr> {
     if let &Some(id) = id {
         let check = ID.with(|cell| {
@@ -97,25 +306,169 @@ enum RunStatus {
     PyRes(PyResult<PyObject>),
 }
 
+/// Restores previous dispositions of SIGTERM and SIGHUP when run ends.
+///
+/// tokio-signal installs its handler once per process and never removes it,
+/// stop signals would be ignored after loop stops otherwise. Signals which
+/// got handler with `add_signal_handler` during run are left alone.
+struct StopSignalsGuard {
+    prev: Vec<(c_int, libc::sigaction)>,
+    handlers: signals::SignalHandlers,
+}
+
+impl Drop for StopSignalsGuard {
+    fn drop(&mut self) {
+        for &(sig, ref action) in self.prev.iter() {
+            if !self.handlers.contains(sig) {
+                unsafe { libc::sigaction(sig, action, ptr::null_mut()); }
+            }
+        }
+    }
+}
+
+/// Resolves with signal number when any of stop signals is received,
+/// SIGINT always, SIGTERM and SIGHUP if `all` is set. Signals with
+/// user `handlers` do not stop the loop.
+fn stop_signals(core: &mut Core, handle: &reactor::Handle, all: bool,
+                handlers: &signals::SignalHandlers)
+                -> (Box<Future<Item=c_int, Error=()>>, StopSignalsGuard)
+{
+    let mut signals: Vec<Box<Future<Item=c_int, Error=()>>> = Vec::new();
+    let mut guard = StopSignalsGuard { prev: Vec::new(), handlers: handlers.clone() };
+
+    // SIGINT
+    if let Ok(ctrlc) = core.0.run(tokio_signal::ctrl_c(handle)) {
+        signals.push(Box::new(
+            ctrlc.into_future().map(|_| libc::SIGINT).map_err(|_| ())));
+    }
+
+    if all {
+        for sig in &[libc::SIGTERM, libc::SIGHUP] {
+            let sig = *sig;
+            if handlers.contains(sig) {
+                continue
+            }
+
+            // remember current disposition, reinstall handler of previous run
+            let action = STOP_SIGNAL_ACTIONS.lock().unwrap().get(&sig).cloned();
+            unsafe {
+                let mut prev: libc::sigaction = mem::zeroed();
+                let res = match action {
+                    Some(ref action) => libc::sigaction(sig, action, &mut prev),
+                    None => libc::sigaction(sig, ptr::null(), &mut prev),
+                };
+                if res == 0 {
+                    guard.prev.push((sig, prev));
+                }
+            }
+
+            if let Ok(signal) = core.0.run(Signal::new(sig, handle)) {
+                if action.is_none() {
+                    unsafe {
+                        let mut action: libc::sigaction = mem::zeroed();
+                        if libc::sigaction(sig, ptr::null(), &mut action) == 0 {
+                            STOP_SIGNAL_ACTIONS.lock().unwrap().insert(sig, action);
+                        }
+                    }
+                }
+                let handlers = handlers.clone();
+                signals.push(Box::new(
+                    signal.filter(move |_| !handlers.contains(sig))
+                        .into_future().map(move |_| sig).map_err(|_| ())));
+            }
+        }
+    }
+
+    // signal handlers can not be installed, never resolves
+    if signals.is_empty() {
+        return (Box::new(future::empty()), guard)
+    }
+    (Box::new(future::select_all(signals).map(|(sig, _, _)| sig).map_err(|_| ())), guard)
+}
+
+
+/// Connect to abstract namespace UNIX socket, name starts with NUL byte
+#[cfg(target_os = "linux")]
+fn connect_abstract(name: &[u8]) -> io::Result<unix::net::UnixStream> {
+    unsafe {
+        let mut addr: libc::sockaddr_un = mem::zeroed();
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        if name.len() > addr.sun_path.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput, "path must be shorter than SUN_LEN"))
+        }
+        for (dst, src) in addr.sun_path.iter_mut().zip(name.iter()) {
+            *dst = *src as libc::c_char;
+        }
+        let len = mem::size_of::<libc::sa_family_t>() + name.len();
+
+        let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0);
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        if libc::connect(fd, &addr as *const _ as *const libc::sockaddr,
+                         len as libc::socklen_t) < 0 {
+            let err = io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err)
+        }
+        Ok(unix::net::UnixStream::from_raw_fd(fd))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn connect_abstract(name: &[u8]) -> io::Result<unix::net::UnixStream> {
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput, "abstract namespace sockets are supported on Linux only"))
+}
+
+
+// handles execution stats, collected in debug mode only
+#[derive(Default)]
+struct DebugStats {
+    handles: u64,
+    scheduled: u64,
+    turns: u64,
+    turn: u64,
+    turn_handles: u64,
+    max_turn_handles: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
 
-#[py::class]
+#[py::class(weakref)]
 pub struct TokioEventLoop {
     token: PyToken,
     id: Option<CoreId>,
+    core_id: CoreId,
     core: Option<Core>,
     handle: Handle,
     remote: Remote,
     instant: Instant,
     lookup: addrinfo::LookupWorkerSender,
+    dns_cache: Option<addrinfo::LookupCache>,
     runner: Option<oneshot::Sender<PyResult<()>>>,
+    running: bool,
     executor: Option<PyObject>,
+    executor_max_workers: Option<usize>,
     exception_handler: PyObject,
     slow_callback_duration: u64,
     debug: bool,
+    debug_stats: DebugStats,
+    handle_stop_signals: bool,
     current_task: Option<PyObject>,
+    task_counter: Cell<usize>,
+    tasks: PyObject,
+    ready: ReadyQueue,
+    timers: PendingTimers,
     signals: sync::mpsc::UnboundedSender<signals::SignalsMessage>,
+    signal_handlers: signals::SignalHandlers,
     readers: HashMap<c_int, OneshotSender<()>>,
     writers: HashMap<c_int, OneshotSender<()>>,
+    fd_cache: fd::PyFdCache,
+    children: process::ChildWatcher,
+    asyncgens: PyObject,
+    asyncgens_shutdown_called: bool,
 }
 
 #[py::ptr(TokioEventLoop)]
@@ -134,6 +487,21 @@ impl TokioEventLoop {
         }
     }
 
+    //
+    // Return a set of not yet finished tasks of the loop.
+    //
+    fn all_tasks(&self, py: Python) -> PyResult<PyObject>
+    {
+        let mut tasks = Vec::new();
+        for task in self.tasks.iter(py)? {
+            let task = task?;
+            if !task.call_method(py, "done", NoArgs, None)?.is_true(py)? {
+                tasks.push(task);
+            }
+        }
+        Classes.Set.call(py, (PyList::new(py, &tasks),), None)
+    }
+
     //
     // Create a Future object attached to the loop.
     //
@@ -148,12 +516,39 @@ impl TokioEventLoop {
         PyFuture::new(py, self.to_inst_ptr())
     }
 
+    //
+    // Create a future which gets resolved with value on the next loop
+    // iteration, same as create_future() with call_soon(fut.set_result, value).
+    // Unlike done future it forces a yield point when awaited.
+    //
+    fn create_future_resolved(&self, py: Python, value: PyObject) -> PyResult<PyFuturePtr>
+    {
+        let fut = self.create_future(py)?;
+
+        let callback = Classes.Helpers.get(py, "set_result_unless_cancelled")?;
+        let h = PyHandle::new(
+            py, &self, callback, (fut.clone_ref(py), value).into_tuple(py))?;
+        h.call_soon(py, &self);
+
+        Ok(fut)
+    }
+
+    //
+    // Wrap asyncio.Future object into a loop future (for tests)
+    //
+    fn _wrap_future(&self, py: Python, fut: PyObject) -> PyResult<PyFuturePtr>
+    {
+        PyFuture::from_fut(py, self.to_inst_ptr(), fut)
+    }
+
     //
     // Schedule a coroutine object.
     //
-    // Return a task object.
+    // Return a task object. If name is not provided, task gets
+    // default "Task-N" name.
     //
-    fn create_task(&self, py: Python, coro: PyObject) -> PyResult<PyObject>
+    fn create_task(&self, py: Python, coro: PyObject,
+                   name: Option<PyObject>) -> PyResult<PyObject>
     {
         if self.debug {
             if let Some(err) = thread_safe_check(py, &self.id) {
@@ -164,7 +559,13 @@ impl TokioEventLoop {
         if let Ok(fut) = PyFuture::downcast_from(py, &coro) {
             return Ok(fut.to_inst_ptr().into())
         }
-        Ok(PyTask::new(py, coro, &self)?.into())
+        let task = PyTask::new(py, coro, &self)?;
+        if let Some(name) = name {
+            if name != py.None() {
+                task.as_mut(py).set_name(py, name)?;
+            }
+        }
+        Ok(task.into())
     }
 
     //
@@ -316,18 +717,19 @@ impl TokioEventLoop {
         } else {
             // get params
             let callback = args.get_item(py, 1);
+            let when = utils::parse_seconds(py, "when", args.get_item(py, 0))?;
 
             // create handle and schedule work
             let mut h = PyHandle::new(
                 py, &self, callback, PyTuple::new(py, &args.as_slice(py)[2..]))?;
 
-            // calculate delay
-            if let Some(when) = utils::parse_seconds(py, "when", args.get_item(py, 0))? {
-                let time = when - self.instant.elapsed();
-
-                h.call_later(py, self, time);
-            } else {
-                h.call_soon(py, self);
+            // calculate delay against single snapshot of loop time,
+            // time in the past is called as soon as possible
+            let now = self.instant.elapsed();
+            match when.and_then(|when| when.checked_sub(now)) {
+                Some(delay) if delay > Duration::new(0, 0) =>
+                    h.call_later(py, self, delay),
+                _ => h.call_soon(py, self),
             }
             Ok(h.into())
         }
@@ -382,6 +784,7 @@ impl TokioEventLoop {
 
             // register signal handler
             let _ = self.signals.send(signals::SignalsMessage::Add(sig, signal, h));
+            self.signal_handlers.insert(sig);
 
             Ok(())
         }
@@ -395,6 +798,7 @@ impl TokioEventLoop {
     {
         // un-register signal handler
         let _ = self.signals.send(signals::SignalsMessage::Remove(sig));
+        self.signal_handlers.remove(sig);
 
         Ok(true)
     }
@@ -412,6 +816,7 @@ impl TokioEventLoop {
             // create handle
             let h = PyHandle::new(
                 py, &self, callback, PyTuple::new(py, &args.as_slice(py)[2..]))?;
+            self.fd_cache.remove(py, fd, self.href());
             match fd::PyFdHandle::reader(fd, self.href(), h) {
                 Ok(tx) => {
                     self.readers.insert(fd, OneshotSender::new(tx));
@@ -424,6 +829,7 @@ impl TokioEventLoop {
 
     fn _remove_reader(&mut self, py: Python, fd: c_int) -> PyResult<bool>
     {
+        self.fd_cache.remove(py, fd, self.href());
         if let Some(tx) = self.readers.remove(&fd) {
             let _ = tx.send(());
             Ok(true)
@@ -446,6 +852,7 @@ impl TokioEventLoop {
             // create handle
             let h = PyHandle::new(
                 py, &self, callback, PyTuple::new(py, &args.as_slice(py)[2..]))?;
+            self.fd_cache.remove(py, fd, self.href());
             match fd::PyFdHandle::writer(fd, self.href(), h) {
                 Ok(tx) => {
                     self.writers.insert(fd, OneshotSender::new(tx));
@@ -457,6 +864,7 @@ impl TokioEventLoop {
     }
 
     fn _remove_writer(&mut self, py: Python, fd: c_int) -> PyResult<bool> {
+        self.fd_cache.remove(py, fd, self.href());
         if let Some(tx) = self.writers.remove(&fd) {
             let _ = tx.send(());
             Ok(true)
@@ -500,18 +908,28 @@ impl TokioEventLoop {
     // The maximum amount of data to be received at once is specified by
     // nbytes.
     //
+    // Data is read with single recv() call, for datagram and seqpacket
+    // sockets result is exactly one datagram, possibly truncated to nbytes.
+    // In debug mode datagram that fills whole buffer is reported
+    // as possibly truncated.
+    //
+    // sock could be raw file descriptor, data is read with read() call.
+    //
     // This method is a coroutine.
     fn sock_recv(&self, py: Python, sock: PyObject, n: PyObject) -> PyResult<PyFuturePtr>
     {
+        if let Ok(fd) = sock.extract::<c_int>(py) {
+            return self.fd_recv(py, fd, sock, n.extract(py)?)
+        }
         let _ = self.is_socket_nonblocking(py, &sock)?;
+        let check_trunc = self.debug && self._is_dgram_socket(py, &sock)?;
 
         // create readiness stream
         let fd = {
             let fd = self.get_socket_fd(py, &sock)?;
-            match fd::PyFdReadable::new(fd, self.href()) {
-                Ok(fd) => fd,
-                Err(err) => return Ok(
-                    PyFuture::done_res(py, self.to_inst_ptr(), Err(err.to_pyerr(py)))?),
+            match self.fd_cache.get(py, fd, &sock, self.href()) {
+                Ok(io) => fd::PyFdReadable::shared(io, self.href()),
+                Err(err) => return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?),
             }
         };
 
@@ -544,6 +962,17 @@ impl TokioEventLoop {
                     }
                 }
                 Ok(result) => {
+                    if check_trunc {
+                        // rest of datagram is discarded by recv()
+                        let len = PyBytes::downcast_from(py, &result)
+                            .map(|b| b.data(py).len()).unwrap_or(0);
+                        if let Ok(size) = n.extract::<usize>(py) {
+                            if len > 0 && len == size {
+                                warn!("Datagram received by sock_recv() \
+                                       is possibly truncated to {} bytes", size);
+                            }
+                        }
+                    }
                     fut.set(py, Ok(result));
                     future::ok(Some(()))
                 }
@@ -558,7 +987,7 @@ impl TokioEventLoop {
             };
         });
 
-        self.href().spawn(f);
+        self.spawn_sock_op(py, &fut, f);
 
         Ok(fut)
     }
@@ -571,10 +1000,15 @@ impl TokioEventLoop {
     // raised, and there is no way to determine how much data, if any, was
     // successfully processed by the receiving end of the connection.
     //
+    // sock could be raw file descriptor, data is sent with write() calls.
+    //
     // This method is a coroutine.
     fn sock_sendall(&self, py: Python, sock: PyObject, data: PyObject)
                     -> PyResult<PyFuturePtr>
     {
+        if let Ok(fd) = sock.extract::<c_int>(py) {
+            return self.fd_sendall(py, fd, sock, data)
+        }
         let _ = self.is_socket_nonblocking(py, &sock)?;
 
         // data is empty, nothing to do
@@ -585,10 +1019,9 @@ impl TokioEventLoop {
         // create readyness stream for write operation
         let fd = {
             let fd = self.get_socket_fd(py, &sock)?;
-            match fd::PyFdWritable::new(fd, self.href()) {
-                Ok(fd) => fd,
-                Err(err) => return Ok(
-                    PyFuture::done_res(py, self.to_inst_ptr(), Err(err.to_pyerr(py)))?),
+            match self.fd_cache.get(py, fd, &sock, self.href()) {
+                Ok(io) => fd::PyFdWritable::shared(io, self.href()),
+                Err(err) => return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?),
             }
         };
 
@@ -596,7 +1029,12 @@ impl TokioEventLoop {
         let fut = PyFuture::new(py, self.to_inst_ptr())?;
         let fut_ready = fut.clone_ref(py);
         let fut_err = fut.clone_ref(py);
-        let wrp = PyList::new(py, &[&data]);
+
+        // use memoryview so partial sends do not copy remaining data
+        let view = Classes.MemoryView.call(py, (data,), None)?
+            .call_method(py, "cast", ("B",), None)?;
+        let len = view.len(py)?;
+        let mut offset: usize = 0;
 
         let f = fd.until(move |_| {
             let gil = Python::acquire_gil();
@@ -607,8 +1045,16 @@ impl TokioEventLoop {
                 return future::ok(Some(()));
             }
 
-            let data = wrp.get_item(py, 0);
-            let res = sock.call_method(py, "send", (data.clone_ref(py),), None);
+            let data = if offset == 0 {
+                view.clone_ref(py)
+            } else {
+                let slice = PySlice::new(py, offset as isize, len as isize, 1);
+                match view.call_method(py, "__getitem__", (slice,), None) {
+                    Ok(data) => data,
+                    Err(e) => return future::err(e),
+                }
+            };
+            let res = sock.call_method(py, "send", (data,), None);
 
             match res {
                 Err(err) => {
@@ -622,24 +1068,15 @@ impl TokioEventLoop {
                     }
                 }
                 Ok(result) => {
-                    if let Ok(n) = result.extract::<c_int>(py) {
-                        let len = data.len(py).unwrap() as c_int;
-                        if n == len {
+                    if let Ok(n) = result.extract::<usize>(py) {
+                        offset += n;
+                        if offset >= len {
                             // all data is sent
                             fut.set(py, Ok(py.None()));
                             future::ok(Some(()))
                         } else {
-                            // some data got send
-                            let slice = PySlice::new(py, n as isize, len as isize, 1);
-                            match data.call_method(py, "__getitem__", (slice,), None) {
-                                Ok(data) => {
-                                    match wrp.set_item(py, 0, data) {
-                                        Ok(_) => future::ok(None),
-                                        Err(e) => future::err(e)
-                                    }
-                                },
-                                Err(e) => future::err(e)
-                            }
+                            // some data got send, advance offset
+                            future::ok(None)
                         }
                     } else {
                         // exception
@@ -658,25 +1095,45 @@ impl TokioEventLoop {
             };
         });
 
-        self.href().spawn(f);
+        self.spawn_sock_op(py, &fut, f);
         Ok(fut)
     }
 
     // Connect to a remote socket at address.
     //
+    // Host name of inet address is resolved first, resolved addresses
+    // are tried in turn until one connects. If all attempts fail,
+    // error of each attempt is reported.
+    //
     // This method is a coroutine.
     fn sock_connect(&self, py: Python, sock: PyObject, address: PyObject)
-                    -> PyResult<PyFuturePtr>
+                    -> PyResult<PyObject>
     {
         let _ = self.is_socket_nonblocking(py, &sock)?;
 
-        //if not hasattr(socket, 'AF_UNIX') or sock.family != socket.AF_UNIX:
-        //resolved = base_events._ensure_resolved(
-        //    address, family=sock.family, proto=sock.proto, loop=self)
-        //    if not resolved.done():
-        //yield from resolved
-        //    _, _, _, _, address = resolved.result()[0]
+        if needs_resolution(py, &sock, &address) {
+            let coro = Classes.Helpers.call(
+                py, "sock_connect", (self.to_inst_ptr(), sock, address), None)?;
+            return Ok(PyTask::new(py, coro, &self)?.into())
+        }
+        Ok(self._sock_connect(py, sock, address)?.into())
+    }
+
+    //
+    // Drop cached reactor registration of socket, helper for sock_connect()
+    // fd of socket gets replaced before next connection attempt
+    //
+    fn _sock_forget(&self, py: Python, sock: PyObject) -> PyResult<()> {
+        self.take_socket_fd(py, &sock)?;
+        Ok(())
+    }
 
+    //
+    // Connect to resolved address, helper for sock_connect()
+    //
+    fn _sock_connect(&self, py: Python, sock: PyObject, address: PyObject)
+                     -> PyResult<PyFuturePtr>
+    {
         // try to connect
         let res = sock.call_method(py, "connect", (address.clone_ref(py),), None);
 
@@ -691,10 +1148,9 @@ impl TokioEventLoop {
                     return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?);
                 }
                 let fd = self.get_socket_fd(py, &sock)?;
-                match fd::PyFdWritable::new(fd, self.href()) {
-                    Err(err) => return Ok(
-                        PyFuture::done_res(py, self.to_inst_ptr(), Err(err.to_pyerr(py)))?),
-                    Ok(fd) => fd
+                match self.fd_cache.get(py, fd, &sock, self.href()) {
+                    Err(err) => return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?),
+                    Ok(io) => fd::PyFdWritable::shared(io, self.href()),
                 }
             }
         };
@@ -751,7 +1207,7 @@ impl TokioEventLoop {
             };
         });
 
-        self.href().spawn(f);
+        self.spawn_sock_op(py, &fut, f);
         Ok(fut)
     }
 
@@ -766,64 +1222,8 @@ impl TokioEventLoop {
     fn sock_accept(&self, py: Python, sock: PyObject) -> PyResult<PyFuturePtr> {
         let _ = self.is_socket_nonblocking(py, &sock)?;
 
-        // create readiness stream
-        let fd = {
-            let fd = self.get_socket_fd(py, &sock)?;
-            match fd::PyFdReadable::new(fd, self.href()) {
-                Ok(fd) => fd,
-                Err(err) => return Ok(
-                    PyFuture::done_res(py, self.to_inst_ptr(), Err(err.to_pyerr(py)))?),
-            }
-        };
-
-        // wait until sock get ready
         let fut = PyFuture::new(py, self.to_inst_ptr())?;
-        let fut_err = fut.clone_ref(py);
-        let fut_ready = fut.clone_ref(py);
-
-        let f = fd.until(move |_| {
-            let gil = Python::acquire_gil();
-            let py = gil.python();
-            let mut fut = fut_ready.as_mut(py);
-
-            // fut cancelled
-            if fut.is_cancelled() {
-                return future::ok(Some(()));
-            }
-            let res = sock.call_method(py, "accept", NoArgs, None);
-
-            match res {
-                Err(err) => {
-                    if err.matches(
-                        py, (py.get_type::<exc::BlockingIOError>(),
-                             py.get_type::<exc::InterruptedError>())) {
-                        // skill blocking, continue
-                        future::ok(None)
-                    } else {
-                        future::err(err)
-                    }
-                }
-                Ok(result) => {
-                    if let Ok(result) = PyTuple::downcast_from(py, &result) {
-                        let _ = result.get_item(py, 0).call_method(
-                            py, "setblocking", (false,), None);
-                    }
-                    
-                    fut.set(py, Ok(result));
-                    future::ok(Some(()))
-                }
-            }
-        }).map_err(move |err| {
-            match err {
-                UntilError::Error(err) => {
-                    // actual python exception
-                    fut_err.with_mut(|py, fut| fut.set(py, Err(err)));
-                },
-                _ => unreachable!(),
-            };
-        });
-
-        self.href().spawn(f);
+        self.sock_accept_wait(py, sock, fut.clone_ref(py));
         Ok(fut)
     }
 
@@ -843,13 +1243,22 @@ impl TokioEventLoop {
     }
 
     fn is_running(&self, py: Python) -> PyResult<bool> {
-        Ok(self.runner.is_some())
+        Ok(self.running)
     }
 
     fn is_closed(&self, py: Python) -> PyResult<bool> {
         Ok(self.id.is_none())
     }
 
+    //
+    // Return identifier of the loop's reactor.
+    //
+    // Identifier stays the same after the loop is closed.
+    //
+    fn core_id(&self, py: Python) -> PyResult<String> {
+        Ok(format!("{:?}", self.core_id))
+    }
+
     //
     // Close the event loop. The event loop must not be running.
     //
@@ -869,20 +1278,68 @@ impl TokioEventLoop {
             let _ = executor.call_method(py, "shutdown", NoArgs, Some(&kwargs));
         }
 
+        // deregister cached sock_* fds, reactor is gone after this
+        self.fd_cache.clear(py, self.href());
+
+        // exit of running children is not reported anymore
+        self.children.clear();
+
         // drop CORE
         self.core.take();
 
-        if let Some(id) = self.id.take() {
-            ID.with(|mut cell| {
-                let curr = if let Some(gid) = cell.borrow().get() {
-                    gid == id
-                } else {
-                    false
-                };
-                if curr {
-                    cell.borrow_mut().take();
-                }
-            });
+        // pending tasks never complete
+        self.tasks.call_method(py, "clear", NoArgs, None)?;
+
+        if is_current_loop(&self.id) {
+            ID.with(|cell| cell.set(None));
+        }
+        self.id.take();
+        Ok(())
+    }
+
+    //
+    // Close all asynchronous generators iterated in this loop.
+    //
+    // Generators get registered by sys.set_asyncgen_hooks() hooks which
+    // are installed while the loop runs. Generator started after this call
+    // is reported with ResourceWarning.
+    //
+    // This method is a coroutine.
+    //
+    fn shutdown_asyncgens(&mut self, py: Python) -> PyResult<PyObject> {
+        self.asyncgens_shutdown_called = true;
+
+        let mut agens = Vec::new();
+        for agen in self.asyncgens.iter(py)? {
+            agens.push(agen?);
+        }
+        self.asyncgens.call_method(py, "clear", NoArgs, None)?;
+
+        Classes.Helpers.call(
+            py, "shutdown_asyncgens", (self.to_inst_ptr(), PyList::new(py, &agens)), None)
+    }
+
+    fn _asyncgen_firstiter_hook(&self, py: Python, agen: PyObject) -> PyResult<()> {
+        if self.asyncgens_shutdown_called {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item(py, "source", self.to_inst_ptr())?;
+            Classes.Warnings.call(
+                py, "warn", (format!("asynchronous generator {} was scheduled after \
+                                      loop.shutdown_asyncgens() call", agen.repr(py)?),
+                             Classes.ResourceWarning.clone_ref(py)), Some(&kwargs))?;
+        }
+        self.asyncgens.call_method(py, "add", (agen,), None)?;
+        Ok(())
+    }
+
+    // generator is garbage collected before it is exhausted, close it in loop
+    fn _asyncgen_finalizer_hook(&self, py: Python, agen: PyObject) -> PyResult<()> {
+        self.asyncgens.call_method(py, "discard", (agen.clone_ref(py),), None)?;
+        if self.id.is_some() {
+            let evloop: PyObject = self.to_inst_ptr().into();
+            let aclose = agen.call_method(py, "aclose", NoArgs, None)?;
+            self.call_soon_threadsafe(
+                py, PyTuple::new(py, &[evloop.getattr(py, "create_task")?, aclose]), None)?;
         }
         Ok(())
     }
@@ -915,12 +1372,33 @@ impl TokioEventLoop {
                 ex
             } else {
                 let concurrent = py.import("concurrent.futures")?;
-                self.executor = Some(concurrent.call(py, "ThreadPoolExecutor", NoArgs, None)?);
+                let kwargs = PyDict::new(py);
+                if let Some(max_workers) = self.executor_max_workers {
+                    kwargs.set_item(py, "max_workers", max_workers)?;
+                }
+                self.executor = Some(
+                    concurrent.call(py, "ThreadPoolExecutor", NoArgs, Some(&kwargs))?);
                 self.executor.as_ref().unwrap()
             };
             // submit function
             executor.call_method(py, "submit", args, None)?
         } else {
+            // in debug mode check that function and arguments
+            // can be sent to worker process
+            if self.debug {
+                let concurrent = py.import("concurrent.futures")?;
+                let pool = PyType::extract(py, &concurrent.get(py, "ProcessPoolExecutor")?)?;
+                if pool.is_instance(py, &executor) {
+                    let pickle = py.import("pickle")?;
+                    if let Err(mut err) = pickle.call(py, "dumps", (args.clone_ref(py),), None) {
+                        return Err(PyErr::new::<exc::TypeError, _>(
+                            py, format!("function and arguments must be picklable \
+                                         to be executed in ProcessPoolExecutor: {:?}",
+                                        err.instance(py))))
+                    }
+                }
+            }
+
             // submit function
             executor.call_method(py, "submit", args, None)?
         };
@@ -936,10 +1414,28 @@ impl TokioEventLoop {
         Ok(())
     }
 
+    //
+    // Set number of worker threads of default executor.
+    //
+    // Setting is used only if default executor is not created yet
+    // and is ignored if executor is set with set_default_executor().
+    //
+    fn set_default_executor_max_workers(&mut self, py: Python, max_workers: usize)
+                                        -> PyResult<()> {
+        if max_workers == 0 {
+            return Err(PyErr::new::<exc::ValueError, _>(
+                py, "max_workers must be greater than 0"))
+        }
+        self.executor_max_workers = Some(max_workers);
+        Ok(())
+    }
+
     /// return list of tuples
     /// item = (family, type, proto, canonname, sockaddr)
     /// sockaddr(IPV4) = (address, port)
     /// sockaddr(IPV6) = (address, port, flow info, scope id)
+    ///
+    /// AI_ADDRCONFIG | AI_V4MAPPED flags are used unless flags are specified
     #[args(args="args", kw="kwargs")]
     fn getaddrinfo(&self, py: Python, args: PyTuple, kwargs: Option<&PyDict>)
                    -> PyResult<PyFuturePtr> {
@@ -984,7 +1480,7 @@ impl TokioEventLoop {
         let mut family: i32 = 0;
         let mut socktype: i32 = 0;
         let mut _proto: i32 = 0;
-        let mut flags: i32 = 0;
+        let mut flags: i32 = addrinfo::AI_DEFAULT;
 
         if let Some(kwargs) = kwargs {
             if let Some(f) = kwargs.get_item(py, "family") {
@@ -1009,50 +1505,64 @@ impl TokioEventLoop {
         let fut_err = res.clone_ref(py);
 
         // lookup process future
-        let lookup = addrinfo::lookup(
-            &self.lookup, host, port, family, flags, addrinfo::SocketType::from_int(socktype));
+        let lookup = self.lookup_addr(
+            host, port, family, flags, addrinfo::SocketType::from_int(socktype));
+
+        // abandon lookup if result future gets cancelled
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        res.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+            let _ = cancel_tx.send(());
+        }));
 
         // convert addr info to python comaptible  values
-        let process = lookup.and_then(move |result| {
-            fut.with_mut(|py, fut| {
-                match result {
-                    Err(ref err) => fut.set(py, Err(err.to_pyerr(py))),
-                    Ok(ref addrs) => {
-                        // create socket.gethostname compatible result
-                        let list = PyList::empty(py);
-                        for info in addrs {
-                            let addr = match info.sockaddr {
-                                net::SocketAddr::V4(addr) => {
-                                    (format!("{}", addr.ip()), addr.port()).into_tuple(py)
-                                }
-                                net::SocketAddr::V6(addr) => {
-                                    (format!("{}", addr.ip()),
-                                     addr.port(), addr.flowinfo(), addr.scope_id(),
-                                    ).into_tuple(py)
-                                },
-                            };
-
-                            let cname = match info.canonname {
-                                Some(ref cname) => PyString::new(py, cname.as_str()),
-                                None => PyString::new(py, ""),
-                            };
-
-                            let item: PyObject = (info.family.to_int(),
-                                                  info.socktype.to_int(),
-                                                  info.protocol.to_int(),
-                                                  cname, addr).into_tuple(py).into();
-                            list.insert_item(py, list.len(py) as isize, item)
-                                .expect("Except to succeed");
-                        }
-                        fut.set(py, Ok(list.into()));
-                    },
-                }
-            });
+        let process = lookup.select2(cancel_rx).then(move |res| {
+            match res {
+                Ok(future::Either::A((result, _))) => fut.with_mut(|py, fut| {
+                    if fut.is_cancelled() {
+                        return
+                    }
+                    match result {
+                        Err(ref err) => fut.set(py, Err(err.to_pyerr(py))),
+                        Ok(ref addrs) => {
+                            // create socket.gethostname compatible result
+                            let list = PyList::empty(py);
+                            for info in addrs {
+                                let addr = match info.sockaddr {
+                                    net::SocketAddr::V4(addr) => {
+                                        (format!("{}", addr.ip()), addr.port()).into_tuple(py)
+                                    }
+                                    net::SocketAddr::V6(addr) => {
+                                        (format!("{}", addr.ip()),
+                                         addr.port(), addr.flowinfo(), addr.scope_id(),
+                                        ).into_tuple(py)
+                                    },
+                                };
+
+                                let cname = match info.canonname {
+                                    Some(ref cname) => PyString::new(py, cname.as_str()),
+                                    None => PyString::new(py, ""),
+                                };
+
+                                let item: PyObject = (info.family.to_int(),
+                                                      info.socktype.to_int(),
+                                                      info.protocol.to_int(),
+                                                      cname, addr).into_tuple(py).into();
+                                list.insert_item(py, list.len(py) as isize, item)
+                                    .expect("Except to succeed");
+                            }
+                            fut.set(py, Ok(list.into()));
+                        },
+                    }
+                }),
+                Err(future::Either::A(_)) => fut_err.with_mut(|py, fut| {
+                    let err = PyErr::new::<exc::RuntimeError, _>(py, "Unknown runtime error");
+                    fut.set(py, Err(err));
+                }),
+                // result future is cancelled, drop lookup
+                _ => (),
+            }
             future::ok(())
-        }).map_err(move |err| fut_err.with_mut(|py, fut| {
-            let err = PyErr::new::<exc::RuntimeError, _>(py, "Unknown runtime error");
-            fut.set(py, Err(err));
-        }));
+        });
 
         // start task
         self.handle.spawn(process);
@@ -1071,6 +1581,8 @@ impl TokioEventLoop {
 
     fn connect_read_pipe(&self, py: Python, protocol_factory: PyObject, pipe: PyObject)
                           -> PyResult<PyFuturePtr> {
+        self.set_pipe_nonblocking(py, &pipe)?;
+
         let protocol = protocol_factory.call(py, NoArgs, None)?;
         let waiter = PyFuture::new(py, self.to_inst_ptr())?;
 
@@ -1110,6 +1622,8 @@ impl TokioEventLoop {
 
     fn connect_write_pipe(&self, py: Python, protocol_factory: PyObject, pipe: PyObject)
                            -> PyResult<PyFuturePtr> {
+        self.set_pipe_nonblocking(py, &pipe)?;
+
         let protocol = protocol_factory.call(py, NoArgs, None)?;
         let waiter = PyFuture::new(py, self.to_inst_ptr())?;
 
@@ -1154,6 +1668,38 @@ impl TokioEventLoop {
         Classes.Socket.call(py, "socketpair", NoArgs, None)
     }
 
+    //
+    // Register callback(pid, returncode, *args) for exit of child process.
+    //
+    // Loop reaps its subprocesses on SIGCHLD itself, asyncio child
+    // watcher is not used.
+    //
+    #[args(args="args", kw="kwargs")]
+    fn _add_child_handler(&mut self, py: Python, args: PyTuple, kwargs: Option<&PyDict>)
+                          -> PyResult<()> {
+        if args.len(py) < 2 {
+            return Err(PyErr::new::<exc::TypeError, _>(
+                py, "function takes at least 2 arguments"))
+        }
+        let pid = args.get_item(py, 0).extract::<libc::pid_t>(py)?;
+        let callback = args.get_item(py, 1);
+        let cb_args = PyTuple::new(py, &args.as_slice(py)[2..]);
+
+        let evloop = self.to_inst_ptr();
+        let handle = self.handle.clone();
+        self.children.add(py, evloop, &handle, pid, callback, cb_args)
+            .map_err(|err| err.to_pyerr(py))
+    }
+
+    //
+    // Remove exit callback of child process.
+    //
+    // Return True if the handler was removed, False if not.
+    //
+    fn _remove_child_handler(&self, py: Python, pid: libc::pid_t) -> PyResult<bool> {
+        Ok(self.children.remove(pid))
+    }
+
     fn _child_watcher_callback(&self, py: Python, pid: PyObject,
                                returncode: PyObject, transp: PyObject) -> PyResult<PyObject> {
         let process_exited = transp.getattr(py, "_process_exited")?;
@@ -1179,36 +1725,9 @@ impl TokioEventLoop {
             &empty
         };
 
-        let stdin: i32 = if let Some(val) = kwargs.get_item(py, "stdin") {
-            let _ = kwargs.del_item(py, "stdin")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
-        let stdout: i32 = if let Some(val) = kwargs.get_item(py, "stdout") {
-            let _ = kwargs.del_item(py, "stdout")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
-        let stderr: i32 = if let Some(val) = kwargs.get_item(py, "stderr") {
-            let _ = kwargs.del_item(py, "stderr")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
+        let stdin = self.parse_stdio(py, kwargs, "stdin")?;
+        let stdout = self.parse_stdio(py, kwargs, "stdout")?;
+        let stderr = self.parse_stdio(py, kwargs, "stderr")?;
         let newlines = if let Some(val) = kwargs.get_item(py, "universal_newlines") {
             let _ = kwargs.del_item(py, "universal_newlines")?;
             if val == py.None() {
@@ -1240,8 +1759,10 @@ impl TokioEventLoop {
             0
         };
 
-        //if not isinstance(cmd, (bytes, str)):
-        //raise ValueError("cmd must be a string")
+        if !PyString::downcast_from(py, &cmd).is_ok() &&
+            !PyBytes::downcast_from(py, &cmd).is_ok() {
+            return Err(PyErr::new::<exc::ValueError, _>(py, "cmd must be a string"))
+        }
 
         if newlines {
             return Err(PyErr::new::<exc::ValueError, _>(
@@ -1256,38 +1777,12 @@ impl TokioEventLoop {
 
         let protocol = protocol_factory.call(py, NoArgs, None)?;
 
-        let ev = Classes.UnixEvents.get(py, "_UnixSelectorEventLoop")?;
-        let coro = ev.call_method(
-            py, "_make_subprocess_transport",
+        let coro = Classes.Helpers.call(
+            py, "make_subprocess_transport",
             (self.to_inst_ptr(), protocol.clone_ref(py), cmd, true,
              stdin, stdout, stderr, bufsize), Some(kwargs))?;
 
-        let fut = PyFuture::new(py, self.to_inst_ptr())?;
-        let fut_ready = fut.clone_ref(py);
-
-        let task = PyTask::new(py, coro, &self)?;
-
-        self.href().spawn(task.then(move |res| {
-            let gil = Python::acquire_gil();
-            let py = gil.python();
-            let fut = fut_ready.as_mut(py);
-
-            match res {
-                Ok(res) => match res {
-                    Ok(transport) => {
-                        let result = (transport, protocol).to_object(py);
-                        fut.set(py, Ok(result));
-                    },
-                    Err(err) => fut.set(py, Err(err))
-                },
-                Err(_) => {
-                    let _ = fut.cancel(py);
-                }
-            }
-            Ok(())
-        }));
-
-        Ok(fut)
+        self.spawn_subprocess_transport(py, coro, protocol)
     }
 
     //
@@ -1311,36 +1806,9 @@ impl TokioEventLoop {
             &empty
         };
 
-        let stdin: i32 = if let Some(val) = kwargs.get_item(py, "stdin") {
-            let _ = kwargs.del_item(py, "stdin")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
-        let stdout: i32 = if let Some(val) = kwargs.get_item(py, "stdout") {
-            let _ = kwargs.del_item(py, "stdout")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
-        let stderr: i32 = if let Some(val) = kwargs.get_item(py, "stderr") {
-            let _ = kwargs.del_item(py, "stderr")?;
-            if val == py.None() {
-                -1
-            } else {
-                val.extract(py)?
-            }
-        } else {
-            -1
-        };
+        let stdin = self.parse_stdio(py, kwargs, "stdin")?;
+        let stdout = self.parse_stdio(py, kwargs, "stdout")?;
+        let stderr = self.parse_stdio(py, kwargs, "stderr")?;
         let newlines = if let Some(val) = kwargs.get_item(py, "universal_newlines") {
             let _ = kwargs.del_item(py, "universal_newlines")?;
             if val == py.None() {
@@ -1384,44 +1852,25 @@ impl TokioEventLoop {
         }
 
         let popen_args = PyTuple::new(py, &args.as_slice(py)[1..]);
+        for arg in popen_args.as_slice(py) {
+            if !PyString::downcast_from(py, arg).is_ok() &&
+                !PyBytes::downcast_from(py, arg).is_ok() &&
+                !arg.hasattr(py, "__fspath__")?
+            {
+                return Err(PyErr::new::<exc::TypeError, _>(
+                    py, format!("program arguments must be a bytes or text string, not {}",
+                                arg.get_type(py).name(py))))
+            }
+        }
 
         let protocol = protocol_factory.call(py, NoArgs, None)?;
 
-        let ev = Classes.UnixEvents.get(py, "_UnixSelectorEventLoop")?;
-        let coro = ev.call_method(
-            py, "_make_subprocess_transport",
+        let coro = Classes.Helpers.call(
+            py, "make_subprocess_transport",
             (self.to_inst_ptr(), protocol.clone_ref(py), popen_args, false,
              stdin, stdout, stderr, bufsize), Some(kwargs))?;
 
-        let fut = PyFuture::new(py, self.to_inst_ptr())?;
-        let fut_ready = fut.clone_ref(py);
-
-        let task = PyTask::new(py, coro, &self)?;
-
-        self.href().spawn(task.then(move |res| {
-            let gil = Python::acquire_gil();
-            let py = gil.python();
-            let fut = fut_ready.as_mut(py);
-
-            match res {
-                Ok(res) => match res {
-                    Ok(transport) => {
-                        let result = (transport, protocol).to_object(py);
-
-                        let _ = fut.set(py, Ok(result));
-                    },
-                    Err(err) => {
-                        let _ = fut.set(py, Err(err));
-                    }
-                },
-                Err(_) => {
-                    let _ = fut.cancel(py);
-                }
-            }
-            Ok(())
-        }));
-
-        Ok(fut)
+        self.spawn_subprocess_transport(py, coro, protocol)
     }
 
     //
@@ -1438,34 +1887,88 @@ impl TokioEventLoop {
     //
     // Return a Server object which can be used to stop the service.
     //
+    // Connection gets aborted if ssl handshake takes longer than
+    // ssl_handshake_timeout seconds, 60 seconds by default.
+    // ssl_shutdown_timeout is validated for asyncio compatibility only,
+    // SSLProtocol does not support shutdown timeout.
+    //
+    // SO_REUSEPORT is set only if reuse_port is true, ValueError is raised
+    // if the platform does not support it.
+    //
     #[defaults(family=0, flags="addrinfo::AI_PASSIVE", backlog=100,
-               reuse_address=true, reuse_port=true)]
+               reuse_address=true)]
     fn create_server(&self, py: Python, protocol_factory: PyObject,
-                     host: Option<PyString>, port: Option<u16>,
+                     host: Option<PyObject>, port: Option<u16>,
                      family: i32, flags: i32,
                      sock: Option<PyObject>, backlog: i32, ssl: Option<PyObject>,
-                     reuse_address: bool, reuse_port: bool)
+                     reuse_address: bool, reuse_port: Option<bool>,
+                     ssl_handshake_timeout: Option<PyObject>,
+                     ssl_shutdown_timeout: Option<PyObject>)
                      -> PyResult<PyFuturePtr>
     {
         self.create_server_helper(
             py, protocol_factory, host, port, family, flags,
-            sock, backlog, ssl, reuse_address, reuse_port, transport::tcp_transport_factory)
+            sock, backlog, ssl, reuse_address, reuse_port,
+            ssl_handshake_timeout, ssl_shutdown_timeout, transport::tcp_transport_factory)
     }
 
     /*#[defaults(family=0, flags="addrinfo::AI_PASSIVE", backlog=100,
-               reuse_address=true, reuse_port=true)]
+               reuse_address=true)]
     fn create_http_server(&self, py: Python, protocol_factory: PyObject,
-                          host: Option<PyString>, port: Option<u16>,
+                          host: Option<PyObject>, port: Option<u16>,
                           family: i32, flags: i32,
                           sock: Option<PyObject>,
                           backlog: i32, ssl: Option<PyObject>,
-                          reuse_address: bool, reuse_port: bool) -> PyResult<PyFuturePtr>
+                          reuse_address: bool, reuse_port: Option<bool>) -> PyResult<PyFuturePtr>
     {
         self.create_server_helper(
             py, protocol_factory, host, port, family, flags,
-            sock, backlog, ssl, reuse_address, reuse_port, http::http_transport_factory)
+            sock, backlog, ssl, reuse_address, reuse_port,
+            None, None, http::http_transport_factory)
     }*/
 
+    // Resolve host and port once for repeated create_connection() calls.
+    //
+    // Return a future with ResolvedEndpoint, pass it to create_connection()
+    // as endpoint keyword argument. Addresses are resolved with the same
+    // parameters as create_connection() uses.
+    //
+    #[defaults(family=0, flags="addrinfo::AI_PASSIVE")]
+    fn resolve_endpoint(&self, py: Python, host: Option<PyString>, port: Option<u16>,
+                        family: i32, flags: i32) -> PyResult<PyFuturePtr> {
+        let host = host.map(|s| String::from(s.to_string_lossy(py)));
+        let port = port.map(|p| p.to_string());
+
+        let fut = PyFuture::new(py, self.to_inst_ptr())?;
+        let fut_res = fut.clone_ref(py);
+
+        let lookup = self.lookup_addr(
+            host, port, family, flags, addrinfo::SocketType::Stream);
+
+        self.handle.spawn(lookup.then(move |res| {
+            fut_res.with_mut(|py, fut| {
+                if fut.is_cancelled() {
+                    return
+                }
+                let res = match res {
+                    Ok(Ok(addrs)) => if addrs.is_empty() {
+                        Err(PyErr::new::<exc::OSError, _>(
+                            py, "getaddrinfo() returned empty list"))
+                    } else {
+                        client::ResolvedEndpoint::new(py, addrs).map(|ep| ep.into())
+                    },
+                    Ok(Err(err)) => Err(err.to_pyerr(py)),
+                    Err(_) => Err(
+                        PyErr::new::<exc::RuntimeError, _>(py, "Unknown runtime error")),
+                };
+                fut.set(py, res);
+            });
+            future::ok(())
+        }));
+
+        Ok(fut)
+    }
+
     // Connect to a TCP server.
     //
     // Create a streaming transport connection to a given Internet host and
@@ -1477,14 +1980,28 @@ impl TokioEventLoop {
     // in the background.  When successful, the coroutine returns a
     // (transport, protocol) pair.
     //
-    #[defaults(family=0, proto=0, flags="addrinfo::AI_PASSIVE")]
+    // If connection to all resolved addresses fails, error of last attempt
+    // is raised, or ExceptionGroup with all errors if all_errors is true.
+    //
+    // TimeoutError is raised if ssl handshake takes longer than
+    // ssl_handshake_timeout seconds, 60 seconds by default.
+    // ssl_shutdown_timeout is validated only, as for create_server().
+    //
+    // endpoint is a result of resolve_endpoint(), addresses are not
+    // resolved again in that case.
+    //
+    #[defaults(family=0, proto=0, flags="addrinfo::AI_PASSIVE", all_errors=false)]
     fn create_connection(&self, py: Python, protocol_factory: PyObject,
                           host: Option<PyString>, port: Option<u16>,
                           ssl: Option<PyObject>,
                           family: i32, proto: i32, flags: i32,
                           sock: Option<PyObject>,
                           local_addr: Option<PyObject>,
-                          server_hostname: Option<PyObject>) -> PyResult<PyFuturePtr> {
+                          server_hostname: Option<PyObject>,
+                          ssl_handshake_timeout: Option<PyObject>,
+                          ssl_shutdown_timeout: Option<PyObject>,
+                          all_errors: bool,
+                          endpoint: Option<PyObject>) -> PyResult<PyFuturePtr> {
         match (&server_hostname, &ssl) {
             (&Some(_), &None) =>
                 return Err(PyErr::new::<exc::ValueError, _>(
@@ -1509,6 +2026,12 @@ impl TokioEventLoop {
             _ => (),
         }
 
+        self.check_ssl_args(py, &ssl, &server_hostname, false)?;
+        let handshake_timeout = self.ssl_timeout(
+            py, "ssl_handshake_timeout", ssl.is_some(), ssl_handshake_timeout)?;
+        let _ = self.ssl_timeout(
+            py, "ssl_shutdown_timeout", ssl.is_some(), ssl_shutdown_timeout)?;
+
         // server hostname for ssl validation
         let server_hostname = match server_hostname {
             Some(s) => Some(s),
@@ -1518,7 +2041,7 @@ impl TokioEventLoop {
             }
         };
 
-        let conn = if let (&None, &None) = (&host, &port) {
+        let conn = if let (&None, &None, &None) = (&host, &port, &endpoint) {
             let sock = if let Some(sock) = sock {
                 // Try to use supplied python connected socket object
                 if ! self.is_stream_socket(py, &sock)? {
@@ -1540,7 +2063,7 @@ impl TokioEventLoop {
                     py, "host and port was not specified and no sock specified"));
             };
 
-            let fileno = self.get_socket_fd(py, &sock)?;
+            let fileno = self.take_socket_fd(py, &sock)?;
             let sockaddr = self.addr_from_socket(py, sock)?;
 
             // create TcpStream object
@@ -1558,43 +2081,54 @@ impl TokioEventLoop {
             future::Either::A(
                 client::create_sock_connection(
                     protocol_factory, self.to_inst_ptr(),
-                    stream, sockaddr, ssl, server_hostname, waiter))
+                    stream, sockaddr, ssl, server_hostname, handshake_timeout, waiter))
         } else {
             if let Some(_) = sock {
                 return Err(PyErr::new::<exc::ValueError, _>(
                     py, "host/port and sock can not be specified at the same time"))
             }
 
-            // exctract hostname
-            let host = host.map(|s| String::from(s.to_string_lossy(py)));
-            let port = port.map(|p| p.to_string());
+            let addrs: Box<Future<Item=Vec<addrinfo::AddrInfo>, Error=io::Error>> =
+                if let Some(endpoint) = endpoint {
+                    if host.is_some() || port.is_some() {
+                        return Err(PyErr::new::<exc::ValueError, _>(
+                            py, "host/port and endpoint can not be specified at the same time"))
+                    }
+                    let endpoint = match client::ResolvedEndpoint::downcast_from(py, &endpoint) {
+                        Ok(endpoint) => endpoint,
+                        Err(_) => return Err(PyErr::new::<exc::TypeError, _>(
+                            py, format!("ResolvedEndpoint is expected, got {:?}", endpoint))),
+                    };
+                    Box::new(future::ok(endpoint.addrs().clone()))
+                } else {
+                    // exctract hostname
+                    let host = host.map(|s| String::from(s.to_string_lossy(py)));
+                    let port = port.map(|p| p.to_string());
+
+                    Box::new(
+                        self.lookup_addr(host, port,
+                                         family, flags, addrinfo::SocketType::Stream)
+                            .map_err(|err| io::Error::new(io::ErrorKind::Other, err.description()))
+                            .and_then(|addrs| addrs.map_err(
+                                |err| io::Error::new(io::ErrorKind::Other, err.description()))))
+                };
 
             let evloop = self.to_inst_ptr();
-            let handle = self.handle.clone();
             let waiter = PyFuture::new(py, self.to_inst_ptr())?;
 
             // resolve addresses and connect
-            let fut = addrinfo::lookup(&self.lookup,
-                                       host, port,
-                                       family, flags, addrinfo::SocketType::Stream)
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.description()))
-                .and_then(move |addrs| match addrs {
-                    Err(err) => future::Either::A(
-                        future::err(
-                            io::Error::new(io::ErrorKind::Other, err.description()))),
-                    Ok(addrs) => {
-                        if addrs.is_empty() {
-                            future::Either::A(future::err(
-                                io::Error::new(
-                                    io::ErrorKind::Other, "getaddrinfo() returned empty list")))
-                        } else {
-                            future::Either::B(
-                                client::create_connection(
-                                    protocol_factory, evloop,
-                                    addrs, ssl, server_hostname, waiter))
-                        }
-                    }
-                });
+            let fut = addrs.and_then(move |addrs| {
+                if addrs.is_empty() {
+                    future::Either::A(future::err(
+                        io::Error::new(
+                            io::ErrorKind::Other, "getaddrinfo() returned empty list")))
+                } else {
+                    future::Either::B(
+                        client::create_connection(
+                            protocol_factory, evloop, addrs, ssl, server_hostname,
+                            handshake_timeout, waiter, all_errors))
+                }
+            });
 
             future::Either::B(fut)
         };
@@ -1607,7 +2141,7 @@ impl TokioEventLoop {
             conn
             // set exception to future
                 .map_err(move |e| fut_err.with_mut(
-                    |py, fut| fut.set(py, Err(e.to_pyerr(py)))))
+                    |py, fut| fut.set(py, Err(client::connect_error_to_pyerr(py, &e)))))
             // set transport and protocol
                 .map(move |res| fut_conn.with_mut(
                     |py, fut| fut.set(py, Ok(res.into_tuple(py).into()))))
@@ -1616,16 +2150,66 @@ impl TokioEventLoop {
     }
 
     //
-    // Connect to a UDS client.
+    // Upgrade existing transport to TLS.
+    //
+    // Return a future with new transport, protocol must use it instead
+    // of passed transport once upgrade completes. Reading is paused until
+    // SSLProtocol takes over the transport, so no raw bytes reach
+    // the application protocol. Transport gets closed if the handshake fails
+    // or takes longer than ssl_handshake_timeout seconds (60 by default).
+    // ssl_shutdown_timeout is validated only, as for create_server().
+    //
+    #[defaults(server_side=false)]
+    fn start_tls(&self, py: Python, transport: PyObject, protocol: PyObject,
+                 sslcontext: PyObject, server_side: bool,
+                 server_hostname: Option<PyObject>,
+                 ssl_handshake_timeout: Option<PyObject>,
+                 ssl_shutdown_timeout: Option<PyObject>) -> PyResult<PyFuturePtr> {
+        if !Classes.SSLContext.is_instance(py, &sslcontext) {
+            return Err(PyErr::new::<exc::TypeError, _>(
+                py, format!("sslcontext is expected to be an instance of ssl.SSLContext, got {}",
+                            sslcontext.get_type(py).name(py))))
+        }
+        if !transport.hasattr(py, "set_protocol")? {
+            return Err(PyErr::new::<exc::TypeError, _>(
+                py, format!("transport {} is not supported by start_tls()", transport)))
+        }
+        let handshake_timeout = self.ssl_timeout(
+            py, "ssl_handshake_timeout", true, ssl_handshake_timeout)?
+            .unwrap_or(Duration::from_secs(transport::SSL_HANDSHAKE_TIMEOUT));
+        let _ = self.ssl_timeout(py, "ssl_shutdown_timeout", true, ssl_shutdown_timeout)?;
+
+        self.upgrade_tls(py, transport, protocol, sslcontext,
+                         server_side, server_hostname, handshake_timeout, None)
+    }
+
+    //
+    // Create UDS server.
+    //
+    // If cleanup_socket is true, socket file created for path
+    // is removed when server is closed. Socket file of pre-bound
+    // sock is never removed.
+    //
+    // ssl_handshake_timeout and ssl_shutdown_timeout are handled
+    // as for create_server().
     //
-    #[defaults(backlog=100)]
+    #[defaults(backlog=100, cleanup_socket=true)]
     fn create_unix_server(&self, py: Python,
                           protocol_factory: PyObject,
                           path: Option<PyObject>,
                           sock: Option<PyObject>,
                           backlog: i32,
-                          ssl: Option<PyObject>) -> PyResult<PyFuturePtr> {
+                          ssl: Option<PyObject>,
+                          ssl_handshake_timeout: Option<PyObject>,
+                          ssl_shutdown_timeout: Option<PyObject>,
+                          cleanup_socket: bool) -> PyResult<PyFuturePtr> {
+        let handshake_timeout = self.ssl_timeout(
+            py, "ssl_handshake_timeout", ssl.is_some(), ssl_handshake_timeout)?;
+        let _ = self.ssl_timeout(
+            py, "ssl_shutdown_timeout", ssl.is_some(), ssl_shutdown_timeout)?;
+
         let path = path.unwrap_or(py.None());
+        let mut unlink_path = None;
 
         let lst = if path != py.None() {
             if let Some(_) = sock {
@@ -1637,7 +2221,11 @@ impl TokioEventLoop {
             let str = s.to_string(py)?;
             let path = Path::new(str.as_ref());
 
-            UnixListener::bind(path, self.href()).map_err(|e| e.to_pyerr(py))?
+            let lst = UnixListener::bind(path, self.href()).map_err(|e| e.to_pyerr(py))?;
+            if cleanup_socket {
+                unlink_path = Some(path.to_path_buf());
+            }
+            lst
         } else {
             let sock = if let Some(sock) = sock {
                 if ! self.is_uds_socket(py, &sock)? {
@@ -1653,7 +2241,7 @@ impl TokioEventLoop {
             // listen
             sock.call_method(py, "listen", (backlog,), None)?;
 
-            let fileno = self.get_socket_fd(py, &sock)?;
+            let fileno = self.take_socket_fd(py, &sock)?;
 
             // create UnixListener object
             let lst = unsafe {
@@ -1664,7 +2252,7 @@ impl TokioEventLoop {
         };
 
         let res = server::create_uds_server(
-            py, &self, lst, ssl, protocol_factory)?;
+            py, &self, lst, ssl, handshake_timeout, protocol_factory, unlink_path)?;
 
         PyFuture::done_fut(py, self.to_inst_ptr(), res)
     }
@@ -1672,11 +2260,16 @@ impl TokioEventLoop {
     //
     // Connect to a UDS client.
     //
+    // ssl_handshake_timeout and ssl_shutdown_timeout are handled
+    // as for create_connection().
+    //
     fn create_unix_connection(&self, py: Python, protocol_factory: PyObject,
                               path: Option<PyObject>,
                               ssl: Option<PyObject>,
                               sock: Option<PyObject>,
-                              server_hostname: Option<PyObject>) -> PyResult<PyFuturePtr> {
+                              server_hostname: Option<PyObject>,
+                              ssl_handshake_timeout: Option<PyObject>,
+                              ssl_shutdown_timeout: Option<PyObject>) -> PyResult<PyFuturePtr> {
         match (&server_hostname, &ssl) {
             (&Some(_), &None) =>
                 return Err(PyErr::new::<exc::ValueError, _>(
@@ -1687,6 +2280,10 @@ impl TokioEventLoop {
             }
             _ => (),
         }
+        let handshake_timeout = self.ssl_timeout(
+            py, "ssl_handshake_timeout", ssl.is_some(), ssl_handshake_timeout)?;
+        let _ = self.ssl_timeout(
+            py, "ssl_shutdown_timeout", ssl.is_some(), ssl_shutdown_timeout)?;
 
         let path = path.unwrap_or(py.None());
 
@@ -1696,11 +2293,29 @@ impl TokioEventLoop {
                     py, "path and sock can not be specified at the same time"))
             }
 
-            let s = PyString::downcast_into(py, path)?;
-            let str = s.to_string(py)?;
-            let path = Path::new(str.as_ref());
+            let path = self.fspath(py, path)?;
+
+            if path.first() == Some(&0) {
+                // abstract namespace socket
+                let stream = connect_abstract(&path).map_err(|e| e.to_pyerr(py))?;
+                UnixStream::from_stream(stream, self.href()).map_err(|e| e.to_pyerr(py))?
+            } else {
+                let path = Path::new(OsStr::from_bytes(&path));
+                if !path.exists() {
+                    return Err(PyErr::new_err(
+                        py, &py.get_type::<exc::FileNotFoundError>(),
+                        (libc::ENOENT, "No such file or directory",
+                         path.to_string_lossy().into_owned())))
+                }
 
-            UnixStream::connect(path, self.href()).map_err(|e| e.to_pyerr(py))?
+                UnixStream::connect(path, self.href()).map_err(|e| match e.kind() {
+                    io::ErrorKind::ConnectionRefused => PyErr::new_err(
+                        py, &py.get_type::<exc::ConnectionRefusedError>(),
+                        (e.raw_os_error().unwrap_or(libc::ECONNREFUSED), e.description(),
+                         path.to_string_lossy().into_owned())),
+                    _ => e.to_pyerr(py),
+                })?
+            }
         } else {
             let sock = if let Some(sock) = sock {
                 if ! self.is_uds_socket(py, &sock)? {
@@ -1713,7 +2328,7 @@ impl TokioEventLoop {
                     py, "no path and sock were specified"))
             };
 
-            let fileno = self.get_socket_fd(py, &sock)?;
+            let fileno = self.take_socket_fd(py, &sock)?;
 
             // create UnixStream object
             let stream = unsafe {
@@ -1732,7 +2347,7 @@ impl TokioEventLoop {
         let waiter = PyFuture::new(py, self.to_inst_ptr())?;
         let result = transport::tcp_transport_factory(
             self.to_inst_ptr(), false, &protocol_factory, &ssl, server_hostname,
-            stream, None, None, Some(waiter.clone_ref(py)))
+            handshake_timeout, stream, None, None, Some(waiter.clone_ref(py)))
             .map_err(|e| e.to_pyerr(py))?;
 
         // wait waiter completion
@@ -1783,12 +2398,17 @@ impl TokioEventLoop {
             Err(err) => return Err(err.to_pyerr(py)),
         };
 
+        // socket has to be connected
+        let peer = match stream.peer_addr() {
+            Ok(peer) => peer,
+            Err(err) => return Err(err.to_pyerr(py)),
+        };
+
         let waiter = PyFuture::new(py, self.to_inst_ptr())?;
-        let peer = stream.peer_addr().expect("should never happen");
 
         let result = transport::tcp_transport_factory(
             self.to_inst_ptr(), true, &protocol_factory, &ssl,
-            None, stream, Some(&addr), Some(peer), Some(waiter.clone_ref(py)));
+            None, None, stream, Some(&addr), Some(peer), Some(waiter.clone_ref(py)));
 
         // client future
         let fut = PyFuture::new(py, self.to_inst_ptr())?;
@@ -1888,10 +2508,11 @@ impl TokioEventLoop {
     // Run until stop() is called
     //
     fn run_forever(&mut self, py: Python) -> PyResult<PyObject> {
-        if let Some(_) = self.runner {
+        if self.running || self.runner.is_some() {
             return Err(PyErr::new::<exc::RuntimeError, _>(
-                py, "Event loop is running already"));
+                py, "This event loop is already running"));
         }
+        check_running_loop(py)?;
 
         let res = {
             let evloop = self.to_inst_ptr();
@@ -1899,27 +2520,32 @@ impl TokioEventLoop {
             py.allow_threads(|| {
                 let ev = evloop.as_mut(GIL::python());
                 if let Some(ref mut core) = evloop.as_mut(GIL::python()).core {
-                    let rx = {
+                    let (rx, prev_loop, prev_hooks) = {
                         let gil = Python::acquire_gil();
                         let py = gil.python();
 
                         // set cancel sender
                         let (tx, rx) = oneshot::channel();
                         evloop.as_mut(py).runner = Some(tx);
-                        rx
+                        evloop.as_mut(py).running = true;
+                        let ev: PyObject = evloop.clone_ref(py).into();
+                        (rx, set_running_loop(py, ev.clone_ref(py)),
+                         set_loop_asyncgen_hooks(py, &ev))
                     };
 
-                    // SIGINT
-                    let ctrlc_f = tokio_signal::ctrl_c(ev.href());
-                    let ctrlc = core.0.run(ctrlc_f).unwrap().into_future();
+                    // SIGINT, SIGTERM, SIGHUP
+                    let (signals, _guard) = stop_signals(
+                        core, ev.href(), ev.handle_stop_signals, &ev.signal_handlers);
 
-                    let fut = rx.select2(ctrlc).then(|res| {
+                    let fut = rx.select2(signals).then(|res| {
                         match res {
                             Ok(future::Either::A((res, _))) => match res {
                                 Ok(_) => future::ok(RunStatus::Stopped),
                                 Err(err) => future::ok(RunStatus::PyRes(Err(err))),
                             },
-                            Ok(future::Either::B(_)) => future::ok(RunStatus::CtrlC),
+                            Ok(future::Either::B((libc::SIGINT, _))) =>
+                                future::ok(RunStatus::CtrlC),
+                            Ok(future::Either::B(_)) => future::ok(RunStatus::Stopped),
                             Err(_) => future::err(()),
                         }
                     });
@@ -1932,9 +2558,16 @@ impl TokioEventLoop {
                         Ok(status) => status,
                         Err(_) => RunStatus::Error,
                     };
+                    ev.running = false;
                     if let Some(id) = old {
                         ID.with(|cell| cell.set(Some(id)));
                     }
+                    with_py(|py| {
+                        set_running_loop(py, prev_loop);
+                        if let Some(hooks) = prev_hooks {
+                            set_asyncgen_hooks(py, hooks);
+                        }
+                    });
 
                     Ok(result)
                 } else {
@@ -1966,10 +2599,13 @@ impl TokioEventLoop {
     ///
     /// Return the Future's result, or raise its exception.
     fn run_until_complete(&self, py: Python, fut: PyObject) -> PyResult<PyObject> {
-        if let Some(_) = self.runner {
+        // nested run from callback is not allowed,
+        // runner could be already taken by stop() at this point
+        if self.running || self.runner.is_some() {
             return Err(PyErr::new::<exc::RuntimeError, _>(
-                py, "Event loop is running already"))
+                py, "This event loop is already running"))
         }
+        check_running_loop(py)?;
 
         let ptr = self.to_inst_ptr();
 
@@ -1979,14 +2615,16 @@ impl TokioEventLoop {
                 return Err(PyErr::new::<exc::ValueError, _>(
                     py, "loop argument must agree with Future"))
             }
-            py.allow_threads(|| ptr.run_future(Box::new(fut)))
+            let waiter = fut.clone_ref(py).into();
+            py.allow_threads(|| ptr.run_future(Box::new(fut), waiter))
         // PyFuture
         } else if let Ok(fut) = PyFuturePtr::downcast_into(py, fut.clone_ref(py)) {
             if !fut.as_ref(py).is_same_loop(&self) {
                 return Err(PyErr::new::<exc::ValueError, _>(
                     py, "loop argument must agree with Future"))
             }
-            py.allow_threads(|| ptr.run_future(Box::new(fut)))
+            let waiter = fut.clone_ref(py).into();
+            py.allow_threads(|| ptr.run_future(Box::new(fut), waiter))
         // asyncio.Future
         } else if fut.hasattr(py, "_asyncio_future_blocking")? {
             let l = fut.getattr(py, "_loop")?;
@@ -1994,12 +2632,14 @@ impl TokioEventLoop {
                 return Err(PyErr::new::<exc::ValueError, _>(
                     py, "loop argument must agree with Future"))
             }
+            let waiter = fut.clone_ref(py);
             let fut = PyFuture::from_fut(py, self.to_inst_ptr(), fut)?;
-            py.allow_threads(|| ptr.run_future(Box::new(fut)))
+            py.allow_threads(|| ptr.run_future(Box::new(fut), waiter))
         } else {
             if utils::iscoroutine(&fut) {
                 let fut = PyTask::new(py, fut, &self)?;
-                py.allow_threads(|| ptr.run_future(Box::new(fut)))
+                let waiter = fut.clone_ref(py).into();
+                py.allow_threads(|| ptr.run_future(Box::new(fut), waiter))
             } else {
                 return Err(PyErr::new::<exc::TypeError, _>(
                     py, "Future or Generator object is required"))
@@ -2010,6 +2650,12 @@ impl TokioEventLoop {
     //
     // Event loop debug flag
     //
+    // In debug mode loop checks that non-thread-safe methods are called
+    // from loop's thread, that sockets passed to sock_* methods are
+    // non-blocking, validates ssl and server_hostname arguments of
+    // create_connection/create_server and logs address resolution
+    // slower than slow_callback_duration.
+    //
     fn get_debug(&self, py: Python) -> PyResult<bool> {
         Ok(self.debug)
     }
@@ -2019,6 +2665,129 @@ impl TokioEventLoop {
         Ok(())
     }
 
+    //
+    // Stop running loop on SIGTERM and SIGHUP, disabled by default.
+    // Signals with handlers from add_signal_handler() do not stop the loop.
+    //
+    fn get_stop_signals(&self, py: Python) -> PyResult<bool> {
+        Ok(self.handle_stop_signals)
+    }
+
+    fn set_stop_signals(&mut self, py: Python, enabled: bool) -> PyResult<()> {
+        self.handle_stop_signals = enabled;
+        Ok(())
+    }
+
+    //
+    // Cache address resolution results for ttl seconds,
+    // cache is used by getaddrinfo, create_connection and create_server.
+    // None or zero disables cache.
+    //
+    fn _set_dns_cache_ttl(&mut self, py: Python, ttl: PyObject) -> PyResult<()> {
+        let millis = if ttl == py.None() {
+            0
+        } else {
+            cmp::min(utils::parse_millis(py, "ttl", ttl)?, MAX_DNS_CACHE_TTL)
+        };
+
+        self.dns_cache = if millis == 0 {
+            None
+        } else {
+            Some(addrinfo::LookupCache::new(Duration::from_millis(millis)))
+        };
+        Ok(())
+    }
+
+    //
+    // Replace address resolution worker pool, lookups already queued
+    // are completed by previous workers.
+    //
+    fn _set_resolver_workers(&mut self, py: Python, num: usize) -> PyResult<()> {
+        check_resolver_workers(py, num)?;
+        self.lookup = addrinfo::start_workers(num);
+        Ok(())
+    }
+
+    //
+    // Return (workers, pending lookups) of address resolution pool
+    //
+    fn _resolver_info(&self, py: Python) -> PyResult<PyObject> {
+        Ok((self.lookup.workers(), self.lookup.pending()).to_object(py))
+    }
+
+    fn _clear_dns_cache(&self, py: Python) -> PyResult<()> {
+        if let Some(ref cache) = self.dns_cache {
+            cache.clear();
+        }
+        Ok(())
+    }
+
+    //
+    // Return (hits, misses) of dns cache
+    //
+    fn _dns_cache_info(&self, py: Python) -> PyResult<PyObject> {
+        match self.dns_cache {
+            Some(ref cache) => Ok(cache.stats().to_object(py)),
+            None => Ok(py.None()),
+        }
+    }
+
+    //
+    // Handles execution stats, collected in debug mode only.
+    // Latency is time between call_soon() and actual callback execution.
+    // "handles" and "turns" are cumulative, "turn_handles" is number of
+    // handles executed by last reactor turn, "max_turn_handles" is the
+    // largest per-turn number seen since last reset.
+    //
+    fn _debug_stats(&self, py: Python) -> PyResult<PyDict> {
+        let stats = &self.debug_stats;
+        let secs = |d: Duration| d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9;
+        let avg = if stats.scheduled > 0 {
+            secs(stats.total_latency) / stats.scheduled as f64
+        } else {
+            0.0
+        };
+
+        let dict = PyDict::new(py);
+        dict.set_item(py, "handles", stats.handles)?;
+        dict.set_item(py, "turns", stats.turns)?;
+        dict.set_item(py, "turn_handles", stats.turn_handles)?;
+        dict.set_item(py, "max_turn_handles", stats.max_turn_handles)?;
+        dict.set_item(py, "max_latency", secs(stats.max_latency))?;
+        dict.set_item(py, "avg_latency", avg)?;
+        Ok(dict)
+    }
+
+    fn _reset_debug_stats(&mut self, py: Python) -> PyResult<()> {
+        self.debug_stats = DebugStats::default();
+        Ok(())
+    }
+
+    //
+    // Number of reactor turns which executed ready callbacks.
+    //
+    fn _loop_turn(&self, py: Python) -> PyResult<u64> {
+        Ok(self.ready.turns())
+    }
+
+    //
+    // Number of pending call_later() and call_at() handles, cancelled
+    // handles are released on next reactor turn. Only handles are counted,
+    // not reactor timer entries, internal timeouts are not included.
+    //
+    fn _pending_timers(&self, py: Python) -> PyResult<usize> {
+        Ok(self.timers.len())
+    }
+
+    //
+    // Return True if loop has no scheduled callbacks, pending timers,
+    // readers or writers.
+    //
+    fn is_idle(&self, py: Python) -> PyResult<bool> {
+        Ok(self.ready.len() == 0 && self.timers.len() == 0 &&
+           self.readers.is_empty() && self.writers.is_empty())
+    }
+
     //
     // slow_callback_duration
     //
@@ -2042,6 +2811,70 @@ impl TokioEventLoop {
         self.debug
     }
 
+    /// Resolve address, results are cached if dns cache is enabled.
+    /// In debug mode resolution slower than slow_callback_duration is logged
+    pub fn lookup_addr(&self, host: Option<String>, port: Option<String>,
+                       family: i32, flags: i32, socktype: addrinfo::SocketType)
+                       -> Box<Future<Item=Result<Vec<addrinfo::AddrInfo>, addrinfo::LookupError>,
+                                     Error=oneshot::Canceled>>
+    {
+        if let Some(ref cache) = self.dns_cache {
+            let key = addrinfo::LookupKey::new(&host, &port, family, flags, socktype);
+            if let Some(res) = cache.get(&key, self.instant.elapsed()) {
+                return Box::new(future::ok(res))
+            }
+
+            // expiration is measured with event loop's clock
+            let cache = cache.clone();
+            let instant = self.instant;
+            return Box::new(
+                self.lookup_worker(host, port, family, flags, socktype)
+                    .map(move |res| {
+                        cache.insert(key, &res, instant.elapsed());
+                        res
+                    }))
+        }
+
+        self.lookup_worker(host, port, family, flags, socktype)
+    }
+
+    fn lookup_worker(&self, host: Option<String>, port: Option<String>,
+                     family: i32, flags: i32, socktype: addrinfo::SocketType)
+                     -> Box<Future<Item=Result<Vec<addrinfo::AddrInfo>, addrinfo::LookupError>,
+                                   Error=oneshot::Canceled>>
+    {
+        if !self.debug {
+            return Box::new(addrinfo::lookup(&self.lookup, host, port, family, flags, socktype))
+        }
+
+        let started = Instant::now();
+        let slow = Duration::from_millis(self.slow_callback_duration);
+        let mut msg = format!("{}:{}", host.as_ref().map_or("None", |h| h.as_str()),
+                              port.as_ref().map_or("None", |p| p.as_str()));
+        if family != 0 {
+            let _ = write!(msg, ", family={}", family);
+        }
+        if flags != 0 {
+            let _ = write!(msg, ", flags={}", flags);
+        }
+
+        Box::new(addrinfo::lookup(&self.lookup, host, port, family, flags, socktype)
+                 .then(move |res| {
+                     let elapsed = started.elapsed();
+                     if elapsed >= slow {
+                         // asyncio logger, so it goes wherever loop's logs go
+                         let secs = elapsed.as_secs() as f64 +
+                             elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+                         with_py(|py| {
+                             Classes.Helpers.call(
+                                 py, "log_slow_getaddrinfo", (msg, secs), None)
+                                 .into_log(py, "can not log slow getaddrinfo");
+                         });
+                     }
+                     res
+                 }))
+    }
+
     /// Get reference to tokio remote handle
     pub fn remote(&self) -> &Remote {
         &self.remote
@@ -2057,6 +2890,16 @@ impl TokioEventLoop {
         self.handle.clone()
     }
 
+    /// Counter of pending timers
+    pub fn timers(&self) -> &PendingTimers {
+        &self.timers
+    }
+
+    /// Schedule handle execution, handles are executed in FIFO order
+    pub fn schedule_handle(&self, h: PyHandlePtr) {
+        self.ready.push(&self.handle, h)
+    }
+
     /// Stop with py exception
     pub fn stop_with_err(&mut self, py: Python, err: PyErr) {
         let runner = self.runner.take();
@@ -2069,25 +2912,115 @@ impl TokioEventLoop {
         }
     }
 
+    /// Default name for new task, "Task-N"
+    pub fn next_task_name(&self) -> String {
+        let id = self.task_counter.get() + 1;
+        self.task_counter.set(id);
+        format!("Task-{}", id)
+    }
+
+    /// Record executed handle, latency is known for call_soon handles only
+    pub fn record_handle(&mut self, latency: Option<Duration>) {
+        if !self.debug {
+            return
+        }
+        let turn = self.ready.turns();
+        let stats = &mut self.debug_stats;
+        if stats.turns == 0 || stats.turn != turn {
+            stats.turn = turn;
+            stats.turns += 1;
+            stats.turn_handles = 0;
+        }
+        stats.turn_handles += 1;
+        stats.max_turn_handles = cmp::max(stats.max_turn_handles, stats.turn_handles);
+
+        stats.handles += 1;
+        if let Some(latency) = latency {
+            stats.scheduled += 1;
+            stats.total_latency += latency;
+            if latency > stats.max_latency {
+                stats.max_latency = latency;
+            }
+        }
+    }
+
     /// set current executing task (for asyncio.Task.current_task api)
     pub fn set_current_task(&mut self, py: Python, task: PyObject) {
         self.current_task = Some(task)
     }
 
-    // Linux's socket.type is a bitmask that can include extra info
+    /// reset current task after task step
+    pub fn clear_current_task(&mut self, py: Python) {
+        self.current_task = None
+    }
+
+    /// Add task to weak registry of tasks, same as asyncio's WeakSet.
+    /// Abandoned pending tasks are not kept alive by the loop.
+    pub fn register_task(&self, py: Python, task: &PyTaskPtr) -> PyResult<()> {
+        self.tasks.call_method(py, "add", (task.clone_ref(py),), None)?;
+        Ok(())
+    }
+
+    // Validate ssl arguments in debug mode
+    fn check_ssl_args(&self, py: Python, ssl: &Option<PyObject>,
+                      server_hostname: &Option<PyObject>, server_side: bool) -> PyResult<()> {
+        if !self.debug {
+            return Ok(())
+        }
+
+        if let Some(ref ssl) = *ssl {
+            // client accepts ssl=True, default context gets created
+            let is_true = !server_side && PyBool::downcast_from(py, ssl).is_ok()
+                && ssl.is_true(py)?;
+            if !is_true && !Classes.SSLContext.is_instance(py, ssl) {
+                return Err(PyErr::new::<exc::TypeError, _>(
+                    py, format!("ssl argument must be an SSLContext, got {}",
+                                ssl.get_type(py).name(py))))
+            }
+        }
+        if let Some(ref hostname) = *server_hostname {
+            if !PyString::downcast_from(py, hostname).is_ok() {
+                return Err(PyErr::new::<exc::TypeError, _>(
+                    py, format!("server_hostname must be a str, got {}",
+                                hostname.get_type(py).name(py))))
+            }
+        }
+        Ok(())
+    }
+
+    // Path as bytes, path could be str, bytes or path-like object
+    fn fspath(&self, py: Python, path: PyObject) -> PyResult<Vec<u8>> {
+        let path = if path.hasattr(py, "__fspath__")? {
+            path.call_method(py, "__fspath__", NoArgs, None)?
+        } else {
+            path
+        };
+
+        if let Ok(s) = PyString::downcast_from(py, &path) {
+            Ok(s.to_string(py)?.into_owned().into_bytes())
+        } else if let Ok(b) = PyBytes::downcast_from(py, &path) {
+            Ok(b.data(py).to_vec())
+        } else {
+            Err(PyErr::new::<exc::TypeError, _>(
+                py, format!("path should be string, bytes or os.PathLike, not {}",
+                            path.get_type(py).name(py))))
+        }
+    }
+
+    // Linux's socket.type is a bitmask that can include extra info
     // about socket, therefore we can't do simple
-    // `sock_type == socket.SOCK_STREAM`.
+    // `sock_type == socket.SOCK_STREAM`. Socket type itself is not a bitmask,
+    // SOCK_SEQPACKET (5) and SOCK_RAW (3) share bits with SOCK_STREAM (1).
     fn is_stream_socket(&self, py: Python, sock: &PyObject) -> PyResult<bool> {
-        let stream = addrinfo::SocketType::Stream.to_int() as i32;
         let socktype: i32 = sock.getattr(py, "type")?.extract(py)?;
-        Ok((socktype & stream) == stream)
+        let socktype = socktype & !(SOCK_NONBLOCK | SOCK_CLOEXEC);
+        Ok(socktype == libc::SOCK_STREAM)
     }
 
     fn is_uds_socket(&self, py: Python, sock: &PyObject) -> PyResult<bool> {
         if self.is_stream_socket(py, sock)? {
-            let unix = addrinfo::Family::Unix.to_int() as i32;
             let family: i32 = sock.getattr(py, "family")?.extract(py)?;
-            Ok((family & unix) == unix)
+            Ok(family == libc::AF_UNIX)
         } else {
             Ok(false)
         }
@@ -2095,11 +3028,13 @@ impl TokioEventLoop {
 
     // Linux's socket.type is a bitmask that can include extra info
     // about socket, therefore we can't do simple
-    // `sock_type == socket.SOCK_DGRAM`.
+    // `sock_type == socket.SOCK_DGRAM`. Socket type itself is not a bitmask,
+    // SOCK_SEQPACKET (5) and SOCK_RAW (3) share bits with SOCK_DGRAM (2).
+    // Seqpacket sockets preserve message boundaries, same as datagram.
     fn _is_dgram_socket(&self, py: Python, sock: &PyObject) -> PyResult<bool> {
-        let dgram = addrinfo::SocketType::DGram.to_int() as i32;
         let socktype: i32 = sock.getattr(py, "type")?.extract(py)?;
-        Ok((socktype & dgram) == dgram)
+        let socktype = socktype & !(SOCK_NONBLOCK | SOCK_CLOEXEC);
+        Ok(socktype == libc::SOCK_DGRAM || socktype == libc::SOCK_SEQPACKET)
     }
 
     // opened sockets only
@@ -2112,6 +3047,14 @@ impl TokioEventLoop {
         }
     }
 
+    // socket fd is going to be registered by transport or server,
+    // drop registration cached by sock_* methods
+    fn take_socket_fd(&self, py: Python, sock: &PyObject) -> PyResult<c_int> {
+        let fd = self.get_socket_fd(py, sock)?;
+        self.fd_cache.remove(py, fd, self.href());
+        Ok(fd)
+    }
+
     // clone socket
     fn clone_socket_fd(&self, py: Python, sock: &PyObject) -> PyResult<c_int> {
         let fd = self.get_socket_fd(py, sock)?;
@@ -2140,6 +3083,389 @@ impl TokioEventLoop {
         }
     }
 
+    // wait until sock is ready and accept connection, on fd exhaustion
+    // retry after delay, pending connection does not trigger readiness again
+    fn sock_accept_wait(&self, py: Python, sock: PyObject, fut: PyFuturePtr) {
+        // create readiness stream
+        let fd = match self.get_socket_fd(py, &sock) {
+            Ok(fd) => match self.fd_cache.get(py, fd, &sock, self.href()) {
+                Ok(io) => fd::PyFdReadable::shared(io, self.href()),
+                Err(err) => {
+                    fut.as_mut(py).set(py, Err(err));
+                    return
+                }
+            },
+            Err(err) => {
+                fut.as_mut(py).set(py, Err(err));
+                return
+            }
+        };
+
+        // wait until sock get ready
+        let fut_err = fut.clone_ref(py);
+        let fut_cancel = fut.clone_ref(py);
+        let fut_ready = fut;
+        let evloop = self.to_inst_ptr();
+
+        let f = fd.until(move |_| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            let mut fut = fut_ready.as_mut(py);
+
+            // fut cancelled
+            if fut.is_cancelled() {
+                return future::ok(Some(()));
+            }
+            let res = sock.call_method(py, "accept", NoArgs, None);
+
+            match res {
+                Err(err) => {
+                    if err.matches(
+                        py, (py.get_type::<exc::BlockingIOError>(),
+                             py.get_type::<exc::InterruptedError>())) {
+                        // skill blocking, continue
+                        future::ok(None)
+                    } else if utils::is_fd_exhausted(py, &err) {
+                        // too many open files, try again later
+                        let timeout = match reactor::Timeout::new(
+                            Duration::from_secs(ACCEPT_RETRY_DELAY),
+                            evloop.as_ref(py).href()) {
+                            Ok(timeout) => timeout,
+                            Err(err) => {
+                                // reactor could be gone already if loop is closing
+                                fut.set(py, Err(err.to_pyerr(py)));
+                                return future::ok(Some(()))
+                            }
+                        };
+                        let sock = sock.clone_ref(py);
+                        let fut = fut_ready.clone_ref(py);
+                        let evloop = evloop.clone_ref(py);
+                        evloop.as_ref(py).href().spawn(timeout.then(move |_| {
+                            with_py(|py| {
+                                if !fut.as_ref(py).is_cancelled() {
+                                    evloop.as_ref(py).sock_accept_wait(py, sock, fut);
+                                }
+                            });
+                            future::ok(())
+                        }));
+                        future::ok(Some(()))
+                    } else {
+                        future::err(err)
+                    }
+                }
+                Ok(result) => {
+                    match evloop.as_ref(py).accepted_conn(py, result) {
+                        Ok(result) => fut.set(py, Ok(result)),
+                        Err(err) => fut.set(py, Err(err)),
+                    }
+                    future::ok(Some(()))
+                }
+            }
+        }).map_err(move |err| {
+            match err {
+                UntilError::Error(err) => {
+                    // actual python exception
+                    fut_err.with_mut(|py, fut| fut.set(py, Err(err)));
+                },
+                _ => unreachable!(),
+            };
+        });
+
+        self.spawn_sock_op(py, &fut_cancel, f);
+    }
+
+    // check if raw fd is in non-blocking mode
+    fn is_fd_nonblocking(&self, py: Python, fd: c_int) -> PyResult<()> {
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error().to_pyerr(py))
+        }
+        if self.debug && flags & libc::O_NONBLOCK == 0 {
+            return Err(PyErr::new::<exc::ValueError, _>(py, "the fd must be non-blocking"))
+        }
+        Ok(())
+    }
+
+    // sock_recv() for raw fd
+    fn fd_recv(&self, py: Python, fd: c_int, fd_obj: PyObject, n: usize)
+               -> PyResult<PyFuturePtr>
+    {
+        self.is_fd_nonblocking(py, fd)?;
+
+        // create readiness stream
+        let stream = match self.fd_cache.get(py, fd, &fd_obj, self.href()) {
+            Ok(io) => fd::PyFdReadable::shared(io, self.href()),
+            Err(err) => return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?),
+        };
+
+        // wait until fd get ready
+        let fut = PyFuture::new(py, self.to_inst_ptr())?;
+        let fut_err = fut.clone_ref(py);
+        let fut_ready = fut.clone_ref(py);
+
+        let f = stream.until(move |_| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            let fut = fut_ready.as_mut(py);
+
+            // fut cancelled
+            if fut.is_cancelled() {
+                return future::ok(Some(()));
+            }
+
+            let mut buf = vec![0; n];
+            let res = unsafe {
+                libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, n)
+            };
+            if res < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    // skip blocking, continue
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted =>
+                        future::ok(None),
+                    _ => future::err(err.to_pyerr(py)),
+                }
+            } else {
+                buf.truncate(res as usize);
+                fut.set(py, Ok(PyBytes::new(py, &buf).to_object(py)));
+                future::ok(Some(()))
+            }
+        }).map_err(move |err| {
+            match err {
+                UntilError::Error(err) => {
+                    fut_err.with_mut(|py, fut| fut.set(py, Err(err)));
+                },
+                _ => unreachable!(),
+            };
+        });
+
+        self.spawn_sock_op(py, &fut, f);
+        Ok(fut)
+    }
+
+    // sock_sendall() for raw fd
+    fn fd_sendall(&self, py: Python, fd: c_int, fd_obj: PyObject, data: PyObject)
+                  -> PyResult<PyFuturePtr>
+    {
+        self.is_fd_nonblocking(py, fd)?;
+
+        // copy of data, python object could change while we wait
+        let data = Classes.MemoryView.call(py, (data,), None)?
+            .call_method(py, "tobytes", NoArgs, None)?;
+        let data = PyBytes::downcast_into(py, data)?.data(py).to_vec();
+        if data.is_empty() {
+            return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Ok(py.None()))?)
+        }
+
+        // create readyness stream for write operation
+        let stream = match self.fd_cache.get(py, fd, &fd_obj, self.href()) {
+            Ok(io) => fd::PyFdWritable::shared(io, self.href()),
+            Err(err) => return Ok(PyFuture::done_res(py, self.to_inst_ptr(), Err(err))?),
+        };
+
+        // wait until fd get ready
+        let fut = PyFuture::new(py, self.to_inst_ptr())?;
+        let fut_err = fut.clone_ref(py);
+        let fut_ready = fut.clone_ref(py);
+        let mut offset: usize = 0;
+
+        let f = stream.until(move |_| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            let fut = fut_ready.as_mut(py);
+
+            if fut.is_cancelled() {
+                return future::ok(Some(()));
+            }
+
+            let res = unsafe {
+                libc::write(fd, data[offset..].as_ptr() as *const libc::c_void,
+                            data.len() - offset)
+            };
+            if res < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    // skip blocking, continue
+                    io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted =>
+                        future::ok(None),
+                    _ => future::err(err.to_pyerr(py)),
+                }
+            } else {
+                offset += res as usize;
+                if offset >= data.len() {
+                    // all data is sent
+                    fut.set(py, Ok(py.None()));
+                    future::ok(Some(()))
+                } else {
+                    future::ok(None)
+                }
+            }
+        }).map_err(move |err| {
+            match err {
+                UntilError::Error(err) => {
+                    fut_err.with_mut(|py, fut| fut.set(py, Err(err)));
+                },
+                _ => unreachable!(),
+            };
+        });
+
+        self.spawn_sock_op(py, &fut, f);
+        Ok(fut)
+    }
+
+    // run subprocess transport creation, result future resolves to
+    // (transport, protocol). cancellation of result future cancels creation
+    // task, asyncio kills the child if it is spawned already. transport
+    // created after cancellation gets closed, which kills the child as well
+    fn spawn_subprocess_transport(&self, py: Python, coro: PyObject, protocol: PyObject)
+                                  -> PyResult<PyFuturePtr> {
+        let fut = PyFuture::new(py, self.to_inst_ptr())?;
+        let fut_ready = fut.clone_ref(py);
+
+        let task = PyTask::new(py, coro, &self)?;
+
+        let fut_cancel = fut.clone_ref(py);
+        let task_cancel: PyObject = task.clone_ref(py).into();
+        fut.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+
+            if fut_cancel.as_ref(py).is_cancelled() {
+                let _ = task_cancel.call_method(py, "cancel", NoArgs, None);
+            }
+            py.release(fut_cancel);
+            py.release(task_cancel);
+        }));
+
+        self.href().spawn(task.then(move |res| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+            let fut = fut_ready.as_mut(py);
+
+            match res {
+                Ok(Ok(transport)) => {
+                    if fut.is_cancelled() {
+                        let _ = transport.call_method(py, "close", NoArgs, None);
+                    } else {
+                        let result = (transport, protocol).to_object(py);
+                        fut.set(py, Ok(result));
+                    }
+                },
+                Ok(Err(err)) => if !fut.is_done() {
+                    fut.set(py, Err(err))
+                },
+                Err(_) => {
+                    let _ = fut.cancel(py);
+                }
+            }
+            Ok(())
+        }));
+
+        Ok(fut)
+    }
+
+    // run readiness future of sock_* operation, cancellation of result
+    // future drops it right away, so fd gets deregistered from reactor
+    // without waiting for next readiness notification
+    fn spawn_sock_op<F>(&self, py: Python, fut: &PyFuturePtr, f: F)
+        where F: Future<Item=(), Error=()> + 'static
+    {
+        let (cancel_tx, cancel_rx) = oneshot::channel::<()>();
+        fut.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+            let _ = cancel_tx.send(());
+        }));
+
+        self.href().spawn(f.select2(cancel_rx).then(|_| future::ok(())));
+    }
+
+    // normalize result of socket.accept() to (socket, address) tuple
+    // and switch new connection to non-blocking mode
+    fn accepted_conn(&self, py: Python, result: PyObject) -> PyResult<PyObject> {
+        let result = match PyTuple::downcast_into(py, result) {
+            Ok(result) => result,
+            Err(_) => return Err(PyErr::new::<exc::TypeError, _>(
+                py, "accept() should return (conn, address) tuple")),
+        };
+        if result.len(py) != 2 {
+            return Err(PyErr::new::<exc::TypeError, _>(
+                py, "accept() should return (conn, address) tuple"))
+        }
+
+        let mut conn = result.get_item(py, 0);
+        let addr = result.get_item(py, 1);
+
+        // raw file descriptor
+        if let Ok(fd) = conn.extract::<c_int>(py) {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item(py, "fileno", fd)?;
+            conn = Classes.Socket.call(py, "socket", NoArgs, Some(&kwargs))?;
+        }
+        conn.call_method(py, "setblocking", (false,), None)?;
+
+        Ok(PyTuple::new(py, &[conn, addr]).into())
+    }
+
+    // parse stdin, stdout or stderr argument of subprocess methods.
+    // None means inherit parent's stream, PIPE is the default.
+    fn parse_stdio(&self, py: Python, kwargs: &PyDict, name: &str) -> PyResult<PyObject> {
+        let val = if let Some(val) = kwargs.get_item(py, name) {
+            let _ = kwargs.del_item(py, name)?;
+            val
+        } else {
+            return Ok(SUBPROCESS_PIPE.to_object(py))
+        };
+
+        if val == py.None() {
+            return Ok(val)
+        }
+
+        match val.extract::<c_int>(py) {
+            Ok(SUBPROCESS_PIPE) | Ok(SUBPROCESS_DEVNULL) => Ok(val),
+            Ok(SUBPROCESS_STDOUT) => if name == "stderr" {
+                Ok(val)
+            } else {
+                Err(PyErr::new::<exc::ValueError, _>(
+                    py, format!("STDOUT can only be used for stderr, not {}", name)))
+            },
+            Ok(fd) if fd >= 0 => Ok(val),
+            Ok(_) => Err(PyErr::new::<exc::ValueError, _>(
+                py, format!("invalid {} value", name))),
+            // file object
+            Err(_) => Ok(val),
+        }
+    }
+
+    // check that object is a pipe, socket or character device
+    // and switch its file descriptor to non-blocking mode
+    fn set_pipe_nonblocking(&self, py: Python, pipe: &PyObject) -> PyResult<()> {
+        let fd: c_int = pipe.call_method(py, "fileno", NoArgs, None)?.extract(py)?;
+
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+            return Err(io::Error::last_os_error().to_pyerr(py))
+        }
+
+        let mode = stat.st_mode & libc::S_IFMT;
+        if mode != libc::S_IFIFO && mode != libc::S_IFSOCK && mode != libc::S_IFCHR {
+            return Err(PyErr::new::<exc::ValueError, _>(
+                py, "Pipe transport is only for pipes, sockets and character devices"))
+        }
+
+        let res = unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1 {
+                flags
+            } else {
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+            }
+        };
+        if res == -1 {
+            Err(io::Error::last_os_error().to_pyerr(py))
+        } else {
+            Ok(())
+        }
+    }
+
     /// Extract AddrInfo from python native socket object
     fn addr_from_socket(&self, py: Python, sock: PyObject) -> PyResult<addrinfo::AddrInfo> {
         let family: i32 = sock.getattr(py, "family")?.extract(py)?;
@@ -2192,42 +3518,179 @@ impl TokioEventLoop {
             sockaddr, None))
     }
 
+    // ssl_handshake_timeout and ssl_shutdown_timeout arguments,
+    // positive number of seconds. Shutdown timeout is checked for asyncio
+    // compatibility only, SSLProtocol does not support it.
+    pub fn ssl_timeout(&self, py: Python, name: &str, ssl: bool,
+                       timeout: Option<PyObject>) -> PyResult<Option<Duration>> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        if !ssl {
+            return Err(PyErr::new::<exc::ValueError, _>(
+                py, format!("{} is only meaningful with ssl", name)))
+        }
+        match utils::parse_seconds(py, name, timeout.clone_ref(py))? {
+            Some(timeout) if timeout > Duration::new(0, 0) => Ok(Some(timeout)),
+            _ => Err(PyErr::new::<exc::ValueError, _>(
+                py, format!("{} should be a positive number, got {}", name, timeout))),
+        }
+    }
+
+    /// Upgrade transport to TLS, `data` is fed to SSLProtocol before
+    /// any bytes read from transport. Returns future with wrapped transport.
+    pub fn upgrade_tls(&self, py: Python, transport: PyObject, protocol: PyObject,
+                       sslcontext: PyObject, server_side: bool,
+                       server_hostname: Option<PyObject>,
+                       handshake_timeout: Duration,
+                       data: Option<PyObject>) -> PyResult<PyFuturePtr> {
+        let waiter = PyFuture::new(py, self.to_inst_ptr())?;
+
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(py, "server_side", server_side)?;
+        if let Some(hostname) = server_hostname {
+            kwargs.set_item(py, "server_hostname", hostname)?;
+        }
+        kwargs.set_item(py, "call_connection_made", false)?;
+        let ssl_proto = Classes.SSLProto.call(py, (
+            self.to_inst_ptr(), protocol, sslcontext, waiter.clone_ref(py)), Some(&kwargs))?;
+
+        // stop reading until SSLProtocol gets connected to transport
+        transport.call_method(py, "pause_reading", NoArgs, None)?;
+        transport.call_method(py, "set_protocol", (ssl_proto.clone_ref(py),), None)?;
+
+        PyHandle::new(py, &self, ssl_proto.getattr(py, "connection_made")?,
+                      PyTuple::new(py, &[transport.clone_ref(py)]))?.call_soon(py, &self);
+        if let Some(data) = data {
+            PyHandle::new(py, &self, Classes.Helpers.get(py, "feed_data")?,
+                          PyTuple::new(py, &[ssl_proto.clone_ref(py), data]))?
+                .call_soon(py, &self);
+        }
+        PyHandle::new(py, &self, transport.getattr(py, "resume_reading")?,
+                      PyTuple::new(py, &[]))?.call_soon(py, &self);
+        transport::ssl_handshake_timeout(
+            py, &self, handshake_timeout, ssl_proto.clone_ref(py), transport.clone_ref(py),
+            &waiter);
+
+        // wrapped transport is available after handshake
+        let fut = PyFuture::new(py, self.to_inst_ptr())?;
+        let res = fut.clone_ref(py);
+        waiter.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |result| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+
+            {
+                let fut = res.as_mut(py);
+                if fut.is_cancelled() {
+                    let _ = transport.call_method(py, "close", NoArgs, None);
+                } else {
+                    match result {
+                        Ok(_) => fut.set(py, ssl_proto.getattr(py, "_app_transport")),
+                        Err(err) => {
+                            let _ = transport.call_method(py, "close", NoArgs, None);
+                            fut.set(py, Err(err));
+                        }
+                    }
+                }
+            }
+            py.release(res);
+            py.release(ssl_proto);
+            py.release(transport);
+        }));
+        Ok(fut)
+    }
+
     pub fn create_server_helper(&self, py: Python, protocol_factory: PyObject,
-                                host: Option<PyString>, port: Option<u16>,
+                                host: Option<PyObject>, port: Option<u16>,
                                 family: i32, flags: i32, sock: Option<PyObject>,
                                 backlog: i32, ssl: Option<PyObject>,
-                                reuse_address: bool, reuse_port: bool,
+                                reuse_address: bool, reuse_port: Option<bool>,
+                                ssl_handshake_timeout: Option<PyObject>,
+                                ssl_shutdown_timeout: Option<PyObject>,
                                 transport_factory: transport::TransportFactory)
                                 -> PyResult<PyFuturePtr> {
 
+        self.check_ssl_args(py, &ssl, &None, true)?;
+        let handshake_timeout = self.ssl_timeout(
+            py, "ssl_handshake_timeout", ssl.is_some(), ssl_handshake_timeout)?;
+        let _ = self.ssl_timeout(
+            py, "ssl_shutdown_timeout", ssl.is_some(), ssl_shutdown_timeout)?;
+
+        // do not set SO_REUSEPORT unless requested explicitly
+        let reuse_port = reuse_port.unwrap_or(false);
+        if reuse_port && Classes.Socket.get(py, "SO_REUSEPORT").is_err() {
+            return Err(PyErr::new::<exc::ValueError, _>(
+                py, "reuse_port not supported by socket module"))
+        }
+
         if let (&None, &None) = (&host, &port) {
             if let Some(sock) = sock {
+                // sock could be a socket or a list of sockets
+                let socks = if PyList::downcast_from(py, &sock).is_ok() ||
+                    PyTuple::downcast_from(py, &sock).is_ok()
+                {
+                    let mut socks = Vec::new();
+                    for item in sock.iter(py)? {
+                        socks.push(item?);
+                    }
+                    if socks.is_empty() {
+                        return Err(PyErr::new::<exc::ValueError, _>(
+                            py, "Empty list of sockets was specified"))
+                    }
+                    socks
+                } else {
+                    vec![sock]
+                };
+
                 // only stream sockets
-                if ! self.is_stream_socket(py, &sock)? {
-                    return Err(PyErr::new::<exc::ValueError, _>(
-                        py, format!("A Stream Socket was expected, got {:?}", sock)))
+                for sock in socks.iter() {
+                    if ! self.is_stream_socket(py, sock)? {
+                        return Err(PyErr::new::<exc::ValueError, _>(
+                            py, format!("A Stream Socket was expected, got {:?}", sock)))
+                    }
                 }
 
                 // check if socket is UNIX domain socket
-                if self.is_uds_socket(py, &sock)? {
+                if socks.len() == 1 && self.is_uds_socket(py, &socks[0])? {
+                    let sock = socks.into_iter().next();
                     return self.create_unix_server(
-                        py, protocol_factory, None, Some(sock), backlog, ssl);
+                        py, protocol_factory, None, sock, backlog, ssl, false);
+                }
+                for sock in socks.iter() {
+                    if self.is_uds_socket(py, sock)? {
+                        return Err(PyErr::new::<exc::ValueError, _>(
+                            py, format!("UNIX domain socket can not be mixed \
+                                         with other sockets, got {:?}", sock)))
+                    }
                 }
 
-                // listen
-                sock.call_method(py, "listen", (backlog,), None)?;
+                let mut addrs = Vec::new();
+                for sock in socks.iter() {
+                    // listen
+                    sock.call_method(py, "listen", (backlog,), None)?;
 
-                // opened sockets only
-                let fileno = self.get_socket_fd(py, &sock)?;
-                let sockaddr = self.addr_from_socket(py, sock)?;
+                    // opened sockets only
+                    let _ = self.take_socket_fd(py, sock)?;
+                    addrs.push(self.addr_from_socket(py, sock.clone_ref(py))?);
+                }
 
-                // create TcpListener object
-                let listener = unsafe {
-                    net::TcpListener::from_raw_fd(fileno as RawFd)
-                };
+                // python sockets own their fds until every socket is checked,
+                // detached fd is owned by listener right away
+                let mut listeners = Vec::new();
+                for (sock, sockaddr) in socks.into_iter().zip(addrs) {
+                    let fileno: c_int = sock.call_method(py, "detach", NoArgs, None)?.extract(py)?;
+
+                    // create TcpListener object
+                    let listener = unsafe {
+                        net::TcpListener::from_raw_fd(fileno as RawFd)
+                    };
+                    listeners.push((listener, sockaddr));
+                }
 
                 let res = server::create_sock_server(
-                    py, &self, listener, sockaddr, ssl, protocol_factory, transport_factory);
+                    py, &self, listeners, ssl, handshake_timeout,
+                    protocol_factory, transport_factory);
 
                 // waiter future
                 return PyFuture::done_res(py, self.to_inst_ptr(), res)
@@ -2243,18 +3706,35 @@ impl TokioEventLoop {
                 py, "host/port and sock can not be specified at the same time"))
         }
 
-        // exctract hostname
-        let host = host.map(|s| String::from(s.to_string_lossy(py)));
+        // exctract hostnames, host could be a string or a sequence of strings
+        let hosts = match host {
+            None => vec![None],
+            Some(host) => if let Ok(host) = PyString::downcast_from(py, &host) {
+                vec![Some(String::from(host.to_string_lossy(py)))]
+            } else {
+                let mut hosts = Vec::new();
+                for item in host.iter(py)? {
+                    let item = item?;
+                    let item = PyString::downcast_into(py, item)?;
+                    hosts.push(Some(String::from(item.to_string_lossy(py))));
+                }
+                hosts
+            }
+        };
 
         // waiter future
         let fut = PyFuture::new(py, self.to_inst_ptr())?;
         let fut_srv = fut.clone_ref(py);
         let evloop = self.to_inst_ptr();
 
-        // resolve addresses and start listening
-        let conn = addrinfo::lookup(&self.lookup,
-                                    host, port.map(|p| p.to_string()),
-                                    family, flags, addrinfo::SocketType::Stream)
+        // resolve addresses for all hosts
+        let lookups: Vec<_> = hosts.into_iter().map(|host| {
+            self.lookup_addr(host, port.map(|p| p.to_string()),
+                             family, flags, addrinfo::SocketType::Stream)
+        }).collect();
+
+        // start listening
+        let conn = future::join_all(lookups)
             .map_err(|err| with_py(
                 |py| io::Error::new(io::ErrorKind::Other, err.description()).to_pyerr(py)))
             .then(move |result| {
@@ -2262,6 +3742,20 @@ impl TokioEventLoop {
                 let py = gil.python();
                 let fut = fut_srv.as_mut(py);
 
+                // same address could be resolved for different hosts,
+                // bind only once to each address
+                let result = result.map(|results| {
+                    results.into_iter().collect::<Result<Vec<_>, _>>().map(|results| {
+                        let mut addrs: Vec<addrinfo::AddrInfo> = Vec::new();
+                        for info in results.into_iter().flat_map(|infos| infos) {
+                            if !addrs.iter().any(|a| a.sockaddr == info.sockaddr) {
+                                addrs.push(info);
+                            }
+                        }
+                        addrs
+                    })
+                });
+
                 match result {
                     Err(err) => {
                         let _ = fut.set(py, Err(err));
@@ -2276,7 +3770,7 @@ impl TokioEventLoop {
                                     py, "getaddrinfo() returned empty list")));
                         } else {
                             let res = server::create_server(
-                                py, evloop.as_ref(py), addrs, backlog, ssl,
+                                py, evloop.as_ref(py), addrs, backlog, ssl, handshake_timeout,
                                 reuse_address, reuse_port, protocol_factory, transport_factory);
                             let _ = fut.set(py, res);
                         }
@@ -2311,7 +3805,7 @@ impl TokioEventLoop {
                          kwargs: Option<&[(PyObject, PyObject)]>) {
         let _: PyResult<()> = {
             let context = PyDict::new(py);
-            let _ = context.set_item(py, "message", "Future exception was never retrieved");
+            let _ = context.set_item(py, "message", message);
             source_traceback.map(
                 |tb| context.set_item(py, "source_traceback", tb));
             exception.map(
@@ -2331,34 +3825,42 @@ impl TokioEventLoop {
 
 impl TokioEventLoopPtr {
     /// Run future to completion
+    ///
+    /// On ctrl-c loop stops, `waiter` gets cancelled and KeyboardInterrupt is raised
     pub fn run_future(&self,
                       fut: Box<Future<Item=PyResult<PyObject>,
-                                      Error=unsync::oneshot::Canceled>>) -> PyResult<PyObject> {
+                                      Error=unsync::oneshot::Canceled>>,
+                      waiter: PyObject) -> PyResult<PyObject> {
         let ev = self.as_mut(GIL::python());
 
         let res = match self.as_mut(GIL::python()).core {
             Some(ref mut core) => {
-                let rx = {
+                let (rx, prev_loop, prev_hooks) = {
                     let gil = Python::acquire_gil();
                     let py = gil.python();
 
                     // stop fut
                     let (tx, rx) = oneshot::channel();
                     self.as_mut(py).runner = Some(tx);
+                    self.as_mut(py).running = true;
 
-                    rx
+                    let ev: PyObject = self.clone_ref(py).into();
+                    (rx, set_running_loop(py, ev.clone_ref(py)),
+                     set_loop_asyncgen_hooks(py, &ev))
                 };
 
-                // SIGINT
-                let ctrlc_f = tokio_signal::ctrl_c(ev.href());
-                let ctrlc = core.0.run(ctrlc_f).unwrap().into_future();
+                // SIGINT, SIGTERM, SIGHUP
+                let (signals, _guard) = stop_signals(
+                    core, ev.href(), ev.handle_stop_signals, &ev.signal_handlers);
 
-                let sel = rx.select2(ctrlc).then(|res| {
+                let sel = rx.select2(signals).then(|res| {
                     match res {
                         Ok(future::Either::A((res, _))) => match res {
                             Ok(_) => future::ok(RunStatus::Stopped),
                             Err(err) => future::ok(RunStatus::PyRes(Err(err))),
                         },
+                        Ok(future::Either::B((libc::SIGINT, _))) =>
+                            future::ok(RunStatus::CtrlC),
                         Ok(_) => future::ok(RunStatus::Stopped),
                         Err(err) => future::err(err),
                     }
@@ -2382,10 +3884,17 @@ impl TokioEventLoopPtr {
                             },
                         }
                     }));
+                ev.running = false;
 
                 if let Some(id) = old {
                     ID.with(|cell| cell.set(Some(id)));
                 }
+                with_py(|py| {
+                    set_running_loop(py, prev_loop);
+                    if let Some(hooks) = prev_hooks {
+                        set_asyncgen_hooks(py, hooks);
+                    }
+                });
 
                 result
             },
@@ -2403,6 +3912,10 @@ impl TokioEventLoopPtr {
 
         match res {
             Ok(RunStatus::PyRes(res)) => res,
+            Ok(RunStatus::CtrlC) => {
+                let _ = waiter.call_method(py, "cancel", NoArgs, None);
+                Err(PyErr::new::<exc::KeyboardInterrupt, _>(py, NoArgs))
+            },
             Err(_) => Err(PyErr::new_err(py, &Classes.CancelledError, NoArgs)),
             _ => Ok(py.None())
         }
@@ -2410,6 +3923,16 @@ impl TokioEventLoopPtr {
 }
 
 
+#[py::proto]
+impl PyObjectProtocol for TokioEventLoop {
+    fn __repr__(&self, py: Python) -> PyResult<PyString> {
+        Ok(PyString::new(
+            py, &format!("<TokioEventLoop {:?} running={} closed={} debug={}>",
+                         self.core_id, self.running, self.id.is_none(), self.debug)))
+    }
+}
+
+
 impl PartialEq for TokioEventLoop {
     fn eq(&self, other: &TokioEventLoop) -> bool {
         let py = GIL::python();
@@ -1,6 +1,10 @@
 use std::io;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::os::raw::c_int;
 use std::os::unix::io::RawFd;
+use pyo3::*;
 use mio::event::Evented;
 use mio::unix::EventedFd;
 use mio::{self, Ready, PollOpt, Token};
@@ -10,6 +14,7 @@ use tokio_core::reactor::{Handle, PollEvented};
 
 use fut::Until;
 use handle::PyHandlePtr;
+use utils::{Classes, ToPyErr};
 
 
 pub struct PyFd (RawFd);
@@ -145,25 +150,29 @@ impl Future for PyFdHandle {
 
 /// Stream of read readyness for file descriptor
 pub struct PyFdReadable {
-    io: Option<PollEvented<PyFd>>,
+    io: Option<Rc<PollEvented<PyFd>>>,
     marked_ready: bool,
     h: Handle,
 }
 
 impl PyFdReadable {
-    pub fn new(fd: c_int, handle: &Handle) -> io::Result<PyFdReadable> {
-        Ok(PyFdReadable{
-            io: Some(PollEvented::new(PyFd::new(fd), handle)?),
+    /// Readiness of registration from `PyFdCache`
+    pub fn shared(io: Rc<PollEvented<PyFd>>, handle: &Handle) -> PyFdReadable {
+        PyFdReadable{
+            io: Some(io),
             marked_ready: false,
             h: handle.clone(),
-        })
+        }
     }
 }
 
 impl Drop for PyFdReadable {
     fn drop(&mut self) {
+        // cached registration stays registered
         if let Some(io) = self.io.take() {
-            let _ = io.deregister(&self.h);
+            if let Ok(io) = Rc::try_unwrap(io) {
+                let _ = io.deregister(&self.h);
+            }
         }
     }
 }
@@ -198,25 +207,29 @@ impl Until for PyFdReadable {}
 
 /// Stream of write readyness for file descriptor
 pub struct PyFdWritable {
-    io: Option<PollEvented<PyFd>>,
+    io: Option<Rc<PollEvented<PyFd>>>,
     marked_ready: bool,
     h: Handle,
 }
 
 impl PyFdWritable {
-    pub fn new(fd: c_int, handle: &Handle) -> io::Result<PyFdWritable> {
-        Ok(PyFdWritable{
-            io: Some(PollEvented::new(PyFd::new(fd), handle)?),
+    /// Readiness of registration from `PyFdCache`
+    pub fn shared(io: Rc<PollEvented<PyFd>>, handle: &Handle) -> PyFdWritable {
+        PyFdWritable{
+            io: Some(io),
             marked_ready: false,
             h: handle.clone(),
-        })
+        }
     }
 }
 
 impl Drop for PyFdWritable {
     fn drop(&mut self) {
+        // cached registration stays registered
         if let Some(io) = self.io.take() {
-            let _ = io.deregister(&self.h);
+            if let Ok(io) = Rc::try_unwrap(io) {
+                let _ = io.deregister(&self.h);
+            }
         }
     }
 }
@@ -247,3 +260,80 @@ impl stream::Stream for PyFdWritable {
 }
 
 impl Until for PyFdWritable {}
+
+
+/// Reactor registrations of sockets used by `sock_*` methods.
+///
+/// Registration is kept between calls, so chatty protocols do not
+/// register and deregister fd for every recv/send. Entry remembers socket
+/// object, if fd number gets reused by other socket entry is dropped.
+/// Objects without weak references support, i.e. raw fds, are not cached.
+pub struct PyFdCache {
+    entries: RefCell<HashMap<c_int, (PyObject, Rc<PollEvented<PyFd>>)>>,
+}
+
+impl PyFdCache {
+
+    pub fn new() -> PyFdCache {
+        PyFdCache { entries: RefCell::new(HashMap::new()) }
+    }
+
+    /// Registration for socket fd, fd gets registered on first use
+    pub fn get(&self, py: Python, fd: c_int, sock: &PyObject, handle: &Handle)
+               -> PyResult<Rc<PollEvented<PyFd>>>
+    {
+        let mut entries = self.entries.borrow_mut();
+
+        let mut alive = false;
+        if let Some(&(ref sock_ref, ref io)) = entries.get(&fd) {
+            let obj = sock_ref.call(py, NoArgs, None)?;
+            if obj.as_ptr() == sock.as_ptr() {
+                return Ok(io.clone())
+            }
+            alive = !obj.is_none(py);
+        }
+
+        if let Some((sock_ref, io)) = entries.remove(&fd) {
+            py.release(sock_ref);
+
+            // same fd is used with other object, i.e. raw fd. otherwise
+            // previous socket is closed, kernel already removed its registration
+            if alive {
+                if let Ok(io) = Rc::try_unwrap(io) {
+                    let _ = io.deregister(handle);
+                }
+            }
+        }
+
+        let io = match PollEvented::new(PyFd::new(fd), handle) {
+            Ok(io) => Rc::new(io),
+            Err(err) => return Err(err.to_pyerr(py)),
+        };
+
+        // socket object does not support weak references, do not cache
+        if let Ok(sock_ref) = Classes.WeakRef.call(py, (sock.clone_ref(py),), None) {
+            entries.insert(fd, (sock_ref, io.clone()));
+        }
+        Ok(io)
+    }
+
+    /// Deregister fd, fd is going to be used by transport or reader/writer callback
+    pub fn remove(&self, py: Python, fd: c_int, handle: &Handle) {
+        if let Some((sock_ref, io)) = self.entries.borrow_mut().remove(&fd) {
+            py.release(sock_ref);
+            if let Ok(io) = Rc::try_unwrap(io) {
+                let _ = io.deregister(handle);
+            }
+        }
+    }
+
+    /// Deregister all fds
+    pub fn clear(&self, py: Python, handle: &Handle) {
+        for (_, (sock_ref, io)) in self.entries.borrow_mut().drain() {
+            py.release(sock_ref);
+            if let Ok(io) = Rc::try_unwrap(io) {
+                let _ = io.deregister(handle);
+            }
+        }
+    }
+}
@@ -1,11 +1,21 @@
-use std::time::Duration;
+use std::cmp;
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 use pyo3::*;
 use futures::future::{self, Future};
 use futures::sync::oneshot;
 use tokio_core::reactor::Timeout;
 
-use ::{TokioEventLoop, TokioEventLoopPtr, Classes};
+use ::{PyFuture, PyFuturePtr, TokioEventLoop, TokioEventLoopPtr, Classes};
+use pyfuture::PyFutureIterPtr;
+use pyunsafe::Handle;
+use utils::ToPyErr;
+
+// max number of ready handles executed per reactor turn
+const MAX_READY_HANDLES: usize = 1024;
 
 #[py::class]
 pub struct PyHandle {
@@ -15,6 +25,10 @@ pub struct PyHandle {
     callback: PyObject,
     args: PyTuple,
     source_traceback: Option<PyObject>,
+    scheduled: Option<Instant>,
+    // created on first await only
+    waiter: RefCell<Option<PyFuturePtr>>,
+    executed: Cell<bool>,
     token: PyToken,
 }
 
@@ -24,13 +38,21 @@ pub struct PyHandlePtr(PyPtr);
 #[py::methods]
 impl PyHandle {
 
-    fn cancel(&mut self, _py: Python) -> PyResult<()> {
+    fn cancel(&mut self, py: Python) -> PyResult<()> {
         self.cancelled = true;
 
         if let Some(tx) = self.cancel_handle.take() {
             let _ = tx.send(());
         }
 
+        // callback is never called, release referenced objects
+        self.callback = py.None();
+        self.args = PyTuple::new(py, &[]);
+
+        if let Some(waiter) = self.waiter.borrow_mut().take() {
+            let _ = waiter.as_mut(py).cancel(py);
+        }
+
         Ok(())
     }
 
@@ -60,26 +82,59 @@ impl PyHandle {
             callback: callback,
             args: args,
             source_traceback: tb,
+            scheduled: None,
+            waiter: RefCell::new(None),
+            executed: Cell::new(false),
             token: t})
     }
 }
 
+#[py::proto]
+impl PyAsyncProtocol for PyHandle {
+
+    //
+    // Wait until callback is executed, result is callback's return value.
+    //
+    // Result is kept for handles awaited before execution only, handle
+    // awaited first time after execution resolves to None.
+    //
+    fn __await__(&self, py: Python) -> PyResult<PyFutureIterPtr> {
+        if self.waiter.borrow().is_none() {
+            let waiter = PyFuture::new(py, self.evloop.clone_ref(py))?;
+            if self.cancelled {
+                waiter.as_mut(py).cancel(py)?;
+            } else if self.executed.get() {
+                waiter.as_mut(py).set(py, Ok(py.None()));
+            }
+            *self.waiter.borrow_mut() = Some(waiter);
+        }
+
+        match *self.waiter.borrow() {
+            Some(ref waiter) => waiter.as_ref(py).iter(py),
+            None => unreachable!(),
+        }
+    }
+}
+
 impl PyHandlePtr {
 
     pub fn call_soon(&self, py: Python, evloop: &TokioEventLoop) {
-        let h = self.clone_ref(py);
+        if evloop.is_debug() {
+            self.as_mut(py).scheduled = Some(Instant::now());
+        }
 
         // schedule work
-        evloop.get_handle().spawn_fn(move || {
-            h.run();
-            future::ok(())
-        });
+        evloop.schedule_handle(self.clone_ref(py));
     }
 
     pub fn call_soon_threadsafe(&self, py: Python, evloop: &TokioEventLoop) {
+        if evloop.is_debug() {
+            self.as_mut(py).scheduled = Some(Instant::now());
+        }
         let h = self.clone_ref(py);
 
-        // schedule work
+        // schedule work, message sent to remote wakes up reactor
+        // immediately, even if core is parked waiting for a timer
         evloop.remote().spawn(move |_| {
             h.run();
             future::ok(())
@@ -93,10 +148,34 @@ impl PyHandlePtr {
 
         // we need to hold reference, otherwise python will release handle object
         let h = self.clone_ref(py);
+        let timers = evloop.timers().clone();
+
+        // start timer, reactor could be gone already if loop is closing
+        let timeout = match Timeout::new(when, evloop.href()) {
+            Ok(timeout) => timeout,
+            Err(err) => {
+                py.release(h);
+
+                // treat handle as cancelled
+                let h = self.as_mut(py);
+                h.cancelled = true;
+                h.cancel_handle = None;
 
-        // start timer
-        let fut = Timeout::new(when, evloop.href()).unwrap().select2(rx)
+                let context = PyDict::new(py);
+                let _ = context.set_item(
+                    py, "message", format!("Failed to schedule callback {:?}", h.callback));
+                let _ = context.set_item(py, "handle", format!("{:?}", h));
+                let _ = context.set_item(py, "exception", err.to_pyerr(py).instance(py));
+                let _ = evloop.call_exception_handler(py, context);
+                return
+            }
+        };
+        // cancel() completes select, unfired Timeout is returned as part of
+        // result and gets dropped right away, which frees its reactor entry
+        timers.inc();
+        let fut = timeout.select2(rx)
             .then(move |res| {
+                timers.dec();
                 if let Ok(future::Either::A(_)) = res {
                     // timeout got fired, call callback
                     h.run();
@@ -112,8 +191,20 @@ impl PyHandlePtr {
             if h.cancelled {
                 return Ok(())
             }
+            h.evloop.as_mut(py).record_handle(h.scheduled.map(|t| t.elapsed()));
 
             let result = h.callback.call(py, h.args.clone_ref(py), None);
+            h.executed.set(true);
+
+            // resolve awaiting coroutine, waiter is kept for later awaits.
+            // result of handle that is not awaited yet is not retained
+            if let Some(ref waiter) = *h.waiter.borrow() {
+                let res = match result {
+                    Ok(ref res) => Ok(res.clone_ref(py)),
+                    Err(ref err) => Err(err.clone_ref(py)),
+                };
+                waiter.as_mut(py).set(py, res);
+            }
 
             // handle python exception
             if let Err(err) = result {
@@ -138,3 +229,101 @@ impl PyHandlePtr {
         });
     }
 }
+
+
+/// FIFO queue of handles scheduled with call_soon.
+///
+/// Handles are executed in batches, each batch is a bounded snapshot
+/// of the queue, handles scheduled during batch execution run on next
+/// reactor turn, so IO gets polled between batches.
+pub struct ReadyQueue {
+    queue: Rc<RefCell<VecDeque<PyHandlePtr>>>,
+    scheduled: Rc<Cell<bool>>,
+    turns: Rc<Cell<u64>>,
+}
+
+// queue is used from loop thread only
+unsafe impl Send for ReadyQueue {}
+
+impl ReadyQueue {
+
+    pub fn new() -> ReadyQueue {
+        ReadyQueue {
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+            scheduled: Rc::new(Cell::new(false)),
+            turns: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// Number of handles waiting for execution
+    pub fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+
+    /// Number of executed batches
+    pub fn turns(&self) -> u64 {
+        self.turns.get()
+    }
+
+    pub fn push(&self, handle: &Handle, h: PyHandlePtr) {
+        self.queue.borrow_mut().push_back(h);
+
+        if !self.scheduled.get() {
+            self.scheduled.set(true);
+            ReadyQueue::run(handle.clone(), self.queue.clone(),
+                            self.scheduled.clone(), self.turns.clone());
+        }
+    }
+
+    fn run(handle: Handle, queue: Rc<RefCell<VecDeque<PyHandlePtr>>>,
+           scheduled: Rc<Cell<bool>>, turns: Rc<Cell<u64>>) {
+        let h = handle.clone();
+        h.spawn_fn(move || {
+            turns.set(turns.get() + 1);
+
+            let count = cmp::min(queue.borrow().len(), MAX_READY_HANDLES);
+            for _ in 0..count {
+                // queue must not be borrowed during callback execution
+                let h = queue.borrow_mut().pop_front();
+                if let Some(h) = h {
+                    h.run();
+                }
+            }
+
+            if queue.borrow().is_empty() {
+                scheduled.set(false);
+            } else {
+                ReadyQueue::run(handle, queue, scheduled, turns);
+            }
+            future::ok(())
+        });
+    }
+}
+
+
+/// Number of pending call_later handles, reactor timers
+/// created outside of `PyHandlePtr::call_later` are not counted
+#[derive(Clone)]
+pub struct PendingTimers(Rc<Cell<usize>>);
+
+// counter is used from loop thread only
+unsafe impl Send for PendingTimers {}
+
+impl PendingTimers {
+
+    pub fn new() -> PendingTimers {
+        PendingTimers(Rc::new(Cell::new(0)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.get()
+    }
+
+    fn inc(&self) {
+        self.0.set(self.0.get() + 1)
+    }
+
+    fn dec(&self) {
+        self.0.set(self.0.get() - 1)
+    }
+}
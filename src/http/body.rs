@@ -0,0 +1,87 @@
+use bytes::{Bytes, BytesMut};
+
+use http::decoder::{Error, RequestMessage};
+
+
+/// Accumulates `RequestMessage::Body` frames into single `Bytes`
+///
+/// Body that arrives in one frame is returned as is, without copying.
+/// `Error::PayloadTooLarge` is returned once total size exceeds `max_size`.
+pub struct BodyCollector {
+    max_size: usize,
+    size: usize,
+    first: Option<Bytes>,
+    buf: BytesMut,
+    remaining: Option<Bytes>,
+}
+
+impl BodyCollector {
+
+    pub fn new(max_size: usize) -> BodyCollector {
+        BodyCollector {
+            max_size: max_size,
+            size: 0,
+            first: None,
+            buf: BytesMut::new(),
+            remaining: None,
+        }
+    }
+
+    /// Number of collected bytes
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Add body frame
+    pub fn push(&mut self, chunk: Bytes) -> Result<(), Error> {
+        if chunk.is_empty() {
+            return Ok(())
+        }
+        if self.size + chunk.len() > self.max_size {
+            return Err(Error::PayloadTooLarge)
+        }
+        self.size += chunk.len();
+
+        if self.size == chunk.len() {
+            self.first = Some(chunk);
+        } else {
+            if let Some(first) = self.first.take() {
+                self.buf.reserve(self.size);
+                self.buf.extend_from_slice(&first);
+            }
+            self.buf.extend_from_slice(&chunk);
+        }
+        Ok(())
+    }
+
+    /// Feed decoder message, returns collected body on `Completed`
+    /// and `Upgrade`, see `take_remaining`. Message heads are ignored.
+    pub fn feed(&mut self, msg: RequestMessage) -> Result<Option<Bytes>, Error> {
+        match msg {
+            RequestMessage::Body(chunk) => {
+                self.push(chunk)?;
+                Ok(None)
+            },
+            RequestMessage::Completed => Ok(Some(self.take())),
+            RequestMessage::Upgrade { remaining, .. } => {
+                self.remaining = Some(remaining);
+                Ok(Some(self.take()))
+            },
+            RequestMessage::Message(_) => Ok(None),
+        }
+    }
+
+    /// Data that follows upgrade request, belongs to new protocol
+    pub fn take_remaining(&mut self) -> Option<Bytes> {
+        self.remaining.take()
+    }
+
+    /// Return collected body and reset collector
+    pub fn take(&mut self) -> Bytes {
+        self.size = 0;
+        match self.first.take() {
+            Some(first) => first,
+            None => self.buf.take().freeze(),
+        }
+    }
+}
@@ -9,7 +9,8 @@ use bytes::{Bytes, BytesMut};
 use tokio_io::codec::Decoder;
 
 use http::headers::{Header, WriteHeaders};
-use http::message::{Version, ContentCompression, ConnectionType, Request, RequestUpdater};
+use http::message::{
+    Version, ContentCompression, ConnectionType, Request, RequestUpdater, TargetForm};
 
 
 /// Parsed request
@@ -18,6 +19,17 @@ pub enum RequestMessage {
     Message(Request),
     Body(Bytes),
     Completed,
+    /// Upgrade request is completed, `remaining` is data that follows
+    /// message head, it belongs to new protocol. Decoder stops after it.
+    Upgrade { protocol: UpgradeKind, remaining: Bytes },
+}
+
+/// Protocol that connection switches to after upgrade request
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UpgradeKind {
+    WebSocket,
+    /// CONNECT tunnel
+    Connect,
 }
 
 /// An error in parsing.
@@ -33,10 +45,26 @@ pub enum Error {
     ContentLength,
     /// Content-Length and Trasnfer-Encoding: chunked
     ContentLengthAndTE,
-    /// An error in parsing a chunk
+    /// An error in parsing a chunk or chunked is not the final transfer coding
     TransferEncoding,
     /// Eof received but payload is not completed yet
     PayloadNotCompleted,
+    /// Eof received in the middle of status line or headers
+    HeadersNotCompleted,
+    /// LF without preceding CR (strict mode)
+    BareLf,
+    /// Request method is too long
+    MethodTooLong,
+    /// Request-target is too long, 414 URI Too Long
+    UriTooLong,
+    /// Collected payload exceeds size limit, 413 Payload Too Large
+    PayloadTooLarge,
+    /// HTTP/1.1 request without Host header, see `set_require_host`
+    MissingHost,
+    /// More than one Host header
+    DuplicateHost,
+    /// Number of headers exceeds limit
+    TooManyHeaders,
     /// std::io::Error
     IOError(std::io::Error),
 }
@@ -52,6 +80,14 @@ impl Error {
             Error::ContentLengthAndTE => "Both defined Content-Length and Trasnfer-Encoding: chunked length",
             Error::TransferEncoding => "transfer encoding error",
             Error::PayloadNotCompleted => "Eof received but payload is not completed yet",
+            Error::HeadersNotCompleted => "Eof received but headers are not completed yet",
+            Error::BareLf => "LF without preceding CR",
+            Error::MethodTooLong => "method too long",
+            Error::UriTooLong => "uri too long",
+            Error::PayloadTooLarge => "payload too large",
+            Error::MissingHost => "missing host header",
+            Error::DuplicateHost => "duplicate host header",
+            Error::TooManyHeaders => "too many headers",
             Error::IOError(_) => "io error",
         }
     }
@@ -69,11 +105,13 @@ impl std::error::Error for Error {
     }
 }
 
-/// Convert Error to io::Error
+/// Convert Error to io::Error, underlying io errors are passed as is
 impl std::convert::From<Error> for std::io::Error {
     fn from(err: Error) -> Self {
-        std::io::Error::new(
-            std::io::ErrorKind::Other, format!("Python exception: {:?}", err.description()))
+        match err {
+            Error::IOError(err) => err,
+            err => std::io::Error::new(std::io::ErrorKind::InvalidData, err.description()),
+        }
     }
 }
 
@@ -134,7 +172,9 @@ enum ParseHeader {
 enum ParseStatusLine {
     Skip(CRLF),
     Method,
+    PathWs,
     Path,
+    VersionWs,
     Version,
     Eol(CRLF),
 }
@@ -178,6 +218,8 @@ enum ParseHeaderName {
     ContentLength(usize),
     ContentEncoding(usize),
 
+    Expect(usize),
+    Host(usize),
     ProxyConnection(usize),
     TransferEncoding(usize),
     Upgrade(usize),
@@ -223,6 +265,12 @@ impl ParseHeaderName {
             ParseHeaderName::ContentEncoding(idx) => {
                 match_hname!(ParseHeaderName::ContentEncoding(idx) == ch, CONTENT_ENCODING)
             },
+            ParseHeaderName::Expect(idx) => {
+                match_hname!(ParseHeaderName::Expect(idx) == ch, EXPECT)
+            },
+            ParseHeaderName::Host(idx) => {
+                match_hname!(ParseHeaderName::Host(idx) == ch, HOST)
+            },
             ParseHeaderName::ProxyConnection(idx) => {
                 match_hname!(ParseHeaderName::ProxyConnection(idx) == ch, PROXY_CONNECTION)
             },
@@ -235,6 +283,8 @@ impl ParseHeaderName {
             ParseHeaderName::New => {
                 match ch {
                     b'c' => ParseHeaderName::Con(0),
+                    b'e' => ParseHeaderName::Expect(0),
+                    b'h' => ParseHeaderName::Host(0),
                     b'p' => ParseHeaderName::ProxyConnection(0),
                     b't' => ParseHeaderName::TransferEncoding(0),
                     b'u' => ParseHeaderName::Upgrade(0),
@@ -250,6 +300,8 @@ impl ParseHeaderName {
             ParseHeaderName::Connection(idx) => idx+1 == CONNECTION.len,
             ParseHeaderName::ContentLength(idx) => idx+1 == CONTENT_LENGTH.len,
             ParseHeaderName::ContentEncoding(idx) => idx+1 == CONTENT_ENCODING.len,
+            ParseHeaderName::Expect(idx) => idx+1 == EXPECT.len,
+            ParseHeaderName::Host(idx) => idx+1 == HOST.len,
             ParseHeaderName::ProxyConnection(idx) => idx+1 == PROXY_CONNECTION.len,
             ParseHeaderName::TransferEncoding(idx) => idx+1 == TRANSFER_ENCODING.len,
             ParseHeaderName::Upgrade(idx) => idx+1 == UPGRADE.len,
@@ -265,6 +317,7 @@ enum ParseTokens {
     C,
     Close(usize),
     Chunked(usize),
+    Continue(usize),
     Gzip(usize),
     Deflate(usize),
     KeepAlive(usize),
@@ -289,6 +342,9 @@ impl ParseTokens {
             ParseTokens::Close(idx) => {
                 match_token!(ParseTokens::Close(idx) == ch, CLOSE)
             },
+            ParseTokens::Continue(idx) => {
+                match_token!(ParseTokens::Continue(idx) == ch, CONTINUE)
+            },
             ParseTokens::Gzip(idx) => {
                 match_token!(ParseTokens::Gzip(idx) == ch, GZIP)
             },
@@ -306,6 +362,7 @@ impl ParseTokens {
             },
             ParseTokens::New => match ch.to_ascii_lowercase() {
                 b'c' => ParseTokens::C,
+                b'1' => ParseTokens::Continue(0),
                 b'g' => ParseTokens::Gzip(0),
                 b'd' => ParseTokens::Deflate(0),
                 b'k' => ParseTokens::KeepAlive(0),
@@ -321,6 +378,7 @@ impl ParseTokens {
         match *self {
             ParseTokens::Chunked(idx) => idx+1 == CHUNKED.len,
             ParseTokens::Close(idx) => idx+1 == CLOSE.len,
+            ParseTokens::Continue(idx) => idx+1 == CONTINUE.len,
             ParseTokens::Gzip(idx) => idx+1 == GZIP.len,
             ParseTokens::Deflate(idx) => idx+1 == DEFLATE.len,
             ParseTokens::KeepAlive(idx) => idx+1 == KEEP_ALIVE.len,
@@ -351,6 +409,9 @@ enum State {
     Header(ParseHeader),
     Body(ParseBody),
     Done,
+    Upgrade(UpgradeKind),
+    // connection switched to other protocol, rest of data is not http
+    Upgraded,
 }
 
 pub struct RequestDecoder {
@@ -365,6 +426,8 @@ pub struct RequestDecoder {
 
     length: Option<u64>,
     chunked: bool,
+    connection: ConnectionType,
+    websocket: bool,
 
     header: Header,
     has_header: bool,
@@ -375,6 +438,16 @@ pub struct RequestDecoder {
     max_line_size: u16,
     max_headers: u16,
     max_field_size: u16,
+    max_method_size: u8,
+    max_uri_size: u16,
+    max_content_length: u64,
+
+    strict: bool,
+    unsized_payload: bool,
+    require_host: bool,
+    record_compress: bool,
+    request_line_ws: bool,
+    checked: bool,
 }
 
 impl RequestDecoder {
@@ -389,9 +462,152 @@ impl RequestDecoder {
             header: Header::new(), has_header: false, header_token: ParseTokens::New,
             header_name: ParseHeaderName::General, header_name_hash: DefaultHasher::new(),
 
-            length: None, chunked: false,
+            length: None, chunked: false, connection: ConnectionType::KeepAlive,
+            websocket: false,
 
             max_line_size: 8190, max_headers: 32768, max_field_size: 8190,
+            max_method_size: 32, max_uri_size: 8190, max_content_length: std::u64::MAX,
+
+            strict: false,
+            unsized_payload: false,
+            require_host: false,
+            record_compress: true,
+            request_line_ws: false,
+            checked: cfg!(feature = "safe_parser"),
+        }
+    }
+
+    /// Reject any LF which is not preceded by CR,
+    /// in status line, headers and chunked payload.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict
+    }
+
+    /// Accept any number of SP and HTAB between request line components.
+    /// Disabled by default, components must be separated with single SP.
+    pub fn set_request_line_ws(&mut self, allow: bool) {
+        self.request_line_ws = allow
+    }
+
+    /// Read payload of message without Content-Length and Transfer-Encoding
+    /// until eof, if connection is not keep-alive (HTTP/1.0 peers).
+    /// Payload gets completed by `decode_eof`
+    pub fn set_unsized_payload(&mut self, unsized_payload: bool) {
+        self.unsized_payload = unsized_payload
+    }
+
+    /// Reject HTTP/1.1 requests without Host header with `Error::MissingHost`,
+    /// RFC 7230 section 5.4. Multiple Host headers are always rejected.
+    pub fn set_require_host(&mut self, require_host: bool) {
+        self.require_host = require_host
+    }
+
+    /// Record Content-Encoding of payload in `Request::compress`,
+    /// so consumer could decompress payload. Enabled by default,
+    /// `Request::compress` stays `Default` otherwise.
+    pub fn set_record_compress(&mut self, record: bool) {
+        self.record_compress = record
+    }
+
+    /// Parse with bounds checked byte access instead of raw pointers,
+    /// slower but events are the same. Always enabled with `safe_parser` feature.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked
+    }
+
+    /// Maximum length of request line and of each header line,
+    /// `Error::LineTooLong` otherwise
+    pub fn set_max_line_size(&mut self, size: u16) {
+        self.max_line_size = size
+    }
+
+    /// Maximum number of headers, `Error::TooManyHeaders` otherwise
+    pub fn set_max_headers(&mut self, count: u16) {
+        self.max_headers = count
+    }
+
+    /// Maximum length of request method, `Error::MethodTooLong` otherwise
+    pub fn set_max_method_size(&mut self, size: u8) {
+        self.max_method_size = size
+    }
+
+    /// Maximum length of request-target, `Error::UriTooLong` otherwise
+    pub fn set_max_uri_size(&mut self, size: u16) {
+        self.max_uri_size = size
+    }
+
+    /// Maximum value of Content-Length header, `Error::ContentLength` otherwise
+    pub fn set_max_content_length(&mut self, size: u64) {
+        self.max_content_length = size
+    }
+
+    /// Reset parse state, so decoder could be reused for next request,
+    /// i.e. after connection error in the middle of message.
+    /// Limits and strict mode are preserved. `Bytes` objects that are
+    /// already returned from decoder are not affected.
+    pub fn reset(&mut self) {
+        self.state = State::Status(ParseStatusLine::Skip(CRLF::CR));
+        self.start = 0;
+        self.meth_pos = 0;
+        self.meth_end = 0;
+        self.path_pos = 0;
+        self.path_end = 0;
+
+        self.request = Request::new();
+
+        self.length = None;
+        self.chunked = false;
+        self.connection = ConnectionType::KeepAlive;
+        self.websocket = false;
+
+        self.header = Header::new();
+        self.has_header = false;
+        self.header_token = ParseTokens::New;
+        self.header_name = ParseHeaderName::General;
+        self.header_name_hash = DefaultHasher::new();
+    }
+
+    /// Connection is keep-alive, for last parsed message head
+    pub fn is_keep_alive(&self) -> bool {
+        self.connection == ConnectionType::KeepAlive
+    }
+
+    /// Connection upgrade requested, for last parsed message head
+    pub fn is_upgrade(&self) -> bool {
+        self.connection == ConnectionType::Upgrade
+    }
+
+    /// WebSocket handshake, for last parsed message head. Requires
+    /// `Upgrade: websocket`, `Connection: upgrade` and `Sec-WebSocket-Key`.
+    ///
+    /// Handshake request has no payload, decoder emits `Upgrade` message
+    /// with rest of data instead of `Completed` and stops.
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.websocket
+    }
+
+    /// Connection is switched to other protocol, decoder does not consume
+    /// any data anymore until `reset()`
+    pub fn is_upgraded(&self) -> bool {
+        if let State::Upgraded = self.state { true } else { false }
+    }
+
+    /// Payload of current message uses chunked transfer encoding
+    pub fn is_chunked(&self) -> bool {
+        self.chunked
+    }
+
+    /// Content-Length of current message
+    pub fn content_length(&self) -> Option<u64> {
+        if self.chunked { None } else { self.length }
+    }
+
+    #[inline]
+    fn lf_error(&self, ch: u8, err: Error) -> Error {
+        if ch == LF && self.strict {
+            Error::BareLf
+        } else {
+            err
         }
     }
 
@@ -409,17 +625,10 @@ impl RequestDecoder {
                         _ => (),
                     }
                 },
-            ParseHeaderName::TransferEncoding(..) => match token {
-                ParseTokens::Chunked(..) => {
-                    self.chunked = true;
-                    self.request.chunked = true;
-                },
-                _ => (),
-            },
             ParseHeaderName::ContentEncoding(..) => match token {
-                ParseTokens::Gzip(..) =>
+                ParseTokens::Gzip(..) if self.record_compress =>
                     self.request.compress = ContentCompression::Gzip,
-                ParseTokens::Deflate(..) =>
+                ParseTokens::Deflate(..) if self.record_compress =>
                     self.request.compress = ContentCompression::Deflate,
                 _ => (),
             },
@@ -428,18 +637,146 @@ impl RequestDecoder {
                     self.request.websocket = true,
                 _ => (),
             },
+            // expectation is defined for HTTP/1.1 only
+            ParseHeaderName::Expect(..) => match token {
+                ParseTokens::Continue(..) =>
+                    if self.request.version == Version::Http11 {
+                        self.request.expect_continue = true
+                    },
+                _ => (),
+            },
             _ => (),
         }
     }
+
+    // Transfer-Encoding is a list of codings, chunked has to be the final
+    // one, otherwise message length can not be determined.
+    // Preceding codings are recorded for decoding of dechunked payload.
+    fn update_transfer_codings(&mut self, value: &[u8]) -> std::result::Result<(), Error> {
+        for coding in value.split(|ch| *ch == b',') {
+            // strip transfer-parameters
+            let coding = trim_ows(coding.split(|ch| *ch == b';').next().unwrap_or(coding));
+            if coding.is_empty() {
+                continue
+            }
+            // coding after chunked, including chunked applied twice
+            if self.chunked {
+                return Err(Error::TransferEncoding)
+            }
+            if coding.eq_ignore_ascii_case(CHUNKED.token) {
+                self.chunked = true;
+                self.request.chunked = true;
+            } else {
+                self.request.transfer_codings.push(
+                    String::from_utf8_lossy(coding).to_ascii_lowercase());
+            }
+        }
+        Ok(())
+    }
 }
 
-impl Decoder for RequestDecoder {
-    type Item = RequestMessage;
-    type Error = Error;
+/// Builder for `RequestDecoder` with strictness presets
+///
+/// ```ignore
+/// let decoder = RequestDecoderBuilder::new().strict().max_headers(100).build();
+/// ```
+pub struct RequestDecoderBuilder {
+    decoder: RequestDecoder,
+}
 
-    fn decode(&mut self, src: &mut BytesMut) -> std::result::Result<Option<Self::Item>, Self::Error> {
+impl RequestDecoderBuilder {
+
+    /// Lenient decoder with default limits
+    pub fn new() -> RequestDecoderBuilder {
+        RequestDecoderBuilder { decoder: RequestDecoder::new() }
+    }
+
+    /// Enable all request smuggling guards: bare LF and request line
+    /// whitespace rejection, Host header is required for HTTP/1.1
+    pub fn strict(mut self) -> Self {
+        self.decoder.set_strict(true);
+        self.decoder.set_require_host(true);
+        self.decoder.set_request_line_ws(false);
+        self
+    }
+
+    /// Accept malformed but unambiguous requests: bare LF line endings
+    /// and extra request line whitespace. Chunked framing stays strict.
+    pub fn lenient(mut self) -> Self {
+        self.decoder.set_strict(false);
+        self.decoder.set_require_host(false);
+        self.decoder.set_request_line_ws(true);
+        self
+    }
+
+    /// Accept extra whitespace in request line, see `set_request_line_ws`
+    pub fn request_line_ws(mut self, allow: bool) -> Self {
+        self.decoder.set_request_line_ws(allow);
+        self
+    }
+
+    /// Read payload without length until eof, see `set_unsized_payload`
+    pub fn unsized_payload(mut self, unsized_payload: bool) -> Self {
+        self.decoder.set_unsized_payload(unsized_payload);
+        self
+    }
+
+    /// Record Content-Encoding of payload, see `set_record_compress`
+    pub fn record_compress(mut self, record: bool) -> Self {
+        self.decoder.set_record_compress(record);
+        self
+    }
+
+    /// Bounds checked byte access, see `set_checked`
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.decoder.set_checked(checked);
+        self
+    }
+
+    /// Maximum number of headers, see `set_max_headers`
+    pub fn max_headers(mut self, count: u16) -> Self {
+        self.decoder.set_max_headers(count);
+        self
+    }
+
+    /// Maximum length of request line and header lines, see `set_max_line_size`
+    pub fn max_line_size(mut self, size: u16) -> Self {
+        self.decoder.set_max_line_size(size);
+        self
+    }
+
+    /// Maximum length of request method, see `set_max_method_size`
+    pub fn max_method_size(mut self, size: u8) -> Self {
+        self.decoder.set_max_method_size(size);
+        self
+    }
+
+    /// Maximum length of request-target, see `set_max_uri_size`
+    pub fn max_uri_size(mut self, size: u16) -> Self {
+        self.decoder.set_max_uri_size(size);
+        self
+    }
+
+    /// Maximum value of Content-Length, see `set_max_content_length`
+    pub fn max_content_length(mut self, size: u64) -> Self {
+        self.decoder.set_max_content_length(size);
+        self
+    }
+
+    /// Configured decoder
+    pub fn build(self) -> RequestDecoder {
+        self.decoder
+    }
+}
+
+impl RequestDecoder {
+
+    fn decode_with<C>(&mut self, src: &mut BytesMut)
+                      -> std::result::Result<Option<RequestMessage>, Error>
+        where C: for<'a> NewCursor<'a>
+    {
         let mut state = self.state;
-        let mut bytes = BytesPtr::new(src.as_ref(), self.start);
+        let mut bytes = C::new(src.as_ref(), self.start);
         let mut header_name = self.header_name;
         let mut header_token = self.header_token;
 
@@ -459,6 +796,9 @@ impl Decoder for RequestDecoder {
                                     continue
                                 },
                                 LF => {
+                                    if self.strict {
+                                        return Err(Error::BareLf);
+                                    }
                                     bytes.bump();
                                     state = State::Status(ParseStatusLine::Method);
                                 }
@@ -479,17 +819,31 @@ impl Decoder for RequestDecoder {
                         break
                     }
                 },
-                ParseStatusLine::Method => match parse_token(&mut bytes, SP)? {
+                ParseStatusLine::Method => match parse_token(&mut bytes, self.request_line_ws)? {
                     Status::Complete(l) => {
+                        if (self.meth_end - self.meth_pos) as usize + l
+                            > self.max_method_size as usize
+                        {
+                            return Err(Error::MethodTooLong);
+                        }
                         if bytes.pos() > self.max_line_size as usize {
                             return Err(Error::LineTooLong);
                         }
                         self.meth_end = self.meth_end + l as u8;
                         self.path_pos = bytes.pos() as u8;
                         self.path_end = self.path_pos as u16;
-                        state = State::Status(ParseStatusLine::Path);
+                        state = if self.request_line_ws {
+                            State::Status(ParseStatusLine::PathWs)
+                        } else {
+                            State::Status(ParseStatusLine::Path)
+                        };
                     }
                     Status::Partial(l) => {
+                        if (self.meth_end - self.meth_pos) as usize + l
+                            > self.max_method_size as usize
+                        {
+                            return Err(Error::MethodTooLong);
+                        }
                         if bytes.pos() > self.max_line_size as usize {
                             return Err(Error::LineTooLong);
                         }
@@ -497,22 +851,69 @@ impl Decoder for RequestDecoder {
                         break
                     }
                 },
-                ParseStatusLine::Path => match parse_path(&mut bytes)? {
+                // skip extra whitespace before request target, see `set_request_line_ws`
+                ParseStatusLine::PathWs => match parse_ows(&mut bytes)? {
+                    Status::Complete(..) => {
+                        // path position is stored as u8
+                        if bytes.pos() > std::u8::MAX as usize {
+                            return Err(Error::BadStatusLine);
+                        }
+                        self.path_pos = bytes.pos() as u8;
+                        self.path_end = self.path_pos as u16;
+                        state = State::Status(ParseStatusLine::Path);
+                    }
+                    Status::Partial(..) => {
+                        if bytes.pos() > self.max_line_size as usize  {
+                            return Err(Error::LineTooLong);
+                        }
+                        break
+                    }
+                },
+                ParseStatusLine::Path => match parse_path(&mut bytes, self.request_line_ws)? {
                     Status::Complete(l) => {
                         if bytes.pos() > self.max_line_size as usize  {
                             return Err(Error::LineTooLong);
                         }
-                       self.path_end = self.path_end + l as u16;
-                        state = State::Status(ParseStatusLine::Version);
+                        if (self.path_end - self.path_pos as u16) as usize + l
+                            > self.max_uri_size as usize
+                        {
+                            return Err(Error::UriTooLong);
+                        }
+                        self.path_end = self.path_end + l as u16;
+                        state = if self.request_line_ws {
+                            State::Status(ParseStatusLine::VersionWs)
+                        } else {
+                            State::Status(ParseStatusLine::Version)
+                        };
                     }
                     Status::Partial(l) => {
                         if bytes.pos() > self.max_line_size as usize  {
                             return Err(Error::LineTooLong);
                         }
+                        if (self.path_end - self.path_pos as u16) as usize + l
+                            > self.max_uri_size as usize
+                        {
+                            return Err(Error::UriTooLong);
+                        }
                         self.path_end = self.path_end + l as u16;
                         break
                     }
                 },
+                // skip extra whitespace before http version
+                ParseStatusLine::VersionWs => match parse_ows(&mut bytes)? {
+                    Status::Complete(..) => {
+                        if bytes.pos() > self.max_line_size as usize  {
+                            return Err(Error::LineTooLong);
+                        }
+                        state = State::Status(ParseStatusLine::Version);
+                    }
+                    Status::Partial(..) => {
+                        if bytes.pos() > self.max_line_size as usize  {
+                            return Err(Error::LineTooLong);
+                        }
+                        break
+                    }
+                },
                 ParseStatusLine::Version => match parse_version(&mut bytes)? {
                     Status::Complete(ver) => {
                         self.request.version = ver;
@@ -524,7 +925,21 @@ impl Decoder for RequestDecoder {
                             src.split_to(bytes.pos()).freeze(),
                             (self.meth_pos, self.meth_end),
                             (self.path_pos, self.path_end));
-                        bytes = BytesPtr::new(src.as_ref(), 0);
+
+                        // CONNECT uses authority-form only,
+                        // asterisk-form is allowed for OPTIONS only
+                        if self.request.path().is_empty() {
+                            return Err(Error::BadStatusLine);
+                        }
+                        match (self.request.method(), self.request.target_form()) {
+                            ("CONNECT", TargetForm::Authority) |
+                            ("OPTIONS", TargetForm::Asterisk) => (),
+                            ("CONNECT", _) |
+                            (_, TargetForm::Authority) |
+                            (_, TargetForm::Asterisk) => return Err(Error::BadStatusLine),
+                            _ => (),
+                        }
+                        bytes = C::new(src.as_ref(), 0);
                         state = State::Status(ParseStatusLine::Eol(CRLF::CR));
                     },
                     Status::Partial(..) => {
@@ -532,10 +947,11 @@ impl Decoder for RequestDecoder {
                     }
                 },
                 ParseStatusLine::Eol(marker) =>
-                    match parse_crlf(&mut bytes, marker, Error::BadStatusLine)? {
+                    match parse_crlf(&mut bytes, marker, Error::BadStatusLine, self.strict)? {
                         Status::Complete(..) => {
                             self.length = None;
                             self.chunked = false;
+                            self.connection = ConnectionType::KeepAlive;
                             state = State::Header(ParseHeader::Eol);
                         },
                         Status::Partial(marker) => {
@@ -554,6 +970,10 @@ impl Decoder for RequestDecoder {
                     Some(ch) =>
                         // reading end http message
                         if ch == CR {
+                            // CRLF could be split between reads, wait for LF
+                            if bytes.get_next_maybe().is_none() {
+                                break
+                            }
                             bytes.bump();
                             if let Some(ch) = bytes.next_maybe() {
                                 if ch == LF {
@@ -562,6 +982,22 @@ impl Decoder for RequestDecoder {
                                     }
                                     self.request.headers.flush(src);
 
+                                    if self.require_host && self.request.host_range().is_none() &&
+                                        self.request.version == Version::Http11
+                                    {
+                                        return Err(Error::MissingHost);
+                                    }
+
+                                    // chunked has to be the final transfer coding,
+                                    // message length is ambiguous otherwise
+                                    if !self.chunked && !self.request.transfer_codings.is_empty() {
+                                        return Err(Error::TransferEncoding);
+                                    }
+
+                                    // last header belongs to this message only,
+                                    // next pipelined message starts from scratch
+                                    self.has_header = false;
+
                                     let length = match self.length{
                                         Some(length) =>
                                             if self.chunked {
@@ -573,10 +1009,24 @@ impl Decoder for RequestDecoder {
                                     };
 
                                     self.start = 0;
-                                    if self.chunked {
+                                    self.connection = self.request.connection;
+                                    self.websocket = self.request.websocket &&
+                                        self.connection == ConnectionType::Upgrade &&
+                                        self.request.headers.contains("sec-websocket-key");
+                                    self.request.content_length = self.content_length();
+                                    if self.websocket {
+                                        // bytes after head belong to new protocol
+                                        self.state = State::Upgrade(UpgradeKind::WebSocket);
+                                    } else if self.request.method() == "CONNECT" {
+                                        self.state = State::Upgrade(UpgradeKind::Connect);
+                                    } else if self.chunked {
                                         self.state = State::Body(ParseBody::ChunkSize(0));
                                     } else if length > 0 {
                                         self.state = State::Body(ParseBody::Length(length));
+                                    } else if self.length.is_none() && self.unsized_payload &&
+                                        self.connection == ConnectionType::Close
+                                    {
+                                        self.state = State::Body(ParseBody::Unsized);
                                     } else {
                                         self.state = State::Done;
                                     }
@@ -590,6 +1040,8 @@ impl Decoder for RequestDecoder {
                             } else {
                                 break
                             }
+                        } else if ch == LF && self.strict {
+                            return Err(Error::BareLf);
                         } else if is_ows(ch) && self.has_header {
                             // header value continuation
                             state = State::Header(ParseHeader::Value);
@@ -600,6 +1052,9 @@ impl Decoder for RequestDecoder {
                             } else {
                                 self.has_header = true
                             }
+                            if self.request.headers.len() >= self.max_headers as usize {
+                                return Err(Error::TooManyHeaders)
+                            }
                             // new header
                             state = State::Header(ParseHeader::Name);
                             header_name = ParseHeaderName::New;
@@ -633,7 +1088,7 @@ impl Decoder for RequestDecoder {
                             self.header_name = header_name;
                             continue 'run
                         } else if !is_token(ch) {
-                            return Err(Error::BadHeader);
+                            return Err(self.lf_error(ch, Error::BadHeader));
                         }
                         let ch = ch.to_ascii_lowercase();
                         self.header_name_hash.write_u8(ch);
@@ -668,23 +1123,29 @@ impl Decoder for RequestDecoder {
                         let ch = bytes.next();
                         if ch == CR {
                             bytes.advance(idx+1);
+                            if bytes.pos() > MAX_HEADERS_SIZE {
+                                return Err(Error::LineTooLong)
+                            }
                             state = State::Header(ParseHeader::ValueEol);
                             self.header.update_value_len(idx);
 
-                            // parse content-length value
-                            let l = unsafe {
-                                std::str::from_utf8_unchecked(&src[self.header.value_range()]) };
+                            // parse content-length value, strict decimal only,
+                            // ambiguous length could be used for request smuggling
+                            let l = ascii_str(&src[self.header.value_range()]);
+                            if l.len() > 1 && l.starts_with('0') {
+                                return Err(Error::ContentLength)
+                            }
                             match l.parse::<u64> () {
-                                Ok(v) => self.length = Some(v),
-                                Err(..) => return Err(Error::ContentLength)
+                                Ok(v) if v <= self.max_content_length => self.length = Some(v),
+                                _ => return Err(Error::ContentLength)
                             }
                             continue 'run
                         } else if !is_num(ch) {
-                            return Err(Error::ContentLength);
+                            return Err(self.lf_error(ch, Error::ContentLength));
                         }
                     }
                     bytes.advance(len);
-                    self.header.update_name_len(len);
+                    self.header.update_value_len(len);
                     break
                 },
                 ParseHeader::Value => {
@@ -694,6 +1155,9 @@ impl Decoder for RequestDecoder {
                         let ch = bytes.next();
                         if ch == CR {
                             bytes.advance(idx+1);
+                            if bytes.pos() > MAX_HEADERS_SIZE {
+                                return Err(Error::LineTooLong)
+                            }
                             // check for specific tokens
                             if header_token.completed() {
                                 self.update_msg_state(header_token);
@@ -703,8 +1167,29 @@ impl Decoder for RequestDecoder {
                             if self.header.is_overflow(self.max_line_size) {
                                 return Err(Error::LineTooLong)
                             }
+                            match header_name {
+                                ParseHeaderName::TransferEncoding(..) => {
+                                    let range = self.header.value_range();
+                                    self.update_transfer_codings(&src[range])?;
+                                },
+                                // second Host could be used for request smuggling,
+                                // folded value of the same header extends the range
+                                ParseHeaderName::Host(..) => {
+                                    let range = self.header.value_range();
+                                    if let Some((start, _)) = self.request.host_range() {
+                                        if start as usize != range.start {
+                                            return Err(Error::DuplicateHost)
+                                        }
+                                    }
+                                    self.request.update_host(range);
+                                },
+                                _ => (),
+                            }
                             continue 'run
+                        } else if ch == LF && self.strict {
+                            return Err(Error::BareLf);
                         } else if ! (is_vchar(ch) || is_obs_text(ch) || is_ows(ch)) {
+                            // NUL, bare LF and other control chars
                             return Err(Error::BadHeader);
                         }
                         if is_token(ch) {
@@ -728,7 +1213,7 @@ impl Decoder for RequestDecoder {
                     break
                 },
                 ParseHeader::ValueEol =>
-                    match parse_crlf(&mut bytes, CRLF::LF, Error::BadHeader)? {
+                    match parse_crlf(&mut bytes, CRLF::LF, Error::BadHeader, self.strict)? {
                         Status::Complete(..) =>
                             state = State::Header(ParseHeader::Eol),
                         Status::Partial(..) => break
@@ -763,8 +1248,7 @@ impl Decoder for RequestDecoder {
                             let count = count + idx;
                             let origin = bytes.origin(count);
 
-                            let hex = unsafe {
-                                std::str::from_utf8_unchecked(&src[origin..origin+count]) };
+                            let hex = ascii_str(&src[origin..origin+count]);
 
                             let size = match u64::from_str_radix(hex, 16) {
                                 Ok(v) => v,
@@ -786,7 +1270,7 @@ impl Decoder for RequestDecoder {
                             state = State::Body(ParseBody::ChunkSizeEol(size));
                             continue 'run
                         } else if !is_hex(ch) {
-                            return Err(Error::TransferEncoding);
+                            return Err(self.lf_error(ch, Error::TransferEncoding));
                         }
                         bytes.bump();
                     }
@@ -800,7 +1284,11 @@ impl Decoder for RequestDecoder {
 
                     for idx in 0..len {
                         let ch = bytes.next();
-                        if ch == LF && prev == CR {
+                        if ch == LF && prev != CR {
+                            // bare LF is rejected in any mode, peers could
+                            // disagree on where chunk data starts
+                            return Err(self.lf_error(ch, Error::TransferEncoding));
+                        } else if ch == LF {
                             bytes.advance(idx+1);
                             if size == 0 {
                                 state = State::Body(ParseBody::ChunkMaybeTrailers);
@@ -838,7 +1326,7 @@ impl Decoder for RequestDecoder {
                     break
                 },
                 ParseBody::ChunkEOL(marker) =>
-                    match parse_crlf(&mut bytes, marker, Error::TransferEncoding)? {
+                    match parse_crlf(&mut bytes, marker, Error::TransferEncoding, self.strict)? {
                         Status::Complete(..) => {
                             state = State::Body(ParseBody::ChunkSize(0))
                         },
@@ -854,13 +1342,15 @@ impl Decoder for RequestDecoder {
                                 if ch == LF {
                                     state = State::Done;
                                     src.split_to(bytes.pos()+2);
-                                    bytes = BytesPtr::new(src.as_ref(), 0);
+                                    bytes = C::new(src.as_ref(), 0);
                                 } else {
                                     state = State::Body(ParseBody::ChunkTrailers(CRLF::CR));
                                 }
                             } else {
                                 break
                             }
+                        } else if ch == LF && self.strict {
+                            return Err(Error::BareLf);
                         } else {
                             state = State::Body(ParseBody::ChunkTrailers(CRLF::CR))
                         }
@@ -871,7 +1361,9 @@ impl Decoder for RequestDecoder {
                     let len = bytes.len();
                     for idx in 0..len {
                         let ch = bytes.next();
-                        if ch == marker.val() {
+                        if ch == LF && marker.val() == CR && self.strict {
+                            return Err(Error::BareLf);
+                        } else if ch == marker.val() {
                             bytes.advance(idx+1);
                             if marker.val() == LF {
                                 state = State::Body(ParseBody::ChunkMaybeTrailers);
@@ -896,22 +1388,59 @@ impl Decoder for RequestDecoder {
                 self.start = 0;
                 self.meth_pos = 0;
                 self.meth_end = 0;
+                // payload is consumed, connection type is still
+                // available for the completed message
+                self.length = None;
+                self.chunked = false;
                 self.state = State::Status(ParseStatusLine::Skip(CRLF::CR));
                 return Ok(Some(RequestMessage::Completed))
-            }
+            },
+            State::Upgrade(kind) => {
+                self.state = State::Upgraded;
+                return Ok(Some(RequestMessage::Upgrade {
+                    protocol: kind, remaining: src.take().freeze()}))
+            },
+            State::Upgraded => return Ok(None),
             }}
         self.start = bytes.pos();
         self.state = state;
         Ok(None)
     }
+}
+
+impl Decoder for RequestDecoder {
+    type Item = RequestMessage;
+    type Error = Error;
+
+    #[cfg(not(feature = "safe_parser"))]
+    fn decode(&mut self, src: &mut BytesMut) -> std::result::Result<Option<Self::Item>, Self::Error> {
+        if self.checked {
+            self.decode_with::<Checked>(src)
+        } else {
+            self.decode_with::<Unchecked>(src)
+        }
+    }
+
+    #[cfg(feature = "safe_parser")]
+    fn decode(&mut self, src: &mut BytesMut) -> std::result::Result<Option<Self::Item>, Self::Error> {
+        self.decode_with::<Checked>(src)
+    }
 
     fn decode_eof(&mut self, buf: &mut BytesMut) -> std::result::Result<Option<Self::Item>, Self::Error> {
+        // connection is closed, flush rest of payload and complete message
+        if let State::Body(ParseBody::Unsized) = self.state {
+            if !buf.is_empty() {
+                return Ok(Some(RequestMessage::Body(buf.take().freeze())))
+            }
+            self.state = State::Done;
+        }
+
         let item = self.decode(buf)?;
 
         match self.state {
-            State::Body(ParseBody::Length(..)) => Err(Error::PayloadNotCompleted),
-            State::Body(ParseBody::Unsized) => Ok(item),
             State::Body(..) => Err(Error::PayloadNotCompleted),
+            State::Status(ParseStatusLine::Skip(..)) => Ok(item),
+            State::Status(..) | State::Header(..) => Err(Error::HeadersNotCompleted),
             _ => Ok(item)
         }
     }
@@ -971,6 +1500,9 @@ const CR: u8 = b'\r';
 const LF: u8 = b'\n';
 const HTAB: u8 = b'\t';
 
+// header positions are stored as u16, including final CRLF CRLF
+const MAX_HEADERS_SIZE: usize = std::u16::MAX as usize - 4;
+
 struct Token{
     len: usize,
     token: &'static [u8],
@@ -986,6 +1518,9 @@ const WEBSOCKET: Token = Token {len: 9, token: b"websocket"};
 const CHUNKED: Token = Token {len: 7, token: b"chunked"};
 const KEEP_ALIVE: Token = Token {len: 10, token: b"keep-alive"};
 const CLOSE: Token = Token {len: 5, token: b"close"};
+const EXPECT: Token = Token {len: 6, token: b"expect"};
+const HOST: Token = Token {len: 4, token: b"host"};
+const CONTINUE: Token = Token {len: 12, token: b"100-continue"};
 const GZIP: Token = Token {len: 4, token: b"gzip"};
 const DEFLATE: Token = Token {len: 7, token: b"deflate"};
 const UPGRADE: Token = Token {len: 7, token: b"upgrade"};
@@ -1007,7 +1542,7 @@ fn is_hex(ch: u8) -> bool {
 
 #[inline]
 fn is_vchar(ch: u8) -> bool {
-    ch >= b'!' || ch <= b'~'  // 0x21 .. 0x7E
+    ch >= b'!' && ch <= b'~'  // 0x21 .. 0x7E
 }
 
 #[inline]
@@ -1016,9 +1551,16 @@ fn is_ows(ch: u8) -> bool {
 }
 
 
+#[inline]
+fn trim_ows(val: &[u8]) -> &[u8] {
+    let start = val.iter().position(|ch| !is_ows(*ch)).unwrap_or(val.len());
+    let end = val.iter().rposition(|ch| !is_ows(*ch)).map(|idx| idx + 1).unwrap_or(start);
+    &val[start..end]
+}
+
 #[inline]
 fn is_obs_text(ch: u8) -> bool {
-    ch >= 0x80 || ch <= 0xfe  // 0x80 .. 0xFF
+    ch >= 0x80  // 0x80 .. 0xFF
 }
 
 
@@ -1038,13 +1580,19 @@ fn is_token(b: u8) -> bool {
     TOKENS[b as usize] != 0
 }
 
+// request line separator, HTAB is accepted if extra whitespace is allowed
 #[inline]
-fn parse_token(bytes: &mut BytesPtr, stop: u8) -> Result<usize, usize> {
+fn is_status_sep(ch: u8, ws: bool) -> bool {
+    ch == SP || (ch == HTAB && ws)
+}
+
+#[inline]
+fn parse_token<C: Cursor>(bytes: &mut C, ws: bool) -> Result<usize, usize> {
     let len = bytes.len();
 
     for idx in 0..len {
         let b = bytes.next();
-        if b == stop {
+        if is_status_sep(b, ws) {
             bytes.advance(idx+1);
             return Ok(Status::Complete(idx));
         } else if !is_token(b) {
@@ -1056,7 +1604,7 @@ fn parse_token(bytes: &mut BytesPtr, stop: u8) -> Result<usize, usize> {
 }
 
 #[inline]
-fn parse_ows(bytes: &mut BytesPtr) -> Result<(), ()> {
+fn parse_ows<C: Cursor>(bytes: &mut C) -> Result<(), ()> {
     loop {
         if let Some(ch) = bytes.get_maybe() {
             if is_ows(ch) {
@@ -1072,12 +1620,12 @@ fn parse_ows(bytes: &mut BytesPtr) -> Result<(), ()> {
 }
 
 #[inline]
-fn parse_path(bytes: &mut BytesPtr) -> Result<usize, usize> {
+fn parse_path<C: Cursor>(bytes: &mut C, ws: bool) -> Result<usize, usize> {
     let len = bytes.len();
 
     for idx in 0..len {
         let b = bytes.next();
-        if b == SP {
+        if is_status_sep(b, ws) {
             bytes.advance(idx+1);
             return Ok(Status::Complete(idx));
         } else if !is_url(b) {
@@ -1107,11 +1655,15 @@ macro_rules! expect {
 }
 
 #[inline]
-fn parse_crlf(bytes: &mut BytesPtr, marker: CRLF, err: Error) -> Result<(), CRLF> {
+fn parse_crlf<C: Cursor>(bytes: &mut C, marker: CRLF, err: Error, strict: bool)
+              -> Result<(), CRLF>
+{
     match marker {
         CRLF::CR => match bytes.next_maybe() {
             Some(ch) => {
-                if ch != CR {
+                if ch == LF && strict {
+                    Err(Error::BareLf)
+                } else if ch != CR {
                     Err(err)
                 } else {
                     match bytes.next_maybe() {
@@ -1125,7 +1677,7 @@ fn parse_crlf(bytes: &mut BytesPtr, marker: CRLF, err: Error) -> Result<(), CRLF
                     }
                 }
             },
-            None => Ok(Status::Partial(CRLF::LF)),
+            None => Ok(Status::Partial(CRLF::CR)),
         },
         CRLF::LF => match bytes.next_maybe() {
             Some(ch) => {
@@ -1141,7 +1693,7 @@ fn parse_crlf(bytes: &mut BytesPtr, marker: CRLF, err: Error) -> Result<(), CRLF
 }
 
 #[inline]
-fn parse_version(bytes: &mut BytesPtr) -> Result<Version, usize> {
+fn parse_version<C: Cursor>(bytes: &mut C) -> Result<Version, usize> {
     if bytes.len() < 9 {
         Ok(Status::Partial(0))
     } else {
@@ -1161,17 +1713,80 @@ fn parse_version(bytes: &mut BytesPtr) -> Result<Version, usize> {
     }
 }
 
-struct BytesPtr {
+#[cfg(not(feature = "safe_parser"))]
+#[inline]
+fn ascii_str(bytes: &[u8]) -> &str {
+    unsafe { std::str::from_utf8_unchecked(bytes) }
+}
+
+// non utf-8 value fails to parse as a number, same as on fast path
+#[cfg(feature = "safe_parser")]
+#[inline]
+fn ascii_str(bytes: &[u8]) -> &str {
+    std::str::from_utf8(bytes).unwrap_or("")
+}
+
+/// Byte access of parser, unchecked `BytesPtr` or bounds checked `CheckedBytes`
+trait Cursor {
+    fn len(&self) -> usize;
+    fn pos(&self) -> usize;
+    fn advance(&mut self, cnt: usize);
+    fn bump(&mut self);
+    fn origin(&self, count: usize) -> usize;
+    fn origin_offset(&self) -> usize;
+    fn get(&mut self) -> u8;
+    fn get_maybe(&mut self) -> Option<u8>;
+    fn get_next_maybe(&mut self) -> Option<u8>;
+    fn next(&mut self) -> u8;
+    fn next_maybe(&mut self) -> Option<u8>;
+}
+
+/// Creates `Cursor` over source buffer, decoder re-creates it
+/// after buffer gets split
+trait NewCursor<'a> {
+    type Cursor: Cursor + Copy;
+
+    fn new(slice: &'a [u8], start: usize) -> Self::Cursor;
+}
+
+#[cfg(not(feature = "safe_parser"))]
+enum Unchecked {}
+
+#[cfg(not(feature = "safe_parser"))]
+impl<'a> NewCursor<'a> for Unchecked {
+    type Cursor = BytesPtr<'a>;
+
+    #[inline]
+    fn new(slice: &'a [u8], start: usize) -> BytesPtr<'a> {
+        BytesPtr::new(slice, start)
+    }
+}
+
+enum Checked {}
+
+impl<'a> NewCursor<'a> for Checked {
+    type Cursor = CheckedBytes<'a>;
+
+    #[inline]
+    fn new(slice: &'a [u8], start: usize) -> CheckedBytes<'a> {
+        CheckedBytes::new(slice, start)
+    }
+}
+
+#[cfg(not(feature = "safe_parser"))]
+#[derive(Copy, Clone)]
+struct BytesPtr<'a> {
     ptr: *const u8,
     size: usize,
     len: usize,
+    slice: std::marker::PhantomData<&'a [u8]>,
 }
 
-
-impl BytesPtr {
+#[cfg(not(feature = "safe_parser"))]
+impl<'a> BytesPtr<'a> {
 
     #[inline]
-    fn new(slice: &[u8], start: usize) -> BytesPtr {
+    fn new(slice: &'a [u8], start: usize) -> BytesPtr<'a> {
         let len = slice.len();
         let ptr = if start > 0 {
             unsafe { slice.as_ptr().offset(start as isize) }
@@ -1182,8 +1797,13 @@ impl BytesPtr {
             ptr: ptr,
             size: len,
             len: len - start,
+            slice: std::marker::PhantomData,
         }
     }
+}
+
+#[cfg(not(feature = "safe_parser"))]
+impl<'a> Cursor for BytesPtr<'a> {
 
     #[inline]
     fn len(&self) -> usize {
@@ -1263,3 +1883,106 @@ impl BytesPtr {
     }
 
 }
+
+/// Bounds checked replacement of `BytesPtr`, used with `safe_parser`
+/// feature or `set_checked`.
+///
+/// Allows to run parser under Miri or sanitizers without unsafe code,
+/// and to compare both parser paths on same input.
+#[derive(Copy, Clone)]
+struct CheckedBytes<'a> {
+    buf: &'a [u8],
+    idx: usize,
+    size: usize,
+    len: usize,
+}
+
+impl<'a> CheckedBytes<'a> {
+
+    #[inline]
+    fn new(slice: &'a [u8], start: usize) -> CheckedBytes<'a> {
+        let len = slice.len();
+        CheckedBytes {
+            buf: slice,
+            idx: start,
+            size: len,
+            len: len - start,
+        }
+    }
+}
+
+impl<'a> Cursor for CheckedBytes<'a> {
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn pos(&self) -> usize {
+        self.size - self.len
+    }
+
+    #[inline]
+    fn advance(&mut self, cnt: usize) {
+        self.len -= cnt
+    }
+
+    #[inline]
+    fn bump(&mut self) {
+        self.len -= 1;
+        self.idx += 1;
+    }
+
+    #[inline]
+    fn origin(&self, count: usize) -> usize {
+        self.size - self.len - count
+    }
+
+    #[inline]
+    fn origin_offset(&self) -> usize {
+        self.size - self.len
+    }
+
+    #[inline]
+    fn get(&mut self) -> u8 {
+        self.buf[self.idx]
+    }
+
+    #[inline]
+    fn get_maybe(&mut self) -> Option<u8> {
+        if self.len != 0 {
+            Some(self.buf[self.idx])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn get_next_maybe(&mut self) -> Option<u8> {
+        if self.len > 1 {
+            Some(self.buf[self.idx + 1])
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> u8 {
+        let b = self.buf[self.idx];
+        self.idx += 1;
+        b
+    }
+
+    #[inline]
+    fn next_maybe(&mut self) -> Option<u8> {
+        if self.len != 0 {
+            let b = self.buf[self.idx];
+            self.len -= 1;
+            self.idx += 1;
+            Some(b)
+        } else {
+            None
+        }
+    }
+}
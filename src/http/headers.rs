@@ -12,82 +12,135 @@ use bytes::{Bytes, BytesMut};
 
 #[derive(Debug)]
 pub struct Headers {
-    headers: HashMap<u64, Header>,
+    // headers in order of appearance
+    headers: Vec<Header>,
+    // name hash -> index of last header with that name
+    index: HashMap<u64, usize>,
     bytes: Option<Bytes>,
     last_pos: u16,
 }
 
+#[inline]
+fn name_hash(name: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for byte in name.bytes().map(|b| b.to_ascii_lowercase()) {
+        hasher.write_u8(byte);
+    }
+    hasher.finish()
+}
+
 impl Headers {
 
     pub fn new() -> Headers {
-        Headers { headers: HashMap::with_capacity(64),
+        Headers { headers: Vec::with_capacity(64),
+                  index: HashMap::with_capacity(64),
                   bytes: None,
                   last_pos: 0,
         }
     }
 
+    /// All headers, values that contain obs-text are decoded lossy
     pub fn headers(&self) -> Vec<(String, String)> {
         let mut vec = Vec::new();
 
         if let Some(ref bytes) = self.bytes {
-            for header in self.headers.values() {
+            for header in self.headers.iter() {
                 vec.push((
-                    String::from(
-                        unsafe {
-                            std::str::from_utf8_unchecked(&bytes[header.name_range()])
-                        }
-                    ),
-
-                    String::from(
-                        unsafe {
-                            std::str::from_utf8_unchecked(&bytes[header.value_range()])
-                        }
-                    )));
+                    String::from_utf8_lossy(&bytes[header.name_range()]).into_owned(),
+                    String::from_utf8_lossy(&bytes[header.value_range()]).into_owned()));
             }
         }
         vec
     }
-    
-    pub fn get(&self, name: &str) -> Option<&str> {
-        let mut hasher = DefaultHasher::new();
-        for byte in name.bytes().map(|b| b.to_ascii_lowercase()) {
-            hasher.write_u8(byte);
-        }
-        let hash = hasher.finish();
 
-        if let Some(ref header) = self.headers.get(&hash) {
-            if let Some(ref bytes) = self.bytes {
-                Some(
-                    unsafe {
-                        std::str::from_utf8_unchecked(&bytes[header.value_range()])
-                    })
-            } else {
-                None
+    /// All headers as raw bytes
+    pub fn headers_bytes(&self) -> Vec<(&[u8], &[u8])> {
+        let mut vec = Vec::new();
+
+        if let Some(ref bytes) = self.bytes {
+            for header in self.headers.iter() {
+                vec.push((&bytes[header.name_range()], &bytes[header.value_range()]));
             }
-        } else {
-            None
         }
+        vec
     }
 
+    /// Header value, case insensitive lookup.
+    ///
+    /// Header value could contain obs-text (0x80-0xFF) bytes,
+    /// returns `None` if value is not valid utf-8, use `get_bytes` for raw value.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.get_bytes(name).and_then(|val| std::str::from_utf8(val).ok())
+    }
+
+    /// Raw header value, case insensitive lookup
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        self.value_bytes(name_hash(name))
+    }
+
+    /// All values of header, case insensitive lookup.
+    ///
+    /// Values are returned in order of appearance,
+    /// values that are not valid utf-8 are skipped.
+    pub fn get_all<'h>(&'h self, name: &str) -> HeaderValues<'h> {
+        HeaderValues {
+            hash: name_hash(name),
+            headers: self.headers.iter(),
+            bytes: self.bytes.as_ref(),
+        }
+    }
+
+    /// Check if header is present, case insensitive lookup
+    pub fn contains(&self, name: &str) -> bool {
+        self.index.contains_key(&name_hash(name))
+    }
+
+    /// Header value, case sensitive lookup.
+    ///
+    /// Returns `None` if value is not valid utf-8, see `get`.
     pub fn get_case(&self, name: &str) -> Option<&str> {
+        self.get_case_bytes(name).and_then(|val| std::str::from_utf8(val).ok())
+    }
+
+    /// Raw header value, case sensitive lookup
+    pub fn get_case_bytes(&self, name: &str) -> Option<&[u8]> {
         let mut hasher = DefaultHasher::new(); //self.hasher.borrow_mut();
         for byte in name.bytes() {
             hasher.write_u8(byte);
         }
-        let hash = hasher.finish();
+        self.value_bytes(hasher.finish())
+    }
 
-        if let Some(ref header) = self.headers.get(&hash) {
+    /// Raw header name as it was sent by client
+    pub fn name_bytes(&self, name: &str) -> Option<&[u8]> {
+        if let Some(idx) = self.index.get(&name_hash(name)) {
             if let Some(ref bytes) = self.bytes {
-                Some(
-                    unsafe {
-                        std::str::from_utf8_unchecked(&bytes[header.value_range()])
-                    })
-            } else {
-                None
+                return Some(&bytes[self.headers[*idx].name_range()])
+            }
+        }
+        None
+    }
+
+    /// Raw bytes of header block, i.e. value range recorded by decoder
+    pub fn range_bytes(&self, range: Range<usize>) -> Option<&[u8]> {
+        match self.bytes {
+            Some(ref bytes) => bytes.get(range),
+            None => None,
+        }
+    }
+
+    fn value_bytes(&self, hash: u64) -> Option<&[u8]> {
+        if let Some(idx) = self.index.get(&hash) {
+            if let Some(ref bytes) = self.bytes {
+                return Some(&bytes[self.headers[*idx].value_range()])
             }
-        } else {
-            None
         }
+        None
+    }
+
+    /// Number of headers, including repeated ones
+    pub fn len(&self) -> usize {
+        self.headers.len()
     }
 
     pub fn has(&self) -> bool {
@@ -108,7 +161,8 @@ impl WriteHeaders for Headers {
 
     fn append(&mut self, header: Header) {
         self.last_pos = header.end();
-        self.headers.insert(header.hash, header);
+        self.index.insert(header.hash, self.headers.len());
+        self.headers.push(header);
     }
 
     fn flush(&mut self, src: &mut BytesMut) {
@@ -117,6 +171,32 @@ impl WriteHeaders for Headers {
     }
 }
 
+/// Iterator over values of headers with the same name
+pub struct HeaderValues<'h> {
+    hash: u64,
+    headers: std::slice::Iter<'h, Header>,
+    bytes: Option<&'h Bytes>,
+}
+
+impl<'h> Iterator for HeaderValues<'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = match self.bytes {
+            Some(bytes) => bytes,
+            None => return None,
+        };
+        while let Some(header) = self.headers.next() {
+            if header.hash == self.hash {
+                if let Ok(val) = std::str::from_utf8(&bytes[header.value_range()]) {
+                    return Some(val)
+                }
+            }
+        }
+        None
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Header {
     hash: u64,
@@ -1,4 +1,5 @@
 use std;
+use std::ops::Range;
 use bytes::Bytes;
 
 use http::headers::Headers;
@@ -25,6 +26,34 @@ pub enum ContentCompression {
     Deflate,
 }
 
+/// Form of request-target, RFC 7230 section 5.3
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TargetForm {
+    /// absolute-path [ "?" query ]
+    Origin,
+    /// absolute-URI, used for requests to proxy
+    Absolute,
+    /// host:port, used for CONNECT requests
+    Authority,
+    /// "*", used for server-wide OPTIONS requests
+    Asterisk,
+}
+
+/// Request method, method names are case-sensitive
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Method<'a> {
+    Get,
+    Post,
+    Put,
+    Delete,
+    Head,
+    Options,
+    Patch,
+    Connect,
+    Trace,
+    Other(&'a str),
+}
+
 #[derive(Debug)]
 pub struct Request {
     pub version: Version,
@@ -32,11 +61,19 @@ pub struct Request {
     pub connection: ConnectionType,
     pub chunked: bool,
     pub websocket: bool,
+    /// Client waits for `100 Continue` before sending payload
+    pub expect_continue: bool,
     pub compress: ContentCompression,
+    /// Transfer codings applied before chunked, in order of application,
+    /// payload has to be decoded with them after dechunking
+    pub transfer_codings: Vec<String>,
+    /// Body length from Content-Length header, None for chunked body
+    pub content_length: Option<u64>,
 
     bytes: Bytes,
     meth: (u8, u8),
     path: (u8, u16),
+    host: Option<(u16, u16)>,
 }
 
 impl Request {
@@ -52,6 +89,94 @@ impl Request {
         unsafe { std::str::from_utf8_unchecked(
             &self.bytes[(self.path.0 as usize)..(self.path.1 as usize)]) }
     }
+
+    /// Value of Host header, recorded by decoder, so no header lookup
+    /// is needed. Returns `None` if value is not valid utf-8.
+    pub fn host(&self) -> Option<&str> {
+        self.host_bytes().and_then(|val| std::str::from_utf8(val).ok())
+    }
+
+    /// Raw value of Host header
+    pub fn host_bytes(&self) -> Option<&[u8]> {
+        match self.host {
+            Some((start, end)) =>
+                self.headers.range_bytes(Range{start: start as usize, end: end as usize}),
+            None => None,
+        }
+    }
+
+    /// Request method for `match` based dispatch
+    pub fn method_kind(&self) -> Method {
+        let method = self.method();
+        match method.as_bytes() {
+            b"GET" => Method::Get,
+            b"POST" => Method::Post,
+            b"PUT" => Method::Put,
+            b"DELETE" => Method::Delete,
+            b"HEAD" => Method::Head,
+            b"OPTIONS" => Method::Options,
+            b"PATCH" => Method::Patch,
+            b"CONNECT" => Method::Connect,
+            b"TRACE" => Method::Trace,
+            _ => Method::Other(method),
+        }
+    }
+
+    /// Form of request-target
+    pub fn target_form(&self) -> TargetForm {
+        let path = self.path();
+        if path.starts_with('/') {
+            TargetForm::Origin
+        } else if path == "*" {
+            TargetForm::Asterisk
+        } else if scheme_len(path).is_some() {
+            TargetForm::Absolute
+        } else {
+            TargetForm::Authority
+        }
+    }
+
+    /// Authority component of request-target,
+    /// available for authority-form and absolute-form targets
+    pub fn authority(&self) -> Option<&str> {
+        let path = self.path();
+        match self.target_form() {
+            TargetForm::Authority => Some(path),
+            TargetForm::Absolute => {
+                let rest = match scheme_len(path) {
+                    Some(len) => &path[len+3..],
+                    None => return None,
+                };
+                let end = rest.find(|ch| ch == '/' || ch == '?' || ch == '#')
+                    .unwrap_or(rest.len());
+                Some(&rest[..end])
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Length of scheme if path starts with `scheme "://"`
+///
+/// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+fn scheme_len(path: &str) -> Option<usize> {
+    let idx = match path.find("://") {
+        Some(idx) if idx > 0 => idx,
+        _ => return None,
+    };
+    let scheme = path[..idx].as_bytes();
+
+    match scheme[0] {
+        b'a'...b'z' | b'A'...b'Z' => (),
+        _ => return None,
+    }
+    for ch in scheme {
+        match *ch {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'+' | b'-' | b'.' => (),
+            _ => return None,
+        }
+    }
+    Some(idx)
 }
 
 pub trait RequestUpdater {
@@ -59,6 +184,10 @@ pub trait RequestUpdater {
     fn new() -> Request;
 
     fn update_status(&mut self, src: Bytes, meth: (u8, u8), path: (u8, u16));
+
+    fn update_host(&mut self, range: Range<usize>);
+
+    fn host_range(&self) -> Option<(u16, u16)>;
 }
 
 impl RequestUpdater for Request {
@@ -70,9 +199,13 @@ impl RequestUpdater for Request {
             connection: ConnectionType::KeepAlive,
             chunked: false,
             websocket: false,
+            expect_continue: false,
             compress: ContentCompression::Default,
+            transfer_codings: Vec::new(),
+            content_length: None,
             meth: (0, 0),
             path: (0, 0),
+            host: None,
             bytes: Bytes::new(),
         }
     }
@@ -82,4 +215,12 @@ impl RequestUpdater for Request {
         self.meth = meth;
         self.path = path;
     }
+
+    fn update_host(&mut self, range: Range<usize>) {
+        self.host = Some((range.start as u16, range.end as u16));
+    }
+
+    fn host_range(&self) -> Option<(u16, u16)> {
+        self.host
+    }
 }
@@ -1,14 +1,22 @@
+mod body;
 mod codec;
 mod decoder;
 mod headers;
 mod message;
+mod stream;
+mod pyparser;
 //mod transport;
 //pub mod pyreq;
 //pub mod pytransport;
 
+pub use self::body::BodyCollector;
 pub use self::codec::{EncoderMessage, HttpTransportCodec};
-pub use self::headers::{Headers};
-pub use self::decoder::{Error, RequestDecoder, RequestMessage};
-pub use self::message::{Version, Request, ContentCompression, ConnectionType};
+pub use self::headers::{Headers, HeaderValues};
+pub use self::decoder::{
+    Error, RequestDecoder, RequestDecoderBuilder, RequestMessage, UpgradeKind};
+pub use self::message::{
+    Version, Method, Request, ContentCompression, ConnectionType, TargetForm};
+pub use self::stream::{RequestStream, HttpRequest, Payload, Upgraded};
+pub use self::pyparser::{HttpRequestParser, HttpRequestParserPtr, new_request_parser};
 //pub use self::transport::{http_transport_factory};
 //pub use self::pyreq::{PyRequest, StreamReader, RawHeaders, Url, PayloadWriter};
@@ -0,0 +1,105 @@
+use std::ascii::AsciiExt;
+
+use pyo3::*;
+use bytes::BytesMut;
+use tokio_io::codec::Decoder;
+
+use http::decoder::{RequestDecoder, RequestMessage, UpgradeKind};
+use http::message::{Request, Version};
+
+
+/// Python interface to http request decoder
+///
+/// Data is buffered until full message head or body chunk is available.
+/// `feed_data()` returns list of parsed events:
+///
+///   ('message', method, path, (major, minor), headers)
+///   ('body', bytes)
+///   ('completed',)
+///   ('upgrade', 'websocket' | 'connect', bytes)
+///
+/// No events are produced after 'upgrade', rest of data belongs
+/// to new protocol.
+///
+#[py::class]
+pub struct HttpRequestParser {
+    decoder: RequestDecoder,
+    buf: BytesMut,
+    token: PyToken,
+}
+
+#[py::ptr(HttpRequestParser)]
+pub struct HttpRequestParserPtr(PyPtr);
+
+pub fn new_request_parser(py: Python) -> PyResult<HttpRequestParserPtr> {
+    py.init(|t| HttpRequestParser {
+        decoder: RequestDecoder::new(),
+        buf: BytesMut::new(),
+        token: t})
+}
+
+#[py::methods]
+impl HttpRequestParser {
+
+    //
+    // Feed data to parser and return list of parsed events.
+    //
+    // ValueError is raised for malformed request, parser
+    // is not usable after error.
+    //
+    fn feed_data(&mut self, py: Python, data: PyBytes) -> PyResult<PyList> {
+        self.buf.extend(data.data(py));
+
+        let mut events = Vec::new();
+        loop {
+            match self.decoder.decode(&mut self.buf) {
+                Ok(Some(RequestMessage::Message(msg))) =>
+                    events.push(message_event(py, &msg)?),
+                Ok(Some(RequestMessage::Body(chunk))) =>
+                    events.push(("body", PyBytes::new(py, chunk.as_ref())).to_object(py)),
+                Ok(Some(RequestMessage::Completed)) =>
+                    events.push(("completed",).to_object(py)),
+                Ok(Some(RequestMessage::Upgrade { protocol, remaining })) => {
+                    let protocol = match protocol {
+                        UpgradeKind::WebSocket => "websocket",
+                        UpgradeKind::Connect => "connect",
+                    };
+                    events.push(("upgrade", protocol,
+                                 PyBytes::new(py, remaining.as_ref())).to_object(py));
+                },
+                Ok(None) =>
+                    return Ok(PyList::new(py, &events)),
+                Err(err) =>
+                    return Err(PyErr::new::<exc::ValueError, _>(
+                        py, format!("Malformed http request: {:?}", err))),
+            }
+        }
+    }
+}
+
+// header names are lower-cased, values of repeated headers
+// are combined with comma, RFC 7230 section 3.2.2
+fn message_event(py: Python, msg: &Request) -> PyResult<PyObject> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for (name, value) in msg.headers.headers() {
+        let name = name.to_ascii_lowercase();
+        if let Some(idx) = headers.iter().position(|h| h.0 == name) {
+            headers[idx].1.push_str(", ");
+            headers[idx].1.push_str(&value);
+            continue
+        }
+        headers.push((name, value));
+    }
+
+    let dict = PyDict::new(py);
+    for (name, value) in headers {
+        dict.set_item(py, name, value)?;
+    }
+
+    let version = match msg.version {
+        Version::Http10 => (1, 0),
+        Version::Http11 => (1, 1),
+    };
+
+    Ok(("message", msg.method(), msg.path(), version, dict).to_object(py))
+}
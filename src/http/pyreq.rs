@@ -439,7 +439,7 @@ impl RawHeaders {
 
     fn __contains__(&self, py: Python, key: PyString) -> PyResult<bool> {
         let key = key.to_string(py)?;
-        if let Some(_) = self.headers(py).get(key.borrow()) {
+        if let Some(_) = self.headers(py).get_bytes(key.borrow()) {
             Ok(true)
         } else {
             Ok(false)
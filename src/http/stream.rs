@@ -0,0 +1,176 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use bytes::Bytes;
+use futures::{Async, Poll, Stream};
+use tokio_io::AsyncRead;
+use tokio_io::codec::FramedRead;
+
+use http::decoder::{Error, RequestDecoder, RequestMessage, UpgradeKind};
+use http::message::Request;
+
+
+/// Stream of http requests over one connection,
+/// keep-alive connection produces multiple requests.
+///
+/// Payload is read from the same connection, so it is only available
+/// until next request is requested from stream. Unread payload
+/// of previous request gets skipped.
+///
+/// Stream ends after upgrade request, connection is available
+/// with `into_upgraded`.
+pub struct RequestStream<T> {
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+/// Message head and payload stream of http request
+pub struct HttpRequest<T> {
+    pub message: Request,
+    pub payload: Payload<T>,
+}
+
+/// Connection after upgrade request, i.e. WebSocket handshake or CONNECT
+pub struct Upgraded<T> {
+    pub protocol: UpgradeKind,
+    pub io: T,
+    /// data received after request head, belongs to new protocol
+    pub remaining: Bytes,
+}
+
+/// Stream of request payload chunks
+pub struct Payload<T> {
+    id: usize,
+    inner: Rc<RefCell<Inner<T>>>,
+}
+
+struct Inner<T> {
+    framed: FramedRead<T, RequestDecoder>,
+    // id of latest request
+    current: usize,
+    // payload of latest request is not completed yet
+    payload: bool,
+    // upgrade request is received, connection is not http anymore
+    upgrade: Option<(UpgradeKind, Bytes)>,
+}
+
+impl<T> RequestStream<T> where T: AsyncRead {
+
+    pub fn new(io: T) -> RequestStream<T> {
+        RequestStream::with_decoder(io, RequestDecoder::new())
+    }
+
+    /// Use pre-configured decoder, i.e. with strict mode or custom limits
+    pub fn with_decoder(io: T, decoder: RequestDecoder) -> RequestStream<T> {
+        RequestStream {
+            inner: Rc::new(RefCell::new(Inner {
+                framed: FramedRead::new(io, decoder),
+                current: 0,
+                payload: false,
+                upgrade: None,
+            }))
+        }
+    }
+
+    /// Connection and data of new protocol, after stream ended with upgrade
+    /// request. Stream is returned back if it is not upgraded or payload
+    /// of upgrade request is not dropped yet.
+    pub fn into_upgraded(self) -> Result<Upgraded<T>, RequestStream<T>> {
+        if self.inner.borrow().upgrade.is_none() {
+            return Err(self)
+        }
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => {
+                let inner = inner.into_inner();
+                let (protocol, remaining) = inner.upgrade.unwrap();
+                Ok(Upgraded {
+                    protocol: protocol,
+                    io: inner.framed.into_inner(),
+                    remaining: remaining,
+                })
+            },
+            Err(inner) => Err(RequestStream { inner: inner }),
+        }
+    }
+}
+
+impl<T> Inner<T> where T: AsyncRead {
+
+    // read next payload chunk of latest request
+    fn poll_payload(&mut self) -> Poll<Option<Bytes>, Error> {
+        if !self.payload {
+            return Ok(Async::Ready(None))
+        }
+
+        match self.framed.poll()? {
+            Async::Ready(Some(RequestMessage::Body(chunk))) =>
+                Ok(Async::Ready(Some(chunk))),
+            Async::Ready(Some(RequestMessage::Completed)) | Async::Ready(None) => {
+                self.payload = false;
+                Ok(Async::Ready(None))
+            },
+            // data after upgrade request is not http, stop reading connection
+            Async::Ready(Some(RequestMessage::Upgrade { protocol, remaining })) => {
+                self.payload = false;
+                self.upgrade = Some((protocol, remaining));
+                Ok(Async::Ready(None))
+            },
+            Async::Ready(Some(RequestMessage::Message(..))) =>
+                unreachable!("decoder completes message before next one"),
+            Async::NotReady =>
+                Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<T> Stream for RequestStream<T> where T: AsyncRead {
+    type Item = HttpRequest<T>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+
+        // skip unread payload of previous request
+        loop {
+            match inner.poll_payload()? {
+                Async::Ready(Some(_)) => continue,
+                Async::Ready(None) => break,
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+        if inner.upgrade.is_some() {
+            return Ok(Async::Ready(None))
+        }
+
+        match inner.framed.poll()? {
+            Async::Ready(Some(RequestMessage::Message(msg))) => {
+                inner.current += 1;
+                inner.payload = true;
+                Ok(Async::Ready(Some(HttpRequest {
+                    message: msg,
+                    payload: Payload { id: inner.current, inner: self.inner.clone() },
+                })))
+            },
+            Async::Ready(Some(..)) =>
+                unreachable!("decoder starts with message head"),
+            Async::Ready(None) =>
+                Ok(Async::Ready(None)),
+            Async::NotReady =>
+                Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<T> Stream for Payload<T> where T: AsyncRead {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+
+        // next request is already requested
+        if inner.current != self.id {
+            return Ok(Async::Ready(None))
+        }
+        inner.poll_payload()
+    }
+}
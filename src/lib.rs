@@ -34,6 +34,7 @@ mod socket;
 mod server;
 mod client;
 mod signals;
+mod process;
 
 pub use pyo3::*;
 pub use utils::{Classes, PyLogger, ToPyErr, with_py};
@@ -41,16 +42,22 @@ pub use pybytes::PyBytes;
 pub use pyfuture::{PyFuture, PyFuturePtr};
 pub use pytask::{PyTask, PyTaskPtr};
 pub use handle::PyHandle;
-pub use event_loop::{TokioEventLoop, TokioEventLoopPtr, new_event_loop};
+pub use event_loop::{
+    TokioEventLoop, TokioEventLoopPtr, new_event_loop, run_coroutine_threadsafe};
 pub use server::create_server;
 pub use client::create_connection;
+pub use http::new_request_parser;
 
 
 py_module_init!(tokio, PyInit__tokio, |py, m| {
     let _ = env_logger::init();
 
     m.add(py, "__doc__", "Asyncio event loop based on tokio-rs")?;
-    m.add(py, "new_event_loop", py_fn!(py, new_event_loop()))?;
+    m.add(py, "new_event_loop", py_fn!(py, new_event_loop(
+        workers: usize = addrinfo::DEFAULT_WORKERS)))?;
+    m.add(py, "run_coroutine_threadsafe", py_fn!(
+        py, run_coroutine_threadsafe(coro: PyObject, evloop: PyObject)))?;
+    m.add(py, "new_request_parser", py_fn!(py, new_request_parser()))?;
 
     register_classes(py, m)?;
     Ok(())
@@ -68,6 +75,8 @@ pub fn register_classes(py: pyo3::Python, m: &pyo3::PyModule) -> pyo3::PyResult<
     m.add_class::<server::TokioServer>(py)?;
     m.add_class::<socket::Socket>(py)?;
     m.add_class::<transport::PyTcpTransport>(py)?;
+    m.add_class::<client::ResolvedEndpoint>(py)?;
+    m.add_class::<http::HttpRequestParser>(py)?;
 
     //m.add_class::<http::PyRequest>(py)?;
     //m.add_class::<http::StreamReader>(py)?;
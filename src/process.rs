@@ -0,0 +1,149 @@
+use std::io;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::raw::c_int;
+
+use libc;
+use pyo3::*;
+use futures::{Future, Stream};
+use tokio_signal::unix::Signal;
+use tokio_core::reactor::Handle;
+
+use ::TokioEventLoopPtr;
+use handle::PyHandle;
+use utils::{with_py, Classes};
+
+
+type Children = Rc<RefCell<HashMap<libc::pid_t, (PyObject, PyTuple)>>>;
+
+/// Delivers exit status of child processes registered with `add`
+///
+/// SIGCHLD signals coalesce, so all registered pids are checked
+/// with non-blocking waitpid on every signal. Children which are not
+/// registered are left for their owners.
+pub struct ChildWatcher {
+    children: Children,
+    started: bool,
+}
+
+impl ChildWatcher {
+
+    pub fn new() -> ChildWatcher {
+        ChildWatcher {
+            children: Rc::new(RefCell::new(HashMap::new())),
+            started: false,
+        }
+    }
+
+    /// Register `callback(pid, returncode, *args)`, callback runs
+    /// right away if child is terminated already
+    pub fn add(&mut self, py: Python, evloop: TokioEventLoopPtr, handle: &Handle,
+               pid: libc::pid_t, callback: PyObject, args: PyTuple) -> io::Result<()> {
+        if !self.started {
+            // event loop owns the watcher, signal task holds weak reference
+            let ev_ref = Classes.WeakRef.call(py, (evloop.clone_ref(py),), None)
+                .map_err(|_| io::Error::new(
+                    io::ErrorKind::Other, "event loop does not support weak references"))?;
+            let children = self.children.clone();
+
+            handle.spawn(
+                Signal::new(libc::SIGCHLD, handle).and_then(move |signal| {
+                    // child could exit before signal handler got installed
+                    with_py(|py| reap_weak(py, &ev_ref, &children));
+
+                    signal
+                        .take_while(move |_| Ok(with_py(|py| reap_weak(py, &ev_ref, &children))))
+                        .for_each(|_| Ok(()))
+                }).map_err(|err| error!("SIGCHLD handler failed: {}", err)));
+            self.started = true;
+        }
+
+        self.children.borrow_mut().insert(pid, (callback, args));
+
+        // child could exit before registration
+        reap(py, &evloop, &self.children);
+        py.release(evloop);
+        Ok(())
+    }
+
+    pub fn remove(&self, pid: libc::pid_t) -> bool {
+        self.children.borrow_mut().remove(&pid).is_some()
+    }
+
+    pub fn clear(&self) {
+        self.children.borrow_mut().clear()
+    }
+}
+
+
+// run callbacks of terminated children
+fn reap(py: Python, evloop: &TokioEventLoopPtr, children: &Children) {
+    let mut exited = Vec::new();
+    {
+        let mut children = children.borrow_mut();
+        let pids: Vec<libc::pid_t> = children.keys().cloned().collect();
+        for pid in pids {
+            if let Some(returncode) = try_wait(pid) {
+                if let Some((callback, args)) = children.remove(&pid) {
+                    exited.push((pid, returncode, callback, args));
+                }
+            }
+        }
+    }
+
+    for (pid, returncode, callback, args) in exited {
+        let mut cb_args = vec![pid.to_object(py), returncode.to_object(py)];
+        cb_args.extend(args.as_slice(py).iter().map(|arg| arg.clone_ref(py)));
+
+        if let Ok(h) = PyHandle::new(
+            py, evloop.as_ref(py), callback, PyTuple::new(py, &cb_args)) {
+            h.run();
+        }
+    }
+}
+
+// run callbacks of terminated children, false if event loop is gone
+fn reap_weak(py: Python, ev_ref: &PyObject, children: &Children) -> bool {
+    let evloop = ev_ref.call(py, NoArgs, None).ok()
+        .and_then(|ev| TokioEventLoopPtr::downcast_into(py, ev).ok());
+    match evloop {
+        Some(evloop) => {
+            reap(py, &evloop, children);
+            py.release(evloop);
+            true
+        }
+        None => false,
+    }
+}
+
+// asyncio compatible returncode of terminated child,
+// negative signal number if child is killed by signal
+fn try_wait(pid: libc::pid_t) -> Option<c_int> {
+    let mut status: c_int = 0;
+    loop {
+        let res = unsafe { libc::waitpid(pid, &mut status, libc::WNOHANG) };
+        if res == 0 {
+            return None
+        }
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue
+            }
+            // reaped by someone else, exit status is lost
+            warn!("Unknown child process pid {}, will report returncode 255", pid);
+            return Some(255)
+        }
+
+        return Some(unsafe {
+            if libc::WIFSIGNALED(status) {
+                -libc::WTERMSIG(status)
+            } else if libc::WIFEXITED(status) {
+                libc::WEXITSTATUS(status)
+            } else {
+                status
+            }
+        })
+    }
+}
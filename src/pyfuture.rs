@@ -18,6 +18,20 @@ pub enum State {
 
 pub type Callback = SendBoxFnOnce<(PyResult<PyObject>,)>;
 
+//
+// Create error from exception instance, keep exception's traceback,
+// so re-raised exception still points to original frames
+//
+fn exception_err(py: Python, exc: &PyObject) -> PyErr {
+    let mut err = PyErr::from_instance(py, exc.clone_ref(py));
+    if let Ok(tb) = exc.getattr(py, "__traceback__") {
+        if !tb.is_none(py) {
+            err.ptraceback = Some(tb);
+        }
+    }
+    err
+}
+
 pub struct _PyFuture {
     pub evloop: TokioEventLoopPtr,
     sender: Option<oneshot::Sender<PyResult<PyObject>>>,
@@ -31,6 +45,9 @@ pub struct _PyFuture {
 
     // rust callbacks
     rcallbacks: Option<Vec<Callback>>,
+
+    // callbacks are being executed in place
+    dispatching: cell::Cell<bool>,
 }
 
 unsafe impl Send for _PyFuture {}
@@ -52,6 +69,7 @@ impl _PyFuture {
             source_tb: tb,
             callbacks: None,
             rcallbacks: None,
+            dispatching: cell::Cell::new(false),
         }
     }
 
@@ -69,6 +87,7 @@ impl _PyFuture {
             source_tb: tb,
             callbacks: None,
             rcallbacks: None,
+            dispatching: cell::Cell::new(false),
         }
     }
 
@@ -89,6 +108,7 @@ impl _PyFuture {
                     source_tb: tb,
                     callbacks: None,
                     rcallbacks: None,
+                    dispatching: cell::Cell::new(false),
                 }
             }
         }
@@ -162,7 +182,7 @@ impl _PyFuture {
                 }
 
                 match self.exception {
-                    Some(ref err) => Err(PyErr::from_instance(py, err.clone_ref(py))),
+                    Some(ref err) => Err(exception_err(py, err)),
                     None => match self.result {
                         Some(ref res) => Ok(res.clone_ref(py)),
                         None => Err(
@@ -173,6 +193,70 @@ impl _PyFuture {
         }
     }
 
+    fn log_exception(&mut self, py: Python) {
+        self.log_exc_tb.set(false);
+
+        let _: PyResult<()> = (|| {
+            let context = PyDict::new(py);
+            context.set_item(py, "message", "Future exception was never retrieved")?;
+            context.set_item(py, "future", "PyFuture")?;
+            if let Some(tb) = self.source_tb.take() {
+                context.set_item(py, "source_traceback", tb)?;
+            }
+            if let Some(ref exc) = self.exception {
+                context.set_item(py, "exception", exc.clone_ref(py))?;
+            }
+            self.evloop.as_ref(py).call_exception_handler(py, context)?;
+            Ok(())
+        })();
+    }
+
+    //
+    // Python GC support, visit python objects owned by the future
+    //
+    pub fn traverse(&self, visit: &PyVisit) -> Result<(), PyTraverseError> {
+        if let Some(ref callbacks) = self.callbacks {
+            for callback in callbacks.iter() {
+                visit.call(callback)?;
+            }
+        }
+        if let Some(ref result) = self.result {
+            visit.call(result)?;
+        }
+        if let Some(ref exception) = self.exception {
+            visit.call(exception)?;
+        }
+        if let Some(ref tb) = self.source_tb {
+            visit.call(tb)?;
+        }
+        Ok(())
+    }
+
+    //
+    // Drop python objects owned by the future, breaks reference cycles
+    //
+    pub fn clear(&mut self, py: Python) {
+        let callbacks = mem::replace(&mut self.callbacks, None);
+        if let Some(callbacks) = callbacks {
+            for cb in callbacks {
+                py.release(cb);
+            }
+        }
+        if let Some(result) = self.result.take() {
+            py.release(result);
+        }
+        // unretrieved exception is kept for Drop, which reports it.
+        // exception handler must not run while gc breaks the cycle
+        if !self.log_exc_tb.get() {
+            if let Some(exception) = self.exception.take() {
+                py.release(exception);
+            }
+        }
+        if let Some(tb) = self.source_tb.take() {
+            py.release(tb);
+        }
+    }
+
     pub fn get_result(&self, py: Python) -> PyResult<PyObject> {
         match self.result {
             Some(ref res) => Ok(res.clone_ref(py)),
@@ -282,7 +366,7 @@ impl _PyFuture {
             State::Finished => {
                 if let Some(ref exc) = self.exception {
                     self.log_exc_tb.set(false);
-                    Err(PyErr::from_instance(py, exc.clone_ref(py)))
+                    Err(exception_err(py, exc))
                 } else {
                     if let Some(ref result) = self.result {
                         Ok(result.clone_ref(py))
@@ -386,13 +470,29 @@ impl _PyFuture {
                 }
             },
             _ => {
-                // schedule callback
-                cb.call(self.result(py, false));
+                let result = self.result(py, false);
+                if self.dispatching.get() {
+                    // callback added by in place callback,
+                    // defer it to avoid unbounded recursion
+                    self.evloop.as_ref(py).href().spawn_fn(move || {
+                        cb.call(result);
+                        future::ok(())
+                    });
+                } else {
+                    cb.call(result);
+                }
             },
         }
     }
 
     //
+    // Set final state and run completion callbacks, in place or on next
+    // reactor turn.
+    //
+    // Callback lists are taken out before invocation, so callbacks added
+    // by running callbacks are not part of current run. State is already
+    // final at that point, so re-entrant set()/cancel() is a no-op and
+    // newly added callbacks get scheduled separately.
     //
     pub fn schedule_callbacks(&mut self, py: Python,
                               state: State, owner: PyObject, inplace: bool)
@@ -432,6 +532,7 @@ impl _PyFuture {
             future::ok(())
         };
         if inplace {
+            self.dispatching.set(true);
             let _ = send_rresults();
         } else {
             evloop.href().spawn_fn(|| send_rresults());
@@ -460,6 +561,7 @@ impl _PyFuture {
             },
             _ => (),
         }
+        self.dispatching.set(false);
     }
 
     pub fn extract_traceback(&self, py: Python) -> PyResult<PyObject> {
@@ -474,19 +576,7 @@ impl _PyFuture {
 impl Drop for _PyFuture {
     fn drop(&mut self) {
         if self.log_exc_tb.get() {
-            let _: PyResult<()> = with_py(|py| {
-                let context = PyDict::new(py);
-                context.set_item(py, "message", "Future exception was never retrieved")?;
-                context.set_item(py, "future", "PyFuture")?;
-                if let Some(tb) = self.source_tb.take() {
-                    context.set_item(py, "source_traceback", tb)?;
-                }
-                if let Some(ref exc) = self.exception {
-                    context.set_item(py, "exception", exc.clone_ref(py))?;
-                }
-                self.evloop.as_ref(py).call_exception_handler(py, context)?;
-                Ok(())
-            });
+            with_py(|py| self.log_exception(py));
         }
     }
 }
@@ -511,7 +601,7 @@ impl future::Future for _PyFuture {
     }
 }
 
-#[py::class]
+#[py::class(weakref)]
 pub struct PyFuture {
     fut: _PyFuture,
     blocking: bool,
@@ -636,6 +726,11 @@ impl PyFuture {
     /// InvalidStateError.
     ///
     pub fn set_result(&mut self, py: Python, result: PyObject) -> PyResult<PyObject> {
+        // check state before touching wrapped future
+        if self.fut.done() {
+            return Err(PyErr::new_err(py, &Classes.InvalidStateError, NoArgs))
+        }
+
         // handle wrapped asyncio.Future object
         if let Some(fut) = self.pyfut.take() {
             // TODO: add logging for exceptions
@@ -652,6 +747,11 @@ impl PyFuture {
     // InvalidStateError.
     //
     fn set_exception(&mut self, py: Python, exception: PyObject) -> PyResult<PyObject> {
+        // check state before touching wrapped future
+        if self.fut.done() {
+            return Err(PyErr::new_err(py, &Classes.InvalidStateError, NoArgs))
+        }
+
         // handle wrapped asyncio.Future object
         if let Some(fut) = self.pyfut.take() {
             // TODO: add logging for exceptions
@@ -710,6 +810,20 @@ impl PyFuture {
         unreachable!();
     }
 
+    //
+    // Chain this future to other future.
+    //
+    // When this future is done, its result, exception or cancellation
+    // is copied to `other`. If `other` is already done at that moment,
+    // its state is left untouched. If `other` gets cancelled first,
+    // this future is cancelled as well.
+    //
+    fn copy_state_to(&mut self, py: Python, other: PyObject) -> PyResult<()> {
+        let dst = PyFuturePtr::downcast_into(py, other)?;
+        self.chain_future(py, dst);
+        Ok(())
+    }
+
     // compatibility
     #[getter(_loop)]
     fn get_loop(&self, py: Python) -> PyResult<TokioEventLoopPtr> {
@@ -737,20 +851,17 @@ impl PyGCProtocol for PyFuture {
     // Python GC support
     //
     fn __traverse__(&self, _py: Python, visit: PyVisit) -> Result<(), PyTraverseError> {
-        if let Some(ref callbacks) = self.fut.callbacks {
-            for callback in callbacks.iter() {
-                visit.call(callback)?;
-            }
+        self.fut.traverse(&visit)?;
+        if let Some(ref fut) = self.pyfut {
+            visit.call(fut)?;
         }
         Ok(())
     }
 
     fn __clear__(&mut self, py: Python) {
-        let callbacks = mem::replace(&mut self.fut.callbacks, None);
-        if let Some(callbacks) = callbacks {
-            for cb in callbacks {
-                py.release(cb);
-            }
+        self.fut.clear(py);
+        if let Some(fut) = self.pyfut.take() {
+            py.release(fut);
         }
     }
 }
@@ -759,7 +870,7 @@ impl PyGCProtocol for PyFuture {
 impl PyAsyncProtocol for PyFuture {
 
     fn __await__(&self, py: Python) -> PyResult<PyFutureIterPtr> {
-        py.init(|t| PyFutureIter {fut: self.to_inst_ptr(), token: t})
+        self.iter(py)
     }
 }
 
@@ -767,12 +878,16 @@ impl PyAsyncProtocol for PyFuture {
 impl PyIterProtocol for PyFuture {
 
     fn __iter__(&mut self, py: Python) -> PyResult<PyFutureIterPtr> {
-        py.init(|t| PyFutureIter {fut: self.to_inst_ptr(), token: t})
+        self.iter(py)
     }
 }
 
 impl PyFuture {
 
+    pub fn iter(&self, py: Python) -> PyResult<PyFutureIterPtr> {
+        py.init(|t| PyFutureIter {fut: self.to_inst_ptr(), token: t})
+    }
+
     pub fn new(py: Python, evloop: TokioEventLoopPtr) -> PyResult<PyFuturePtr> {
         py.init(|t| PyFuture { fut: _PyFuture::new(py, evloop.clone_ref(py)),
                                blocking: false,
@@ -852,6 +967,52 @@ impl PyFuture {
         self.fut.add_callback(py, cb);
     }
 
+    //
+    // Propagate completion of this future to `dst`, asyncio's _chain_future
+    //
+    pub fn chain_future(&mut self, py: Python, dst: PyFuturePtr) {
+        // destination is cancelled already, nothing to wait for
+        if dst.as_ref(py).is_cancelled() {
+            let _ = self.cancel(py);
+            py.release(dst);
+            return
+        }
+
+        // cancellation of destination cancels source
+        let src = self.to_inst_ptr();
+        let dst2 = dst.clone_ref(py);
+        dst.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+
+            if dst2.as_ref(py).is_cancelled() && !src.as_ref(py).is_done() {
+                let _ = src.as_mut(py).cancel(py);
+            }
+            py.release(src);
+            py.release(dst2);
+        }));
+
+        // copy source state to destination
+        let src = self.to_inst_ptr();
+        self.add_callback(py, SendBoxFnOnce::from(move |result| {
+            let gil = Python::acquire_gil();
+            let py = gil.python();
+
+            {
+                let fut = dst.as_mut(py);
+                if !fut.is_done() {
+                    if src.as_ref(py).is_cancelled() {
+                        let _ = fut.cancel(py);
+                    } else {
+                        fut.set(py, result);
+                    }
+                }
+            }
+            py.release(src);
+            py.release(dst);
+        }));
+    }
+
     //
     // bloking
     //
@@ -905,18 +1066,28 @@ impl PyFutureIter {
         self.__next__(py)
     }
 
+    //
+    // Throw exception into awaiting coroutine, CancelledError
+    // cancels awaited future, same as task cancellation
+    //
     fn throw(&mut self, py: Python, tp: PyObject, val: Option<PyObject>,
              _tb: Option<PyObject>) -> PyResult<Option<PyObject>>
     {
-        if Classes.Exception.is_instance(py, &tp) {
-            PyErr::from_instance(py, tp).restore(py);
+        let err = if Classes.Exception.is_instance(py, &tp) {
+            PyErr::from_instance(py, tp)
         } else {
             if let Ok(tp) = PyType::downcast_into(py, tp) {
-                PyErr::new_lazy_init(tp, val).restore(py);
+                PyErr::new_lazy_init(tp, val)
             } else {
-                PyErr::new::<exc::TypeError, _>(py, NoArgs).restore(py);
+                PyErr::new::<exc::TypeError, _>(py, NoArgs)
             }
+        };
+
+        if err.matches(py, &Classes.CancelledError) {
+            let _ = self.fut.as_mut(py).cancel(py);
+            return Err(err)
         }
+        err.restore(py);
 
         self.__next__(py)
     }
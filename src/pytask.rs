@@ -1,4 +1,3 @@
-use std::mem;
 use pyo3::*;
 use futures::{future, unsync, Poll};
 use boxfnonce::SendBoxFnOnce;
@@ -9,12 +8,14 @@ use pyunsafe::GIL;
 use pyfuture::{_PyFuture, PyFuture, Callback, State};
 
 
-#[py::class]
+#[py::class(weakref)]
 pub struct PyTask {
     fut: _PyFuture,
     waiter: Option<PyObject>,
     must_cancel: bool,
+    num_cancels_requested: usize,
     blocking: bool,
+    name: String,
 
     token: PyToken,
 }
@@ -34,6 +35,7 @@ impl PyTask {
     //
     fn cancel(&mut self, py: Python) -> PyResult<bool> {
         if !self.fut.done() {
+            self.num_cancels_requested += 1;
             if let Some(ref waiter) = self.waiter {
                 let _ = waiter.call_method(py, "cancel", NoArgs, None)?;
                 return Ok(true);
@@ -45,6 +47,26 @@ impl PyTask {
         }
     }
 
+    //
+    // Return the number of pending cancellation requests,
+    // i.e. number of calls to cancel() less the number of uncancel() calls.
+    //
+    fn cancelling(&self, _py: Python) -> PyResult<usize> {
+        Ok(self.num_cancels_requested)
+    }
+
+    //
+    // Decrement the count of pending cancellation requests.
+    //
+    // Return the remaining number of requests, counter never goes below zero.
+    //
+    fn uncancel(&mut self, _py: Python) -> PyResult<usize> {
+        if self.num_cancels_requested > 0 {
+            self.num_cancels_requested -= 1;
+        }
+        Ok(self.num_cancels_requested)
+    }
+
     //
     // Return True if the future was cancelled
     //
@@ -152,6 +174,21 @@ impl PyTask {
             token: t})
     }
 
+    //
+    // Return the name of the task.
+    //
+    fn get_name(&self, _py: Python) -> PyResult<String> {
+        Ok(self.name.clone())
+    }
+
+    //
+    // Set the name of the task, value is converted to str.
+    //
+    pub fn set_name(&mut self, _py: Python, value: PyObject) -> PyResult<()> {
+        self.name = format!("{}", value);
+        Ok(())
+    }
+
     // compatibility
     #[getter(_loop)]
     fn get_loop(&self, py: Python) -> PyResult<TokioEventLoopPtr> {
@@ -216,9 +253,14 @@ impl PyTask {
             fut:  _PyFuture::new(py, evloop.to_inst_ptr()),
             waiter: None,
             must_cancel: false,
+            num_cancels_requested: 0,
             blocking: false,
+            name: evloop.next_task_name(),
             token: t})?;
 
+        // completed tasks are filtered out by all_tasks()
+        evloop.register_task(py, &task)?;
+
         let fut = task.clone_ref(py);
 
         evloop.href().spawn_fn(move|| {
@@ -259,6 +301,14 @@ impl PyTask {
     //
     // helpers methods
     //
+    pub fn is_done(&self) -> bool {
+        self.fut.done()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.fut.cancelled()
+    }
+
     pub fn is_same_loop(&self, evloop: &TokioEventLoop) -> bool {
         self.fut.evloop.as_ptr() == evloop.as_ptr()
     }
@@ -270,20 +320,17 @@ impl PyGCProtocol for PyTask {
     // Python GC support
     //
     fn __traverse__(&self, _py: Python, visit: PyVisit) -> Result<(), PyTraverseError> {
-        if let Some(ref callbacks) = self.fut.callbacks {
-            for callback in callbacks.iter() {
-                let _ = visit.call(callback);
-            }
+        self.fut.traverse(&visit)?;
+        if let Some(ref waiter) = self.waiter {
+            visit.call(waiter)?;
         }
         Ok(())
     }
 
     fn __clear__(&mut self, py: Python) {
-        let callbacks = mem::replace(&mut self.fut.callbacks, None);
-        if let Some(callbacks) = callbacks {
-            for cb in callbacks {
-                py.release(cb);
-            }
+        self.fut.clear(py);
+        if let Some(waiter) = self.waiter.take() {
+            py.release(waiter);
         }
     }
 }
@@ -291,7 +338,7 @@ impl PyGCProtocol for PyTask {
 #[py::proto]
 impl PyObjectProtocol for PyTask {
     fn __repr__(&self, py: Python) -> PyResult<PyObject> {
-        Classes.Helpers.call(py, "future_repr", ("Task", self.to_inst_ptr(),), None)
+        Classes.Helpers.call(py, "task_repr", (self.to_inst_ptr(),), None)
     }
 }
 
@@ -397,9 +444,15 @@ fn wakeup_task(fut: PyTaskPtr, coro: PyObject, result: PyResult<PyObject>) {
 
 
 //
-// execute task step
+// execute task step, task is current task of the loop during the step
 //
 fn task_step(py: Python, task: &mut PyTask, coro: PyObject, exc: Option<PyObject>, retry: usize) {
+    task.fut.evloop.as_mut(py).set_current_task(py, task.to_inst_ptr().into());
+    run_step(py, task, coro, exc, retry);
+    task.fut.evloop.as_mut(py).clear_current_task(py);
+}
+
+fn run_step(py: Python, task: &mut PyTask, coro: PyObject, exc: Option<PyObject>, retry: usize) {
     // cancel if needed
     let mut exc = exc;
     if task.must_cancel {
@@ -415,11 +468,6 @@ fn task_step(py: Python, task: &mut PyTask, coro: PyObject, exc: Option<PyObject
     }
     task.waiter = None;
 
-    //let mut evloop = fut.evloop.as_mut(py);
-
-    // set current task
-    task.fut.evloop.as_mut(py).set_current_task(py, task.to_inst_ptr().into());
-
     // call either coro.throw(exc) or coro.send(None).
     let res = match exc {
         None => coro.call_method(py, "send", (py.None(),), None),
@@ -1,5 +1,8 @@
+use std::fs;
 use std::io;
 use std::net;
+use std::path::PathBuf;
+use std::time::Duration;
 use std::os::unix;
 use pyo3::*;
 use futures::{unsync, Async, Stream, Future, Poll};
@@ -19,7 +22,8 @@ use transport::{TransportFactory, tcp_transport_factory};
 
 pub fn create_server(py: Python, evloop: &TokioEventLoop,
                      addrs: Vec<addrinfo::AddrInfo>, backlog: i32,
-                     ssl: Option<PyObject>, reuse_address: bool, reuse_port: bool,
+                     ssl: Option<PyObject>, handshake_timeout: Option<Duration>,
+                     reuse_address: bool, reuse_port: bool,
                      proto_factory: PyObject, transport_factory: TransportFactory)
                      -> PyResult<PyObject> {
 
@@ -45,8 +49,11 @@ pub fn create_server(py: Python, evloop: &TokioEventLoop,
         };
 
         let _ = builder.reuse_address(reuse_address);
-        let _ = builder.reuse_port(reuse_port);
+        if reuse_port {
+            let _ = builder.reuse_port(true);
+        }
 
+        // already bound listeners get closed on drop
         if let Err(err) = builder.bind(info.sockaddr) {
             return Err(err.to_pyerr(py));
         }
@@ -56,8 +63,9 @@ pub fn create_server(py: Python, evloop: &TokioEventLoop,
                 match TcpListener::from_listener(listener, &info.sockaddr, &handle.h) {
                     Ok(lst) => {
                         info!("Started listening on {:?}", info.sockaddr);
+                        // actual address, port could be assigned by OS
                         let mut addr = info.clone();
-                        addr.sockaddr = lst.local_addr().expect("should not fail");
+                        addr.sockaddr = lst.local_addr().map_err(|err| err.to_pyerr(py))?;
                         let s = Socket::new(py, &addr)?;
                         sockets.push(s);
                         listeners.push((lst, addr));
@@ -84,60 +92,82 @@ pub fn create_server(py: Python, evloop: &TokioEventLoop,
         handles.push(pyunsafe::OneshotSender::new(tx));
 
         Server::serve(evloop, addr, listener.incoming(),
-                      transport_factory, proto_factory.clone_ref(py), s, rx);
+                      transport_factory, proto_factory.clone_ref(py), s, handshake_timeout, rx);
     }
 
     py.init(|token| TokioServer{
         evloop: evloop.to_inst_ptr(),
         sockets: PyTuple::new(py, &sockets[..]),
         stop_handle: Some(handles),
+        unlink_path: None,
         token: token}).map(|ptr| ptr.into())
 }
 
 
 pub fn create_sock_server(py: Python, evloop: &TokioEventLoop,
-                          listener: net::TcpListener, info: addrinfo::AddrInfo,
-                          ssl: Option<PyObject>, proto_factory: PyObject,
+                          listeners: Vec<(net::TcpListener, addrinfo::AddrInfo)>,
+                          ssl: Option<PyObject>, handshake_timeout: Option<Duration>,
+                          proto_factory: PyObject,
                           transport_factory: TransportFactory) -> PyResult<PyObject> {
 
-    match TcpListener::from_listener(listener, &info.sockaddr, evloop.href()) {
-        Ok(lst) => {
-            info!("Started listening on {:?}", info.sockaddr);
-            let mut addr = info.clone();
-            addr.sockaddr = lst.local_addr().expect("should not fail");
-            let sock = Socket::new(py, &addr)?;
-
-            let (tx, rx) = unsync::oneshot::channel::<()>();
-            let handles = vec![pyunsafe::OneshotSender::new(tx)];
-
-            Server::serve(evloop, addr, lst.incoming(),
-                          transport_factory, proto_factory, ssl, rx);
-
-            py.init(|token| TokioServer {
-                evloop: evloop.to_inst_ptr(),
-                sockets: PyTuple::new(py, &[sock]),
-                stop_handle: Some(handles),
-                token: token}).map(|ptr| ptr.into())
-        },
-        Err(err) => Err(err.to_pyerr(py)),
+    // register all listeners before start serving
+    let mut lsts = Vec::new();
+    let mut sockets = Vec::new();
+    for (listener, info) in listeners {
+        match TcpListener::from_listener(listener, &info.sockaddr, evloop.href()) {
+            Ok(lst) => {
+                info!("Started listening on {:?}", info.sockaddr);
+                let mut addr = info.clone();
+                addr.sockaddr = lst.local_addr().map_err(|err| err.to_pyerr(py))?;
+                sockets.push(Socket::new(py, &addr)?);
+                lsts.push((lst, addr));
+            },
+            Err(err) => return Err(err.to_pyerr(py)),
+        }
+    }
+
+    let mut handles = Vec::new();
+    for (lst, addr) in lsts {
+        // copy sslcontext for each server
+        let s = if let Some(ref ssl) = ssl {
+            Some(ssl.clone_ref(py))
+        } else {
+            None
+        };
+
+        let (tx, rx) = unsync::oneshot::channel::<()>();
+        handles.push(pyunsafe::OneshotSender::new(tx));
+
+        Server::serve(evloop, addr, lst.incoming(),
+                      transport_factory, proto_factory.clone_ref(py), s, handshake_timeout, rx);
     }
+
+    py.init(|token| TokioServer {
+        evloop: evloop.to_inst_ptr(),
+        sockets: PyTuple::new(py, &sockets[..]),
+        stop_handle: Some(handles),
+        unlink_path: None,
+        token: token}).map(|ptr| ptr.into())
 }
 
 
 pub fn create_uds_server(py: Python, evloop: &TokioEventLoop,
                          listener: tokio_uds::UnixListener, ssl: Option<PyObject>,
-                         proto_factory: PyObject) -> PyResult<PyObject> {
+                         handshake_timeout: Option<Duration>,
+                         proto_factory: PyObject, unlink_path: Option<PathBuf>)
+                         -> PyResult<PyObject> {
     info!("Started listening on {:?}", listener.local_addr().unwrap());
 
     let (tx, rx) = unsync::oneshot::channel::<()>();
     let handles = vec![pyunsafe::OneshotSender::new(tx)];
 
-    UdsServer::serve(evloop, listener.incoming(), proto_factory, ssl, rx);
+    UdsServer::serve(evloop, listener.incoming(), proto_factory, ssl, handshake_timeout, rx);
 
     py.init(|token| TokioServer{
         evloop: evloop.to_inst_ptr(),
         sockets: PyTuple::empty(py),
         stop_handle: Some(handles),
+        unlink_path: unlink_path,
         token: token}).map(|ptr| ptr.into())
 }
 
@@ -147,6 +177,8 @@ pub struct TokioServer {
     evloop: TokioEventLoopPtr,
     sockets: PyTuple,
     stop_handle: Option<Vec<pyunsafe::OneshotSender<()>>>,
+    // socket file created by server
+    unlink_path: Option<PathBuf>,
     token: PyToken,
 }
 
@@ -167,6 +199,9 @@ impl TokioServer {
                 let _ = h.send(());
             }
         }
+        if let Some(path) = self.unlink_path.take() {
+            let _ = fs::remove_file(path);
+        }
         Ok(py.None())
     }
 
@@ -175,6 +210,24 @@ impl TokioServer {
     }
 }
 
+#[py::proto]
+impl PyAsyncProtocol for TokioServer {
+
+    // server is serving already, nothing to wait for
+    fn __aenter__(&mut self, py: Python) -> PyResult<PyFuturePtr> {
+        PyFuture::done_fut(py, self.evloop.clone_ref(py), self.to_inst_ptr().into())
+    }
+
+    // close server, listeners are closed synchronously so wait_closed()
+    // is complete already. result is false, exception is not suppressed
+    fn __aexit__(&mut self, py: Python, exc_type: Option<PyObject>,
+                 exc_value: Option<PyObject>, traceback: Option<PyObject>)
+                 -> PyResult<PyFuturePtr> {
+        self.close(py)?;
+        PyFuture::done_fut(py, self.evloop.clone_ref(py), false.to_object(py))
+    }
+}
+
 
 struct Server {
     evloop: TokioEventLoopPtr,
@@ -184,6 +237,7 @@ struct Server {
     transport: TransportFactory,
     factory: PyObject,
     ssl: Option<PyObject>,
+    handshake_timeout: Option<Duration>,
 }
 
 impl Server {
@@ -193,10 +247,12 @@ impl Server {
     //
     fn serve(evloop: &TokioEventLoop, addr: addrinfo::AddrInfo,
              stream: Incoming, transport: TransportFactory,
-             factory: PyObject, ssl: Option<PyObject>, stop: unsync::oneshot::Receiver<()>) {
+             factory: PyObject, ssl: Option<PyObject>, handshake_timeout: Option<Duration>,
+             stop: unsync::oneshot::Receiver<()>) {
 
         let srv = Server { evloop: evloop.to_inst_ptr(), addr: addr, stop: stop, stream: stream,
-                           transport: transport, factory: factory, ssl: ssl};
+                           transport: transport, factory: factory, ssl: ssl,
+                           handshake_timeout: handshake_timeout};
 
         evloop.get_handle().spawn(
             srv.map_err(|e| {
@@ -224,8 +280,8 @@ impl Future for Server
             Async::Ready(Some((socket, peer))) => {
                 (self.transport)(
                     self.evloop.clone_ref(pyunsafe::GIL::python()),
-                    true, &self.factory, &self.ssl,
-                    None, socket, Some(&self.addr), Some(peer), None)?;
+                    true, &self.factory, &self.ssl, None, self.handshake_timeout,
+                    socket, Some(&self.addr), Some(peer), None)?;
 
                 // we can not just return Async::NotReady here,
                 // because self.stream is not registered within mio anymore
@@ -249,6 +305,7 @@ struct UdsServer {
     stop: unsync::oneshot::Receiver<()>,
     factory: PyObject,
     ssl: Option<PyObject>,
+    handshake_timeout: Option<Duration>,
 }
 
 impl UdsServer {
@@ -257,10 +314,12 @@ impl UdsServer {
     // Start accepting incoming connections
     //
     fn serve(evloop: &TokioEventLoop, stream: UdsIncoming,
-             factory: PyObject, ssl: Option<PyObject>, stop: unsync::oneshot::Receiver<()>) {
+             factory: PyObject, ssl: Option<PyObject>, handshake_timeout: Option<Duration>,
+             stop: unsync::oneshot::Receiver<()>) {
 
         let srv = UdsServer { evloop: evloop.to_inst_ptr(), stop: stop,
-                              stream: stream, factory: factory, ssl: ssl};
+                              stream: stream, factory: factory, ssl: ssl,
+                              handshake_timeout: handshake_timeout};
 
         evloop.get_handle().spawn(
             srv.map_err(|e| {
@@ -290,7 +349,8 @@ impl Future for UdsServer
             Async::Ready(Some((socket, _peer))) => {
                 tcp_transport_factory(
                     self.evloop.clone_ref(pyunsafe::GIL::python()),
-                    true, &self.factory, &self.ssl, None, socket, None, None, None)?;
+                    true, &self.factory, &self.ssl, None, self.handshake_timeout,
+                    socket, None, None, None)?;
 
                 // we can not just return Async::NotReady here,
                 // because self.stream is not registered within mio anymore
@@ -1,5 +1,7 @@
+use std::rc::Rc;
+use std::cell::RefCell;
 use std::os::raw::c_int;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use futures::sync::mpsc;
 use futures::{Async, Future, Poll, Stream};
@@ -15,6 +17,26 @@ pub enum SignalsMessage {
 }
 
 
+/// Signal numbers with handlers registered by `add_signal_handler`
+#[derive(Clone, Default)]
+pub struct SignalHandlers(Rc<RefCell<HashSet<c_int>>>);
+
+impl SignalHandlers {
+
+    pub fn insert(&self, sig: c_int) {
+        self.0.borrow_mut().insert(sig);
+    }
+
+    pub fn remove(&self, sig: c_int) {
+        self.0.borrow_mut().remove(&sig);
+    }
+
+    pub fn contains(&self, sig: c_int) -> bool {
+        self.0.borrow().contains(&sig)
+    }
+}
+
+
 pub struct Signals {
     rx: mpsc::UnboundedReceiver<SignalsMessage>,
     signals: HashMap<c_int, (Signal, PyHandlePtr)>,
@@ -1,23 +1,29 @@
 #![allow(unused_variables)]
 
 use std::io;
-use std::net::SocketAddr;
+use std::mem;
+use std::slice;
+use std::net::{self, SocketAddr};
+use std::time::Duration;
 use std::collections::HashMap;
-use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use libc;
 use pyo3::*;
-use futures::unsync::mpsc;
-use futures::{unsync, Async, AsyncSink, Stream, Future, Poll, Sink};
+use boxfnonce::SendBoxFnOnce;
+use futures::unsync::{mpsc, oneshot};
+use futures::{future, unsync, Async, AsyncSink, Stream, Future, Poll, Sink};
 use bytes::{Bytes, BytesMut, BufMut};
 use tokio_io::{AsyncRead, AsyncWrite};
 use tokio_io::codec::{Encoder, Decoder, Framed};
 use tokio_core::net::TcpStream;
+use tokio_core::reactor;
 
 use {TokioEventLoop, TokioEventLoopPtr};
-use utils::{Classes, PyLogger, ToPyErr};
+use utils::{with_py, Classes, PyLogger, ToPyErr};
 use addrinfo::AddrInfo;
 use pybytes;
 use pyfuture::{PyFuture, PyFuturePtr};
-use pyunsafe::{GIL, Sender};
+use pyunsafe::{GIL, Sender, OneshotSender};
 use socket::Socket;
 
 #[derive(Debug)]
@@ -44,28 +50,62 @@ impl IntoPyTuple for InitializedTransport {
 
 // Transport factory
 pub type TransportFactory = fn(
-    TokioEventLoopPtr, bool, &PyObject, &Option<PyObject>, Option<PyObject>,
+    TokioEventLoopPtr, bool, &PyObject, &Option<PyObject>, Option<PyObject>, Option<Duration>,
     TcpStream, Option<&AddrInfo>, Option<SocketAddr>,
     Option<PyFuturePtr>) -> io::Result<InitializedTransport>;
 
-pub struct BytesMsg {
-    pub buf: buffer::PyBuffer,
-    pub len: usize,
+// default ssl handshake timeout, seconds
+pub const SSL_HANDSHAKE_TIMEOUT: u64 = 60;
+
+// local address of connected socket
+fn local_addr(fd: RawFd) -> io::Result<SocketAddr> {
+    let sock = unsafe { net::TcpStream::from_raw_fd(fd) };
+    let addr = sock.local_addr();
+    let _ = sock.into_raw_fd();
+    addr
+}
+
+pub enum BytesMsg {
+    // python object's buffer, copied into write buffer by codec
+    Buffer {
+        buf: buffer::PyBuffer,
+        len: usize,
+    },
+    // chunks joined by writelines()
+    Joined(Bytes),
+}
+
+// copy python buffer to the end of `dst`
+fn copy_buffer(py: Python, buf: &buffer::PyBuffer, len: usize,
+               dst: &mut BytesMut) -> PyResult<()> {
+    dst.reserve(len);
+    {
+        let mut slice = unsafe { dst.bytes_mut() };
+        buf.copy_to_slice(py, &mut slice[..len])?;
+    }
+    unsafe {
+        let new_len = dst.len() + len;
+        dst.set_len(new_len);
+    }
+    Ok(())
 }
 
+// default write buffer high-water limit, 64KiB
+const HIGH_WATER: usize = 64 * 1024;
+
 pub enum TcpTransportMessage {
     Bytes(BytesMsg),
     Pause,
     Resume,
+    WriteEof,
     Close,
-    Shutdown,
 }
 
 
 pub fn tcp_transport_factory<T>(
     evloop: TokioEventLoopPtr, server: bool,
     factory: &PyObject, ssl: &Option<PyObject>, server_hostname: Option<PyObject>,
-    socket: T, addr: Option<&AddrInfo>,
+    handshake_timeout: Option<Duration>, socket: T, addr: Option<&AddrInfo>,
     peer: Option<SocketAddr>, waiter: Option<PyFuturePtr>) -> io::Result<InitializedTransport>
 
     where T: AsyncRead + AsyncWrite + AsRawFd + 'static
@@ -76,8 +116,12 @@ pub fn tcp_transport_factory<T>(
     let ev = evloop.as_ref(py);
     let mut info: HashMap<&'static str, PyObject> = HashMap::new();
 
-    if let (Some(ref addr), Some(peer)) = (addr, peer) {
-        let sock = Socket::new_peer(py, addr, peer, Some(socket.as_raw_fd()))?;
+    if let (Some(addr), Some(peer)) = (addr, peer) {
+        // addr is either listening or remote address, use actual local address
+        let mut addr = addr.clone();
+        addr.sockaddr = local_addr(socket.as_raw_fd())?;
+
+        let sock = Socket::new_peer(py, &addr, peer, Some(socket.as_raw_fd()))?;
         let sock_ref = sock.as_ref(py);
         info.insert("sockname", sock_ref.getsockname(py)?.into());
         info.insert("peername", sock_ref.getpeername(py)?.into());
@@ -90,6 +134,7 @@ pub fn tcp_transport_factory<T>(
 
     // create py transport
     let (tx, rx) = mpsc::unbounded();
+    let (abort_tx, abort_rx) = oneshot::channel();
 
     let (tr, wrp_tr) = if let Some(ref ssl) = *ssl {
         // create SSLProtocol and wrpped transport
@@ -99,24 +144,47 @@ pub fn tcp_transport_factory<T>(
         if let Some(hostname) = server_hostname {
             let _ = kwargs.set_item(py, "server_hostname", hostname);
         }
+        // server side has no handshake waiter, use internal one to cancel handshake timer
+        let waiter = match waiter {
+            Some(waiter) => waiter,
+            None => {
+                let waiter = PyFuture::new(py, evloop.clone_ref(py))?;
+                let fut = waiter.clone_ref(py);
+                waiter.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+                    with_py(|py| {
+                        // handshake error is reported by SSLProtocol
+                        let _ = fut.as_ref(py).get(py);
+                        py.release(fut);
+                    });
+                }));
+                waiter
+            }
+        };
         let ssl_proto = Classes.SSLProto.call(py, (
-            evloop.clone_ref(py), proto.clone_ref(py), ssl.clone_ref(py), waiter), Some(&kwargs))?;
+            evloop.clone_ref(py), proto.clone_ref(py), ssl.clone_ref(py),
+            waiter.clone_ref(py)), Some(&kwargs))?;
 
-        let tr = PyTcpTransportPtr::new(py, ev, Sender::new(tx), &ssl_proto, info)?;
+        let tr = PyTcpTransportPtr::new(
+            py, ev, Sender::new(tx), OneshotSender::new(abort_tx), &ssl_proto, info)?;
         let wrp_tr = ssl_proto.getattr(py, "_app_transport")?;
+
+        ssl_handshake_timeout(
+            py, ev, handshake_timeout.unwrap_or(Duration::from_secs(SSL_HANDSHAKE_TIMEOUT)),
+            ssl_proto, tr.clone_ref(py).into(), &waiter);
         (tr, wrp_tr)
     } else {
         // normal transport
         if let Some(waiter) = waiter {
             waiter.as_mut(py).set(py, Ok(py.None()));
         }
-        let tr = PyTcpTransportPtr::new(py, ev, Sender::new(tx), &proto, info)?;
+        let tr = PyTcpTransportPtr::new(
+            py, ev, Sender::new(tx), OneshotSender::new(abort_tx), &proto, info)?;
         let wrp_tr = tr.clone_ref(py).into();
         (tr, wrp_tr)
     };
 
     // create transport and then call connection_made on protocol
-    let transport = TcpTransport::new(socket, rx, tr.clone_ref(py));
+    let transport = TcpTransport::new(socket, rx, abort_rx, tr.clone_ref(py));
 
     // handle connection lost
     let conn_err = tr.clone_ref(py);
@@ -134,14 +202,71 @@ pub fn tcp_transport_factory<T>(
 }
 
 
+/// Abort connection if ssl handshake does not complete in time,
+/// handshake waiter gets `TimeoutError`. Timer is cancelled once waiter is done.
+pub fn ssl_handshake_timeout(py: Python, evloop: &TokioEventLoop, timeout: Duration,
+                             ssl_proto: PyObject, transport: PyObject, waiter: &PyFuturePtr) {
+    let timer = match reactor::Timeout::new(timeout, evloop.href()) {
+        Ok(timer) => timer,
+        Err(err) => {
+            let _ = evloop.log_error(py, err.to_pyerr(py), "SSL handshake timer error");
+            return
+        }
+    };
+
+    // handshake is done, release ssl protocol and transport
+    let (tx, rx) = oneshot::channel::<()>();
+    let tx = OneshotSender::new(tx);
+    waiter.as_mut(py).add_callback(py, SendBoxFnOnce::from(move |_| {
+        let _ = tx.send(());
+    }));
+
+    evloop.href().spawn(timer.select2(rx).then(move |res| {
+        with_py(|py| {
+            let expired = match res {
+                Ok(future::Either::A(_)) => true,
+                _ => false,
+            };
+            let in_handshake = expired && ssl_proto.getattr(py, "_in_handshake")
+                .and_then(|val| val.is_true(py))
+                .unwrap_or(false);
+
+            if in_handshake {
+                let mut err = PyErr::new::<exc::TimeoutError, _>(
+                    py, format!("SSL handshake is taking longer than {} seconds: \
+                                 aborting the connection", timeout.as_secs()));
+                let _ = ssl_proto.call_method(
+                    py, "_wakeup_waiter", (err.instance(py),), None);
+                let _ = transport.call_method(py, "abort", NoArgs, None);
+            }
+            py.release(ssl_proto);
+            py.release(transport);
+        });
+        future::ok(())
+    }));
+}
+
+
 #[py::class]
 pub struct PyTcpTransport {
     evloop: TokioEventLoopPtr,
+    protocol: PyObject,
     connection_lost: PyObject,
-    data_received: PyObject,
+    data_received: Option<PyObject>,
+    // get_buffer() and buffer_updated() of BufferedProtocol
+    buffered: Option<(PyObject, PyObject)>,
+    eof_received: Option<PyObject>,
+    pause_writing: Option<PyObject>,
+    resume_writing: Option<PyObject>,
     transport: Sender<TcpTransportMessage>,
+    abort: Option<OneshotSender<()>>,
     drain: Option<PyFuturePtr>,
     drained: bool,
+    eof: bool,
+    write_buffer_size: usize,
+    high_water: usize,
+    low_water: usize,
+    writing_paused: bool,
     closing: bool,
     info: HashMap<&'static str, PyObject>,
     paused: bool,
@@ -160,13 +285,6 @@ impl PyTcpTransport {
 
     fn get_extra_info(&self, py: Python,
                       name: PyString, default: Option<PyObject>) -> PyResult<PyObject> {
-        if self.closing {
-            return match default {
-                Some(val) => Ok(val),
-                None => Ok(py.None())
-            };
-        }
-
         if let Some(val) = self.info.get(name.to_string(py)?.as_ref()) {
             Ok(val.clone_ref(py))
         } else {
@@ -181,6 +299,14 @@ impl PyTcpTransport {
     // write bytes to transport
     //
     fn write(&mut self, py: Python, data: PyObject) -> PyResult<()> {
+        // transport is aborted, data would be discarded anyway
+        if self.abort.is_none() {
+            return Ok(())
+        }
+        if self.eof {
+            return Err(PyErr::new::<exc::RuntimeError, _>(
+                py, "Cannot call write() after write_eof()"))
+        }
         let data = buffer::PyBuffer::get(py, &data)?;
         let len = if let Some(slice) = data.as_slice::<u8>(py) {
             slice.len() as usize
@@ -190,28 +316,82 @@ impl PyTcpTransport {
         };
 
         self.drained = false;
+        self.write_buffer_size += len;
         let _ = self.transport.send(
-            TcpTransportMessage::Bytes(BytesMsg{buf:data, len:len}));
+            TcpTransportMessage::Bytes(BytesMsg::Buffer{buf:data, len:len}));
+        self.maybe_pause_protocol(py);
         Ok(())
     }
 
     //
-    // write bytes to transport
+    // write sequence of bytes to transport,
+    // chunks are joined into one buffer and sent as single write
     //
     fn writelines(&mut self, py: Python, data: PyObject) -> PyResult<()> {
-        let iter = data.iter(py)?;
+        // transport is aborted, data would be discarded anyway
+        if self.abort.is_none() {
+            return Ok(())
+        }
+        if self.eof {
+            return Err(PyErr::new::<exc::RuntimeError, _>(
+                py, "Cannot call writelines() after write_eof()"))
+        }
 
-        for item in iter {
-            let _ = self.write(py, item?)?;
+        let mut joined = BytesMut::new();
+        for item in data.iter(py)? {
+            let item = buffer::PyBuffer::get(py, &item?)?;
+            let len = if let Some(slice) = item.as_slice::<u8>(py) {
+                slice.len() as usize
+            } else {
+                return Err(PyErr::new::<exc::TypeError, _>(
+                    py, "data argument must be a bytes-like object"))
+            };
+            copy_buffer(py, &item, len, &mut joined)?;
+        }
+        if joined.is_empty() {
+            return Ok(())
         }
 
+        self.drained = false;
+        self.write_buffer_size += joined.len();
+        let _ = self.transport.send(
+            TcpTransportMessage::Bytes(BytesMsg::Joined(joined.freeze())));
+        self.maybe_pause_protocol(py);
         Ok(())
     }
 
     //
-    // write eof, close tx part of socket
+    // write eof, close tx part of socket after all buffered data is flushed
     //
-    fn write_eof(&self, _py: Python) -> PyResult<()> {
+    fn write_eof(&mut self, py: Python) -> PyResult<()> {
+        if self.closing || self.eof {
+            return Ok(())
+        }
+        self.eof = true;
+        let _ = self.transport.send(TcpTransportMessage::WriteEof);
+        Ok(())
+    }
+
+    fn can_write_eof(&self, py: Python) -> PyResult<bool> {
+        Ok(true)
+    }
+
+    fn get_protocol(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.protocol.clone_ref(py))
+    }
+
+    //
+    // replace protocol, transport calls new protocol's callbacks from now on
+    //
+    fn set_protocol(&mut self, py: Python, protocol: PyObject) -> PyResult<()> {
+        self.connection_lost = protocol.getattr(py, "connection_lost")?;
+        let (data_received, buffered) = data_callbacks(py, &protocol)?;
+        self.data_received = data_received;
+        self.buffered = buffered;
+        self.eof_received = protocol.getattr(py, "eof_received").ok();
+        self.pause_writing = protocol.getattr(py, "pause_writing").ok();
+        self.resume_writing = protocol.getattr(py, "resume_writing").ok();
+        self.protocol = protocol;
         Ok(())
     }
 
@@ -232,18 +412,107 @@ impl PyTcpTransport {
         }
     }
 
+    //
+    // Return the current size of the write buffer.
+    //
+    fn get_write_buffer_size(&self, py: Python) -> PyResult<usize> {
+        Ok(self.write_buffer_size)
+    }
+
+    fn get_write_buffer_limits(&self, py: Python) -> PyResult<PyObject> {
+        Ok((self.low_water, self.high_water).to_object(py))
+    }
+
+    //
+    // Set the high- and low-water limits for write flow control.
+    //
+    // These two values control when to call the protocol's
+    // pause_writing() and resume_writing() methods.  If specified,
+    // the low-water limit must be less than or equal to the
+    // high-water limit.  Neither value can be negative.
+    //
+    // Bytes written to socket are subtracted from write buffer size,
+    // resume_writing() is called once it drops to the low-water limit.
+    //
+    fn set_write_buffer_limits(&mut self, py: Python,
+                               high: Option<isize>, low: Option<isize>) -> PyResult<()> {
+        let high = match (high, low) {
+            (Some(high), _) => high,
+            (None, Some(low)) => low * 4,
+            (None, None) => HIGH_WATER as isize,
+        };
+        let low = match low {
+            Some(low) => low,
+            None => high / 4,
+        };
+
+        if !(high >= low && low >= 0) {
+            return Err(PyErr::new::<exc::ValueError, _>(
+                py, format!("high ({:?}) must be >= low ({:?}) must be >= 0", high, low)))
+        }
+
+        self.high_water = high as usize;
+        self.low_water = low as usize;
+        self.maybe_pause_protocol(py);
+        Ok(())
+    }
+
+    //
+    // Upgrade connection to TLS, same as loop.start_tls() with current
+    // protocol. Lets server protocol decide on TLS after inspecting
+    // initial bytes, bytes consumed by protocol could be passed back
+    // with `data`, they get fed to TLS layer before new socket data.
+    //
+    #[defaults(server_side=true)]
+    fn start_tls(&self, py: Python, sslcontext: PyObject, server_side: bool,
+                 data: Option<PyObject>, server_hostname: Option<PyObject>,
+                 ssl_handshake_timeout: Option<PyObject>) -> PyResult<PyFuturePtr> {
+        if !Classes.SSLContext.is_instance(py, &sslcontext) {
+            return Err(PyErr::new::<exc::TypeError, _>(
+                py, format!("sslcontext is expected to be an instance of ssl.SSLContext, got {}",
+                            sslcontext.get_type(py).name(py))))
+        }
+        if self.closing {
+            return Err(PyErr::new::<exc::RuntimeError, _>(py, "Transport is closing"))
+        }
+        let evloop = self.evloop.as_ref(py);
+        let handshake_timeout = evloop.ssl_timeout(
+            py, "ssl_handshake_timeout", true, ssl_handshake_timeout)?
+            .unwrap_or(Duration::from_secs(SSL_HANDSHAKE_TIMEOUT));
+
+        evloop.upgrade_tls(py, self.to_inst_ptr().into(), self.protocol.clone_ref(py),
+                           sslcontext, server_side, server_hostname, handshake_timeout, data)
+    }
+
+    //
+    // Pause the receiving end, socket is not polled for incoming data
+    // and protocol's data_received() is not called until resume_reading().
+    //
     fn pause_reading(&mut self, py: Python) -> PyResult<()> {
+        if self.closing || self.paused {
+            return Ok(())
+        }
         self.paused = true;
         let _ = self.transport.send(TcpTransportMessage::Pause);
         Ok(())
     }
 
+    //
+    // Resume the receiving end, data that is buffered in socket gets delivered
+    //
     fn resume_reading(&mut self, py: Python) -> PyResult<()> {
+        if self.closing || !self.paused {
+            return Ok(())
+        }
         self.paused = false;
         let _ = self.transport.send(TcpTransportMessage::Resume);
         Ok(())
     }
 
+    fn is_reading(&self, py: Python) -> PyResult<bool> {
+        Ok(!self.paused && !self.closing)
+    }
+
     //
     // close transport
     //
@@ -256,33 +525,115 @@ impl PyTcpTransport {
     }
 
     //
-    // abort transport
+    // abort transport, close socket immediately and discard buffered data.
+    // protocol's connection_lost() is called with None.
     //
     fn abort(&mut self, py: Python) -> PyResult<()> {
         self.closing = true;
-        let _ = self.transport.send(TcpTransportMessage::Shutdown);
+
+        if let Some(abort) = self.abort.take() {
+            // buffered data is dropped, do not touch protocol's flow control
+            self.write_buffer_size = 0;
+            self.writing_paused = false;
+            let _ = abort.send(());
+        }
         Ok(())
     }
 }
 
+// BufferedProtocol receives data with get_buffer()/buffer_updated(),
+// classic protocol with data_received(). BufferedProtocol is not
+// available before python 3.7
+fn data_callbacks(py: Python, protocol: &PyObject)
+                  -> PyResult<(Option<PyObject>, Option<(PyObject, PyObject)>)> {
+    if let Some(ref buffered) = Classes.BufferedProtocol {
+        if buffered.is_instance(py, protocol) {
+            return Ok((None, Some((protocol.getattr(py, "get_buffer")?,
+                                   protocol.getattr(py, "buffer_updated")?))))
+        }
+    }
+    Ok((Some(protocol.getattr(py, "data_received")?), None))
+}
+
+impl PyTcpTransport {
+
+    // log protocol failure and abort connection, same as asyncio's _fatal_error
+    fn fatal_error(&mut self, py: Python, err: PyErr, message: &str) {
+        self.evloop.as_ref(py).log_exception(
+            py, message, Some(err), None, Some(&self.error_context(py)));
+        let _ = self.abort(py);
+    }
+
+    // transport, protocol and socket for exception handler context
+    fn error_context(&self, py: Python) -> Vec<(PyObject, PyObject)> {
+        let mut context = vec![
+            ("transport".to_object(py), self.to_inst_ptr().into()),
+            ("protocol".to_object(py), self.protocol.clone_ref(py))];
+        if let Some(sock) = self.info.get("socket") {
+            context.push(("socket".to_object(py), sock.clone_ref(py)));
+        }
+        context
+    }
+
+    fn maybe_pause_protocol(&mut self, py: Python) {
+        if self.write_buffer_size <= self.high_water || self.writing_paused {
+            return
+        }
+        self.writing_paused = true;
+
+        if let Some(ref pause_writing) = self.pause_writing {
+            self.evloop.as_ref(py).with(
+                py, "protocol.pause_writing() failed",
+                |py| pause_writing.call(py, NoArgs, None));
+        }
+    }
+
+    fn maybe_resume_protocol(&mut self, py: Python) {
+        if !self.writing_paused || self.write_buffer_size > self.low_water {
+            return
+        }
+        self.writing_paused = false;
+
+        if let Some(ref resume_writing) = self.resume_writing {
+            self.evloop.as_ref(py).with(
+                py, "protocol.resume_writing() failed",
+                |py| resume_writing.call(py, NoArgs, None));
+        }
+    }
+}
+
 impl PyTcpTransportPtr {
 
     pub fn new(py: Python, evloop: &TokioEventLoop,
-               sender: Sender<TcpTransportMessage>,
+               sender: Sender<TcpTransportMessage>, abort: OneshotSender<()>,
                protocol: &PyObject, info: HashMap<&'static str, PyObject>) -> PyResult<PyTcpTransportPtr> {
 
         // get protocol callbacks
         let connection_made = protocol.getattr(py, "connection_made")?;
         let connection_lost = protocol.getattr(py, "connection_lost")?;
-        let data_received = protocol.getattr(py, "data_received")?;
+        let (data_received, buffered) = data_callbacks(py, protocol)?;
+        let eof_received = protocol.getattr(py, "eof_received").ok();
+        let pause_writing = protocol.getattr(py, "pause_writing").ok();
+        let resume_writing = protocol.getattr(py, "resume_writing").ok();
 
         let transport = py.init(|token| PyTcpTransport {
             evloop: evloop.to_inst_ptr(),
+            protocol: protocol.clone_ref(py),
             connection_lost: connection_lost,
             data_received: data_received,
+            buffered: buffered,
+            eof_received: eof_received,
+            pause_writing: pause_writing,
+            resume_writing: resume_writing,
             transport: sender,
+            abort: Some(abort),
             drain: None,
             drained: true,
+            eof: false,
+            write_buffer_size: 0,
+            high_water: HIGH_WATER,
+            low_water: HIGH_WATER / 4,
+            writing_paused: false,
             closing: false,
             info: info,
             paused: false,
@@ -321,6 +672,15 @@ impl PyTcpTransportPtr {
                 _ => {
                     trace!("Protocol.connection_lost(err): {:?}", err);
                     let mut e = err.to_pyerr(py);
+
+                    // same as asyncio, peer disconnects are not reported
+                    if !e.matches(py, (py.get_type::<exc::BrokenPipeError>(),
+                                       py.get_type::<exc::ConnectionResetError>(),
+                                       py.get_type::<exc::ConnectionAbortedError>())) {
+                        tr.evloop.as_ref(py).log_exception(
+                            py, "Fatal error on transport", Some(e.clone_ref(py)),
+                            None, Some(&tr.error_context(py)));
+                    }
                     tr.connection_lost.call(py, (e.instance(py),), None)
                         .into_log(py, "connection_lost error");
                 }
@@ -330,25 +690,105 @@ impl PyTcpTransportPtr {
 
     pub fn data_received(&self, bytes: Bytes) -> bool {
         self.with(|py, tr| {
-            tr.evloop.as_ref(py).with(
-                py, "data_received error", |py| {
-                    // let bytes = pybytes::PyBytes::new(py, bytes)?;
-                    let bytes = PyBytes::new(py, bytes.as_ref());
-                    tr.data_received.call(py, (bytes,), None)
-                });
+            // let bytes = pybytes::PyBytes::new(py, bytes)?;
+            let bytes = PyBytes::new(py, bytes.as_ref());
+            if let Some(ref data_received) = tr.data_received {
+                if let Err(err) = data_received.call(py, (bytes,), None) {
+                    tr.evloop.as_ref(py).log_exception(
+                        py, "Fatal error: protocol.data_received() call failed.",
+                        Some(err), None, Some(&tr.error_context(py)));
+                }
+            }
             !tr.paused
         })
     }
 
+    pub fn is_buffered(&self) -> bool {
+        self.with(|py, tr| tr.buffered.is_some())
+    }
+
+    // read from socket directly into buffer of BufferedProtocol.
+    // returns false if protocol paused reading, None on eof
+    pub fn buffer_received<R: io::Read>(&self, io: &mut R) -> io::Result<Option<bool>> {
+        self.with_mut(|py, tr| {
+            let (get_buffer, buffer_updated) = match tr.buffered {
+                Some((ref get_buffer, ref buffer_updated)) =>
+                    (get_buffer.clone_ref(py), buffer_updated.clone_ref(py)),
+                None => return Ok(Some(true)),
+            };
+
+            let buf = match get_buffer.call(py, (-1,), None)
+                .and_then(|buf| buffer::PyBuffer::get(py, &buf))
+            {
+                Ok(buf) => buf,
+                Err(err) => {
+                    tr.fatal_error(py, err, "Fatal error: protocol.get_buffer() call failed.");
+                    return Ok(Some(false))
+                }
+            };
+            if buf.readonly() || !buf.is_c_contiguous() || buf.len_bytes() == 0 {
+                let err = PyErr::new::<exc::RuntimeError, _>(
+                    py, "get_buffer() returned an empty or read-only buffer");
+                tr.fatal_error(py, err, "Fatal error: protocol.get_buffer() call failed.");
+                return Ok(Some(false))
+            }
+
+            let size = {
+                let data = unsafe {
+                    slice::from_raw_parts_mut(buf.buf_ptr() as *mut u8, buf.len_bytes())
+                };
+                io.read(data)?
+            };
+            if size == 0 {
+                return Ok(None)
+            }
+
+            if let Err(err) = buffer_updated.call(py, (size,), None) {
+                tr.fatal_error(py, err, "Fatal error: protocol.buffer_updated() call failed.");
+                return Ok(Some(false))
+            }
+            Ok(Some(!tr.paused))
+        })
+    }
+
+    // returns true if protocol wants to keep transport open
+    pub fn eof_received(&self) -> bool {
+        trace!("Protocol.eof_received()");
+        self.with(|py, tr| {
+            match tr.eof_received {
+                Some(ref eof_received) => match eof_received.call(py, NoArgs, None) {
+                    Ok(res) => res.is_true(py).unwrap_or(false),
+                    Err(err) => {
+                        tr.evloop.as_ref(py).log_exception(
+                            py, "Fatal error: protocol.eof_received() call failed.",
+                            Some(err), None, Some(&tr.error_context(py)));
+                        false
+                    }
+                },
+                None => false,
+            }
+        })
+    }
+
+    // part of write buffer is written to socket
+    pub fn written(&self, len: usize) {
+        self.with_mut(|py, tr| {
+            tr.write_buffer_size = tr.write_buffer_size.saturating_sub(len);
+            tr.maybe_resume_protocol(py);
+        })
+    }
+
     pub fn drained(&self) {
         self.with_mut(|py, tr| {
             tr.drained = true;
+            tr.write_buffer_size = 0;
             match tr.drain.take() {
                 Some(fut) => {
                     let _ = fut.as_mut(py).set(py, Ok(py.None()));
                 },
                 None => (),
             }
+            tr.maybe_resume_protocol(py);
         })
     }
 }
@@ -362,15 +802,57 @@ enum TransportState {
     Closed,
 }
 
+// socket wrapper, counts bytes written to socket for write flow control
+struct CountingIo<T> {
+    io: T,
+    written: usize,
+}
+
+impl<T: io::Read> io::Read for CountingIo<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<T: io::Write> io::Write for CountingIo<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.io.write(buf)?;
+        self.written += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for CountingIo<T> {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.io.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for CountingIo<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
 struct TcpTransport<T> {
     fd: RawFd,
-    framed: Framed<T, TcpTransportCodec>,
+    // taken apart only while moving buffered data to BufferedProtocol
+    framed: Option<Framed<CountingIo<T>, TcpTransportCodec>>,
     intake: unsync::mpsc::UnboundedReceiver<TcpTransportMessage>,
+    abort: Option<oneshot::Receiver<()>>,
     transport: PyTcpTransportPtr,
 
     buf: Option<BytesMsg>,
+    framed_read: bool,
     incoming_eof: bool,
     flushed: bool,
+    write_eof: bool,
+    eof_sent: bool,
+    keep_open: bool,
     state: TransportState,
 }
 
@@ -380,22 +862,87 @@ impl<T> TcpTransport<T>
 
     fn new(socket: T,
            intake: mpsc::UnboundedReceiver<TcpTransportMessage>,
+           abort: oneshot::Receiver<()>,
            transport: PyTcpTransportPtr) -> TcpTransport<T> {
 
         TcpTransport {
             fd: socket.as_raw_fd(),
-            framed: socket.framed(TcpTransportCodec),
+            framed: Some(CountingIo{io: socket, written: 0}.framed(TcpTransportCodec)),
             intake: intake,
+            abort: Some(abort),
             transport: transport,
 
             buf: None,
+            framed_read: false,
             incoming_eof: false,
             flushed: true,
+            write_eof: false,
+            eof_sent: false,
+            keep_open: false,
             state: TransportState::Normal,
         }
     }
 }
 
+impl<T> TcpTransport<T>
+    where T: AsyncRead + AsyncWrite
+{
+
+    fn framed(&mut self) -> &mut Framed<CountingIo<T>, TcpTransportCodec> {
+        self.framed.as_mut().expect("framed is taken apart")
+    }
+
+    // data already read from socket by framed reader goes to
+    // BufferedProtocol first. returns false if protocol paused reading
+    fn drain_read_buffer(&mut self) -> io::Result<bool> {
+        if !mem::replace(&mut self.framed_read, false) {
+            return Ok(true)
+        }
+        let mut parts = match self.framed.take() {
+            Some(framed) => framed.into_parts(),
+            None => return Ok(true),
+        };
+
+        let mut result = Ok(true);
+        while !parts.readbuf.is_empty() {
+            let (res, consumed) = {
+                let mut data: &[u8] = &parts.readbuf;
+                let res = self.transport.buffer_received(&mut data);
+                (res, parts.readbuf.len() - data.len())
+            };
+            parts.readbuf.split_to(consumed);
+
+            match res {
+                Ok(Some(true)) => continue,
+                Ok(Some(false)) => result = Ok(false),
+                Ok(None) => (),
+                Err(err) => result = Err(err),
+            }
+            break
+        }
+
+        // protocol paused reading, rest goes on next resume
+        self.framed_read = !parts.readbuf.is_empty();
+        self.framed = Some(Framed::from_parts(parts, TcpTransportCodec));
+        result
+    }
+
+    fn received_eof(&mut self) {
+        self.incoming_eof = true;
+
+        // protocol could write response and close transport,
+        // otherwise transport closes itself
+        self.keep_open = self.transport.eof_received();
+        if !self.keep_open {
+            match self.state {
+                TransportState::Normal | TransportState::Paused =>
+                    self.state = TransportState::Closing,
+                _ => (),
+            }
+        }
+    }
+}
+
 
 impl<T> Future for TcpTransport<T>
     where T: AsyncRead + AsyncWrite
@@ -405,6 +952,26 @@ impl<T> Future for TcpTransport<T>
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         // println!("POLL");
+
+        // transport aborted, drop socket and all buffered data
+        let aborted = match self.abort {
+            Some(ref mut abort) => match abort.poll() {
+                Ok(Async::Ready(_)) => Some(true),
+                Ok(Async::NotReady) => None,
+                Err(_) => Some(false),
+            },
+            None => None,
+        };
+        match aborted {
+            Some(true) => {
+                self.state = TransportState::Closed;
+                self.buf.take();
+                return Ok(Async::Ready(()))
+            },
+            Some(false) => { self.abort.take(); },
+            None => (),
+        }
+
         loop {
             let bytes = if let Some(bytes) = self.buf.take() {
                 Some(bytes)
@@ -436,6 +1003,10 @@ impl<T> Future for TcpTransport<T>
                                 }
                                 return self.poll()
                             },
+                            TcpTransportMessage::WriteEof => {
+                                self.write_eof = true;
+                                continue
+                            },
                             TcpTransportMessage::Close => {
                                 match self.state {
                                     TransportState::Normal | TransportState::Paused =>
@@ -444,11 +1015,6 @@ impl<T> Future for TcpTransport<T>
                                 }
                                 None
                             }
-                            TcpTransportMessage::Shutdown => {
-                                self.state = TransportState::Closed;
-                                let _ = self.framed.get_mut().shutdown();
-                                return Ok(Async::Ready(()))
-                            }
                         }
                     }
                     Ok(_) => None,
@@ -462,14 +1028,15 @@ impl<T> Future for TcpTransport<T>
                 self.flushed = false;
 
                 //println!("sending bytes {:?}", self.fd);
-                match self.framed.start_send(bytes) {
+                match self.framed().start_send(bytes) {
                     Ok(AsyncSink::NotReady(bytes)) => {
                         //println!("sink not ready");
                         self.buf = Some(bytes);
                         break
                     }
                     Ok(AsyncSink::Ready) => continue,
-                    Err(_) => return Err(io::Error::new(io::ErrorKind::Other, "Closed")),
+                    // keep os error, protocol gets matching OSError
+                    Err(err) => return Err(err),
                 }
             } else {
                 break
@@ -478,7 +1045,7 @@ impl<T> Future for TcpTransport<T>
 
         // flush sink
         if !self.flushed {
-            self.flushed = self.framed.poll_complete()?.is_ready();
+            self.flushed = self.framed().poll_complete()?.is_ready();
             //println!("flushed: {:?} {:?}", self.fd, self.flushed);
 
             if self.flushed {
@@ -486,10 +1053,60 @@ impl<T> Future for TcpTransport<T>
             }
         }
 
+        // partially flushed, protocol could be resumed at low-water limit
+        let written = mem::replace(&mut self.framed().get_mut().written, 0);
+        if written > 0 && !self.flushed {
+            self.transport.written(written);
+        }
+
+        // half-close, shutdown write side of socket
+        if self.write_eof && self.flushed && !self.eof_sent {
+            self.eof_sent = true;
+            if unsafe { libc::shutdown(self.fd, libc::SHUT_WR) } != 0 {
+                return Err(io::Error::last_os_error())
+            }
+        }
+
+        // protocol switched to BufferedProtocol, deliver data read by framed
+        if !self.incoming_eof && self.state != TransportState::Paused &&
+            self.transport.is_buffered()
+        {
+            match self.drain_read_buffer() {
+                Ok(true) => (),
+                Ok(false) => self.state = TransportState::Paused,
+                Err(err) => return Err(err),
+            }
+        }
+
+        // read directly into protocol's buffer
+        if !self.incoming_eof && self.state != TransportState::Paused &&
+            self.transport.is_buffered()
+        {
+            loop {
+                let io = self.framed.as_mut().expect("framed is taken apart").get_mut();
+                match self.transport.buffer_received(io) {
+                    Ok(Some(true)) => continue,
+                    Ok(Some(false)) => {
+                        self.state = TransportState::Paused;
+                        break
+                    },
+                    Ok(None) => {
+                        self.received_eof();
+                        return self.poll()
+                    },
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
         // poll for incoming data
-        if !self.incoming_eof && self.state != TransportState::Paused {
+        if !self.incoming_eof && self.state != TransportState::Paused &&
+            !self.transport.is_buffered()
+        {
+            self.framed_read = true;
             loop {
-                match self.framed.poll() {
+                match self.framed().poll() {
                     Ok(Async::Ready(Some(bytes))) => {
                         //println!("received: {:?}", self.fd);
                         if ! self.transport.data_received(bytes) {
@@ -498,8 +1115,10 @@ impl<T> Future for TcpTransport<T>
                         }
                         continue
                     },
-                    Ok(Async::Ready(None)) =>
-                        self.incoming_eof = true,
+                    Ok(Async::Ready(None)) => {
+                        self.received_eof();
+                        return self.poll()
+                    },
                     Ok(Async::NotReady) => {
                         //println!("received not ready: {:?}", self.fd);
                         ()
@@ -513,13 +1132,13 @@ impl<T> Future for TcpTransport<T>
 
         // close
         if self.state == TransportState::Closing {
-            if self.incoming_eof {
+            if self.incoming_eof && self.flushed {
                 return Ok(Async::Ready(()))
             }
-            return self.framed.close();
+            return self.framed().close();
         }
 
-        if self.flushed && self.incoming_eof {
+        if self.flushed && self.incoming_eof && !self.keep_open {
             Ok(Async::Ready(()))
         } else {
             Ok(Async::NotReady)
@@ -548,16 +1167,10 @@ impl Encoder for TcpTransportCodec {
     type Error = io::Error;
 
     fn encode(&mut self, msg: BytesMsg, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        dst.reserve(msg.len);
-        {
-            let mut slice = unsafe { dst.bytes_mut() };
-            msg.buf.copy_to_slice(GIL::python(), &mut slice[..msg.len])?;
-        }
-        unsafe {
-            let new_len = dst.len() + msg.len;
-            dst.set_len(new_len);
+        match msg {
+            BytesMsg::Buffer{buf, len} => copy_buffer(GIL::python(), &buf, len, dst)?,
+            BytesMsg::Joined(bytes) => dst.extend_from_slice(&bytes),
         }
-
         Ok(())
     }
 
@@ -3,10 +3,13 @@
 use pyo3;
 use pyo3::*;
 use std::io;
+use std::u64;
 use std::os::raw::c_long;
 use std::time::Duration;
 use std::error::Error;
 use std::fmt::Write;
+use std::ffi::CStr;
+use libc;
 
 use pyfuture::PyFuture;
 use addrinfo::LookupError;
@@ -21,14 +24,26 @@ pub struct WorkingClasses {
     pub InvalidStateError: PyType,
     pub TimeoutError: PyType,
     pub SSLProto: PyType,
+    // python 3.7+
+    pub BufferedProtocol: Option<PyType>,
+    pub SSLContext: PyType,
     pub Coroutines: PyModule,
     pub UnixEvents: PyModule,
+    pub Events: PyModule,
+    pub ConcurrentFuture: PyType,
 
     pub Helpers: PyModule,
 
     pub Exception: PyType,
     pub BaseException: PyType,
     pub StopIteration: PyType,
+    pub MemoryView: PyType,
+    pub Set: PyType,
+    pub WeakRef: PyType,
+    pub WeakSet: PyType,
+    pub ResourceWarning: PyType,
+    pub Warnings: PyModule,
+    pub Builtins: PyModule,
 
     pub Socket: PyModule,
     pub GaiError: PyType,
@@ -69,8 +84,16 @@ lazy_static! {
                 py, &asyncio.get(py, "TimeoutError").unwrap()).unwrap(),
             SSLProto: PyType::extract(
                 py, &sslproto.get(py, "SSLProtocol").unwrap()).unwrap(),
+            BufferedProtocol: asyncio.get(py, "BufferedProtocol").ok()
+                .and_then(|tp| PyType::extract(py, &tp).ok()),
+            SSLContext: PyType::extract(
+                py, &py.import("ssl").unwrap().get(py, "SSLContext").unwrap()).unwrap(),
             Coroutines: py.import("asyncio.coroutines").unwrap(),
             UnixEvents: py.import("asyncio.unix_events").unwrap(),
+            Events: py.import("asyncio.events").unwrap(),
+            ConcurrentFuture: PyType::extract(
+                py, &py.import("concurrent.futures").unwrap()
+                    .get(py, "Future").unwrap()).unwrap(),
 
             Helpers: py.import("tokio.helpers").unwrap(),
 
@@ -81,6 +104,18 @@ lazy_static! {
                 py, &builtins.get(py, "Exception").unwrap()).unwrap(),
             BaseException: PyType::extract(
                 py, &builtins.get(py, "BaseException").unwrap()).unwrap(),
+            MemoryView: PyType::extract(
+                py, &builtins.get(py, "memoryview").unwrap()).unwrap(),
+            Set: PyType::extract(
+                py, &builtins.get(py, "set").unwrap()).unwrap(),
+            WeakRef: PyType::extract(
+                py, &py.import("weakref").unwrap().get(py, "ref").unwrap()).unwrap(),
+            WeakSet: PyType::extract(
+                py, &py.import("weakref").unwrap().get(py, "WeakSet").unwrap()).unwrap(),
+            ResourceWarning: PyType::extract(
+                py, &builtins.get(py, "ResourceWarning").unwrap()).unwrap(),
+            Warnings: py.import("warnings").unwrap(),
+            Builtins: builtins.clone_ref(py),
 
             SocketTimeout: PyType::extract(
                 py, &socket.get(py, "timeout").unwrap()).unwrap(),
@@ -167,10 +202,20 @@ pub trait ToPyErr {
 
 }
 
-/// Create OSError from io::Error
+/// Create OSError from io::Error, os errors get converted to
+/// matching OSError subclass with errno and strerror set
 impl ToPyErr for io::Error {
 
     fn to_pyerr(&self, py: Python) -> PyErr {
+        if let Some(errno) = self.raw_os_error() {
+            let strerror = unsafe { CStr::from_ptr(libc::strerror(errno)) }.to_string_lossy();
+            // OSError constructor picks subclass for errno
+            match py.get_type::<exc::OSError>().call(py, (errno, strerror.as_ref()), None) {
+                Ok(exc) => return PyErr::from_instance(py, exc),
+                Err(err) => return err,
+            }
+        }
+
         let tp = match self.kind() {
             io::ErrorKind::BrokenPipe => py.get_type::<exc::BrokenPipeError>(),
             io::ErrorKind::ConnectionRefused => py.get_type::<exc::ConnectionRefusedError>(),
@@ -198,6 +243,8 @@ impl ToPyErr for LookupError {
             &LookupError::IOError(ref err) => err.to_pyerr(py),
             &LookupError::Other(ref err_str) =>
                 PyErr::new_err(py, &Classes.GaiError, (err_str.to_object(py),)),
+            &LookupError::Gai(code, ref err_str) =>
+                PyErr::new_err(py, &Classes.GaiError, (code, err_str.to_object(py))),
             &LookupError::NulError(_) =>
                 PyErr::new_err(py, &Classes.GaiError, ("nil pointer",)),
             &LookupError::Generic =>
@@ -222,6 +269,22 @@ pub fn print_exception(py: Python, w: &mut String, err: PyErr) {
     }
 }
 
+//
+// check if OSError is caused by fd exhaustion (EMFILE or ENFILE)
+//
+pub fn is_fd_exhausted(py: Python, err: &PyErr) -> bool {
+    if !err.matches(py, py.get_type::<exc::OSError>()) {
+        return false
+    }
+    match err.clone_ref(py).instance(py).getattr(py, "errno") {
+        Ok(errno) => match errno.extract::<i32>(py) {
+            Ok(libc::EMFILE) | Ok(libc::ENFILE) => true,
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
 //
 // convert PyFloat or PyInt into Duration
 //
@@ -252,12 +315,15 @@ pub fn parse_seconds(py: Python, name: &str, value: PyObject) -> PyResult<Option
 
 //
 // convert PyFloat or PyInt into u64 (milliseconds)
+// negative values are treated as zero
 //
 pub fn parse_millis(py: Python, name: &str, value: PyObject) -> PyResult<u64> {
     if let Ok(f) = PyFloat::downcast_from(py, &value) {
-        let val = f.value(py);
-        if val > 0.0 {
-            Ok((val * 1000.0) as u64)
+        let val = f.value(py) * 1000.0;
+        if val >= u64::MAX as f64 {
+            Ok(u64::MAX)
+        } else if val > 0.0 {
+            Ok(val as u64)
         } else {
             Ok(0)
         }
@@ -266,7 +332,7 @@ pub fn parse_millis(py: Python, name: &str, value: PyObject) -> PyResult<u64> {
             if val < 0 {
                 Ok(0)
             } else {
-                Ok((val * 1000) as u64)
+                Ok((val as u64).saturating_mul(1000))
             }
         } else {
             Ok(0)
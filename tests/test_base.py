@@ -4,8 +4,11 @@
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
 import asyncio
+import gc
 import logging
+import operator
 import os
+import socket
 import threading
 import time
 import weakref
@@ -14,6 +17,8 @@ from unittest import mock
 import pytest
 import uvloop
 
+import tokio
+
 
 def test_close(loop):
     assert not loop.is_closed()
@@ -34,6 +39,101 @@ def test_close(loop):
         loop.run_until_complete(f)
 
 
+def test_loop_weakref():
+    loop = tokio.new_event_loop()
+    ref = weakref.ref(loop)
+    assert ref() is loop
+
+    loop.close()
+    del loop
+    gc.collect()
+    assert ref() is None
+
+
+def test_run():
+    cancelled = []
+
+    async def forever():
+        try:
+            await asyncio.sleep(10)
+        except asyncio.CancelledError:
+            cancelled.append(True)
+            raise
+
+    async def main():
+        loop = asyncio.get_event_loop()
+        assert isinstance(loop, tokio._tokio.TokioEventLoop)
+        assert loop.get_debug()
+
+        loop.create_task(forever())
+        await asyncio.sleep(0.01)
+
+        async def nested():
+            pass
+
+        coro = nested()
+        with pytest.raises(RuntimeError):
+            tokio.run(coro)
+        coro.close()
+
+        return loop, 42
+
+    loop, result = tokio.run(main(), debug=True)
+    assert result == 42
+    assert cancelled == [True]
+    assert loop.is_closed()
+
+    with pytest.raises(ValueError):
+        tokio.run(42)
+
+
+def test_run_shutdown_asyncgens():
+    closed = []
+
+    async def agen():
+        try:
+            yield 1
+            yield 2
+        finally:
+            closed.append(True)
+
+    async def main():
+        gen = agen()
+        assert await gen.__anext__() == 1
+        return gen
+
+    gen = tokio.run(main())
+    assert closed == [True]
+    assert gen.ag_frame is None
+
+
+def test_shutdown_asyncgens(tokio_loop):
+    loop = tokio_loop
+    closed = []
+
+    async def agen():
+        try:
+            yield 1
+        finally:
+            closed.append(True)
+
+    async def start():
+        gen = agen()
+        await gen.__anext__()
+        return gen
+
+    gen = loop.run_until_complete(start())
+    assert closed == []
+
+    loop.run_until_complete(loop.shutdown_asyncgens())
+    assert closed == [True]
+
+    # generators started after shutdown are reported
+    with pytest.warns(ResourceWarning):
+        gen2 = loop.run_until_complete(start())
+    loop.run_until_complete(gen2.aclose())
+
+
 @pytest.mark.skip
 def test_handle_weakref(loop):
     wd = weakref.WeakValueDictionary()
@@ -61,6 +161,210 @@ def test_call_soon(loop):
     assert calls == [10, 1]
 
 
+def test_get_running_loop(loop):
+    loops = []
+
+    def cb():
+        loops.append(asyncio.events._get_running_loop())
+        loop.stop()
+
+    async def coro():
+        return asyncio.events._get_running_loop()
+
+    loop.call_soon(cb)
+    loop.run_forever()
+
+    assert loops == [loop]
+    assert loop.run_until_complete(coro()) is loop
+
+    # not running anymore
+    assert asyncio.events._get_running_loop() is None
+
+
+def test_run_while_another_loop_running(tokio_loop):
+    loop = tokio_loop
+    other = tokio.new_event_loop()
+    errors = []
+
+    def cb():
+        for run in (other.run_forever,
+                    lambda: other.run_until_complete(other.create_future())):
+            try:
+                run()
+            except RuntimeError as exc:
+                errors.append(str(exc))
+        loop.stop()
+
+    try:
+        loop.call_soon(cb)
+        loop.run_forever()
+    finally:
+        other.close()
+
+    assert errors == [
+        'Cannot run the event loop while another loop is running'] * 2
+
+
+def test_call_soon_fifo_order(loop):
+    # more callbacks than single ready queue batch
+    total = 3000
+    calls = []
+
+    def last():
+        calls.append('last')
+        loop.stop()
+
+    def cb(i):
+        calls.append(i)
+        if i == 0:
+            # scheduled during execution, runs after all registered ones
+            loop.call_soon(last)
+
+    for i in range(total):
+        loop.call_soon(cb, i)
+    loop.run_forever()
+
+    assert calls == list(range(total)) + ['last']
+
+
+def test_call_soon_io_not_starved(tokio_loop):
+    loop = tokio_loop
+    total = 10000
+    calls = 0
+    seen = []
+
+    rsock, wsock = socket.socketpair()
+
+    def cb():
+        nonlocal calls
+        calls += 1
+        if calls == total:
+            loop.stop()
+
+    def on_read():
+        rsock.recv(1)
+        seen.append(calls)
+        loop.remove_reader(rsock.fileno())
+
+    with rsock, wsock:
+        wsock.send(b'x')
+        loop.add_reader(rsock.fileno(), on_read)
+        for _ in range(total):
+            loop.call_soon(cb)
+        loop.run_forever()
+
+    assert calls == total
+    assert len(seen) == 1
+    assert seen[0] < total
+
+
+def run_turns(loop, count):
+    # drive loop for fixed number of reactor turns
+    start = loop._loop_turn()
+
+    def cb():
+        if loop._loop_turn() - start < count:
+            loop.call_soon(cb)
+        else:
+            loop.stop()
+
+    loop.call_soon(cb)
+    loop.run_forever()
+
+
+def test_loop_turn(tokio_loop):
+    loop = tokio_loop
+    turns = []
+
+    def cb(n):
+        turns.append(loop._loop_turn())
+        if n:
+            loop.call_soon(cb, n - 1)
+        else:
+            loop.stop()
+
+    assert loop.is_idle()
+    start = loop._loop_turn()
+    loop.call_soon(cb, 3)
+    assert not loop.is_idle()
+    loop.run_forever()
+
+    assert turns == sorted(set(turns))
+    assert turns[0] > start
+
+    h = loop.call_later(10, cb, 0)
+    assert not loop.is_idle()
+    h.cancel()
+    run_turns(loop, 3)
+    assert loop.is_idle()
+
+
+def test_call_later_cancel_releases_timer(tokio_loop):
+    loop = tokio_loop
+    baseline = loop._pending_timers()
+
+    handles = [loop.call_later(3600, lambda: None) for _ in range(1000)]
+    assert loop._pending_timers() == baseline + 1000
+
+    for h in handles:
+        h.cancel()
+    run_turns(loop, 3)
+
+    assert loop._pending_timers() == baseline
+
+
+def test_call_soon_await(tokio_loop):
+    loop = tokio_loop
+    calls = []
+
+    def cb(arg):
+        calls.append(arg)
+        return arg * 2
+
+    async def main():
+        h = loop.call_soon(cb, 21)
+        assert calls == []
+        assert await h == 42
+        assert calls == [21]
+
+        # already executed, result is kept by waiter
+        assert await h == 42
+
+        # awaited after execution only, result is not retained
+        class Result:
+            pass
+
+        refs = []
+
+        def make_result():
+            res = Result()
+            refs.append(weakref.ref(res))
+            return res
+
+        h = loop.call_soon(make_result)
+        await asyncio.sleep(0)
+        gc.collect()
+        assert refs[0]() is None
+        assert await h is None
+
+        errors = []
+        loop.set_exception_handler(lambda loop, ctx: errors.append(ctx))
+        h = loop.call_soon(operator.truediv, 1, 0)
+        for _ in range(2):
+            with pytest.raises(ZeroDivisionError):
+                await h
+        assert len(errors) == 1
+        loop.set_exception_handler(None)
+
+        h = loop.call_soon(cb, 1)
+        h.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await h
+        assert calls == [21]
+
+    loop.run_until_complete(main())
+
+
 def test_call_soon_base_exc(loop):
     def cb():
         raise KeyboardInterrupt()
@@ -163,6 +467,40 @@ def test_call_later_2(loop):
     assert delta > 0.019
 
 
+def test_call_later_pending_on_close(loop):
+    calls = []
+
+    h = loop.call_later(10, calls.append, 'a')
+    loop.call_later(0.01, loop.stop)
+    loop.run_forever()
+
+    # pending timer does not prevent loop from closing
+    loop.close()
+    assert loop.is_closed()
+    assert calls == []
+
+    h.cancel()
+
+
+def test_call_later_cancel_releases_callback(loop):
+    class Callback:
+        def __call__(self, arg):
+            pass
+
+    cb = Callback()
+    arg = Callback()
+    cb_ref = weakref.ref(cb)
+    arg_ref = weakref.ref(arg)
+
+    h = loop.call_later(10, cb, arg)
+    del cb, arg
+    h.cancel()
+    gc.collect()
+
+    assert cb_ref() is None
+    assert arg_ref() is None
+
+
 def test_call_later_negative(loop):
     calls = []
 
@@ -175,6 +513,23 @@ def test_call_later_negative(loop):
     assert calls == ['a']
 
 
+def test_call_later_negative_float(loop):
+    calls = []
+
+    def cb(arg):
+        calls.append(arg)
+        loop.stop()
+
+    loop.call_later(-1.0, cb, 'a')
+
+    started = time.monotonic()
+    loop.run_forever()
+    finished = time.monotonic()
+
+    assert calls == ['a']
+    assert finished - started < 0.01
+
+
 @pytest.mark.skipif(os.environ.get('TRAVIS_OS_NAME') is not None,
                     reason='time is not monotonic on Travis')
 def test_call_at(loop):
@@ -200,6 +555,38 @@ def test_call_at(loop):
     assert finished - started > 0.045
 
 
+def test_call_at_past(loop):
+    calls = []
+
+    def cb(arg):
+        calls.append(arg)
+        loop.stop()
+
+    loop.run_until_complete(asyncio.sleep(0.01, loop=loop))
+    loop.call_at(loop.time() - 0.005, cb, 'a')
+
+    started = time.monotonic()
+    loop.run_forever()
+    finished = time.monotonic()
+
+    assert calls == ['a']
+    assert finished - started < 0.01
+
+
+def test_call_at_ordering(loop):
+    calls = []
+    base = loop.time() + 0.01
+    whens = [base + i * 0.002 for i in range(20)]
+
+    # register in reverse order
+    for when in reversed(whens):
+        loop.call_at(when, calls.append, when)
+    loop.call_at(whens[-1] + 0.01, loop.stop)
+
+    loop.run_forever()
+    assert calls == whens
+
+
 def test_check_thread(loop, other_loop):
     def check_thread(loop, debug):
         def cb():
@@ -267,6 +654,103 @@ def test_check_thread(loop, other_loop):
     # test_thread(loop, False, create_loop=True)
 
 
+def test_call_soon_threadsafe_wakeup(loop):
+    delays = []
+
+    def cb(started):
+        delays.append(time.monotonic() - started)
+        loop.stop()
+
+    def thread():
+        time.sleep(0.1)
+        loop.call_soon_threadsafe(cb, time.monotonic())
+
+    # loop is idle, waiting on a long timer
+    handle = loop.call_later(10, loop.stop)
+    th = threading.Thread(target=thread)
+    th.start()
+    loop.run_forever()
+    th.join()
+    handle.cancel()
+
+    assert len(delays) == 1
+    assert delays[0] < 0.5
+
+
+def test_run_coroutine_threadsafe(tokio_loop):
+    results = []
+
+    async def coro(value):
+        await asyncio.sleep(0.01, loop=tokio_loop)
+        return value
+
+    def run_in_thread():
+        fut = tokio.run_coroutine_threadsafe(coro(42), tokio_loop)
+        results.append(fut.result(timeout=5))
+        tokio_loop.call_soon_threadsafe(tokio_loop.stop)
+
+    thread = threading.Thread(target=run_in_thread)
+    thread.start()
+    tokio_loop.run_forever()
+    thread.join()
+
+    assert results == [42]
+
+
+def test_run_coroutine_threadsafe_error(tokio_loop):
+    async def coro():
+        raise ValueError()
+
+    fut = tokio.run_coroutine_threadsafe(coro(), tokio_loop)
+    tokio_loop.run_until_complete(asyncio.sleep(0.01, loop=tokio_loop))
+
+    with pytest.raises(ValueError):
+        fut.result(timeout=0)
+
+    with pytest.raises(TypeError):
+        tokio.run_coroutine_threadsafe(lambda: None, tokio_loop)
+
+
+def test_run_coroutine_threadsafe_task_cancelled(tokio_loop):
+    started = asyncio.Event(loop=tokio_loop)
+
+    async def coro():
+        started.set()
+        await asyncio.sleep(10, loop=tokio_loop)
+
+    fut = tokio.run_coroutine_threadsafe(coro(), tokio_loop)
+    tokio_loop.run_until_complete(started.wait())
+
+    # cancelled task cancels concurrent future
+    for task in tokio_loop.all_tasks():
+        task.cancel()
+    tokio_loop.run_until_complete(asyncio.sleep(0.01, loop=tokio_loop))
+
+    assert fut.cancelled()
+
+
+def test_run_coroutine_threadsafe_future_cancelled(tokio_loop):
+    started = asyncio.Event(loop=tokio_loop)
+    cancelled = []
+
+    async def coro():
+        started.set()
+        try:
+            await asyncio.sleep(10, loop=tokio_loop)
+        except asyncio.CancelledError:
+            cancelled.append(True)
+            raise
+
+    fut = tokio.run_coroutine_threadsafe(coro(), tokio_loop)
+    tokio_loop.run_until_complete(started.wait())
+
+    # cancelled concurrent future cancels task
+    assert fut.cancel()
+    tokio_loop.run_until_complete(asyncio.sleep(0.01, loop=tokio_loop))
+
+    assert cancelled == [True]
+
+
 def test_run_once_in_executor_plain(loop):
     called = []
 
@@ -288,6 +772,62 @@ def test_set_debug(loop):
     assert not loop.get_debug()
 
 
+def test_core_id(tokio_loop):
+    other = tokio.new_event_loop()
+    try:
+        assert tokio_loop.core_id() != other.core_id()
+        assert tokio_loop.core_id() in repr(tokio_loop)
+        assert 'closed=False' in repr(other)
+    finally:
+        other.close()
+
+    core_id = other.core_id()
+    assert other.core_id() == core_id
+    assert 'closed=True' in repr(other)
+
+
+def test_debug_stats(tokio_loop):
+    calls = []
+
+    # stats are not collected in non-debug mode
+    tokio_loop.call_soon(calls.append, 0)
+    tokio_loop.call_soon(tokio_loop.stop)
+    tokio_loop.run_forever()
+    assert tokio_loop._debug_stats()['handles'] == 0
+
+    tokio_loop.set_debug(True)
+    for i in range(5):
+        tokio_loop.call_soon(calls.append, i)
+    tokio_loop.call_soon(time.sleep, 0.01)
+    tokio_loop.call_soon(tokio_loop.stop)
+    tokio_loop.run_forever()
+
+    stats = tokio_loop._debug_stats()
+    assert stats['handles'] >= 7
+    assert stats['turns'] >= 1
+    # all handles were scheduled before start, they run in one turn
+    assert stats['max_turn_handles'] >= stats['turn_handles'] >= 7
+    assert stats['max_latency'] > 0
+    assert stats['max_latency'] >= stats['avg_latency'] > 0
+
+    tokio_loop._reset_debug_stats()
+    assert tokio_loop._debug_stats() == {
+        'handles': 0, 'turns': 0, 'turn_handles': 0, 'max_turn_handles': 0,
+        'max_latency': 0.0, 'avg_latency': 0.0}
+
+    # per-turn counters follow reactor turns
+    tokio_loop.call_soon(calls.append, 0)
+    tokio_loop.call_soon(lambda: tokio_loop.call_soon(tokio_loop.stop))
+    tokio_loop.run_forever()
+
+    stats = tokio_loop._debug_stats()
+    assert stats['handles'] >= 3
+    assert stats['turns'] >= 2
+    assert stats['max_turn_handles'] >= 2
+    assert stats['turn_handles'] >= 1
+    tokio_loop.set_debug(False)
+
+
 def test_run_until_complete_type_error(loop):
     with pytest.raises(TypeError):
         loop.run_until_complete('blah')
@@ -307,6 +847,46 @@ def test_run_until_complete_error(loop):
         loop.run_until_complete(foo())
 
 
+def test_run_until_complete_nested(loop):
+    errors = []
+    called = []
+
+    def nested():
+        for run in (lambda: loop.run_until_complete(asyncio.sleep(0, loop=loop)),
+                    loop.run_forever):
+            try:
+                run()
+            except RuntimeError as exc:
+                errors.append(str(exc))
+
+        loop.call_soon(called.append, loop.is_running())
+        loop.call_soon(loop.stop)
+
+    loop.call_soon(nested)
+    loop.run_forever()
+
+    assert errors == ['This event loop is already running'] * 2
+    assert called == [True]
+    assert not loop.is_running()
+
+
+def test_run_until_complete_nested_after_stop(loop):
+    errors = []
+
+    def nested():
+        loop.stop()
+        assert loop.is_running()
+        try:
+            loop.run_until_complete(asyncio.sleep(0, loop=loop))
+        except RuntimeError as exc:
+            errors.append(str(exc))
+
+    loop.call_soon(nested)
+    loop.run_forever()
+
+    assert errors == ['This event loop is already running']
+
+
 @pytest.mark.skip(reason='tokio is not support this')
 def test_debug_slow_callbacks(loop):
     logger = logging.getLogger('asyncio')
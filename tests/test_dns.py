@@ -3,10 +3,14 @@
 #
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
+import asyncio
+import logging
 import socket
 
 import pytest
 
+import tokio
+
 
 @pytest.mark.parametrize(
     'args', [(('example.com', 80), {}),
@@ -52,9 +56,14 @@ import pytest
              (('127.0.0.1', 80), {}),
              (('127.0.0.1', 80), {'type': socket.SOCK_STREAM})])
 def test_getaddrinfo(loop, args):
+    kwargs = dict(args[1])
+    if isinstance(loop, tokio._tokio.TokioEventLoop):
+        # tokio loop uses AI_ADDRCONFIG | AI_V4MAPPED by default
+        kwargs.setdefault('flags', socket.AI_ADDRCONFIG | socket.AI_V4MAPPED)
+
     err = None
     try:
-        a1 = socket.getaddrinfo(*args[0], **args[1])
+        a1 = socket.getaddrinfo(*args[0], **kwargs)
     except socket.gaierror as ex:
         err = ex
 
@@ -104,3 +113,175 @@ def test_getnameinfo(loop, args):
             raise err
 
         assert a1 == a2
+
+
+def _has_ipv6():
+    try:
+        infos = socket.getaddrinfo(
+            'localhost', 80, flags=socket.AI_ADDRCONFIG)
+    except socket.gaierror:
+        return False
+    return any(info[0] == socket.AF_INET6 for info in infos)
+
+
+def test_getaddrinfo_addrconfig(tokio_loop):
+    loop = tokio_loop
+
+    infos = loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    expected = socket.getaddrinfo(
+        'localhost', 80, flags=socket.AI_ADDRCONFIG | socket.AI_V4MAPPED)
+    assert infos == expected
+    if not _has_ipv6():
+        assert all(info[0] != socket.AF_INET6 for info in infos)
+
+    # explicit flags override default
+    infos = loop.run_until_complete(
+        loop.getaddrinfo('localhost', 80, flags=0))
+    assert infos == socket.getaddrinfo('localhost', 80, flags=0)
+
+
+def test_getaddrinfo_cancel(loop):
+    errors = []
+    loop.set_exception_handler(lambda loop, ctx: errors.append(ctx))
+
+    async def runner():
+        # more lookups than resolver workers
+        futs = [loop.getaddrinfo('localhost', 80) for _ in range(10)]
+        for fut in futs:
+            fut.cancel()
+        await asyncio.sleep(0.1, loop=loop)
+
+        # resolver is still operational
+        res = await asyncio.wait_for(
+            loop.getaddrinfo('127.0.0.1', 80), 5, loop=loop)
+        assert res
+
+    loop.run_until_complete(runner())
+    assert errors == []
+
+
+def test_getaddrinfo_dns_cache(tokio_loop):
+    loop = tokio_loop
+    assert loop._dns_cache_info() is None
+
+    loop._set_dns_cache_ttl(60)
+
+    res1 = loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    assert loop._dns_cache_info() == (0, 1)
+
+    # second lookup does not hit resolver
+    res2 = loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    assert loop._dns_cache_info() == (1, 1)
+    assert res1 == res2
+
+    # different key
+    loop.run_until_complete(loop.getaddrinfo('localhost', 81))
+    assert loop._dns_cache_info() == (1, 2)
+
+    loop._clear_dns_cache()
+    loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    assert loop._dns_cache_info() == (1, 3)
+
+    loop._set_dns_cache_ttl(None)
+    assert loop._dns_cache_info() is None
+
+
+def test_getaddrinfo_gaierror_errno(tokio_loop):
+    loop = tokio_loop
+    loop._set_dns_cache_ttl(60)
+
+    # cached error keeps EAI code
+    for _ in range(2):
+        with pytest.raises(socket.gaierror) as excinfo:
+            loop.run_until_complete(
+                loop.getaddrinfo('nonexistent.invalid', 80))
+
+        assert excinfo.value.errno == socket.EAI_NONAME
+        assert excinfo.value.strerror
+
+    assert loop._dns_cache_info() == (1, 1)
+
+
+def test_getaddrinfo_dns_cache_ttl(tokio_loop):
+    loop = tokio_loop
+    loop._set_dns_cache_ttl(0.05)
+
+    loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    loop.run_until_complete(asyncio.sleep(0.1, loop=loop))
+
+    # entry is expired
+    loop.run_until_complete(loop.getaddrinfo('localhost', 80))
+    assert loop._dns_cache_info() == (0, 2)
+
+
+def test_getaddrinfo_dns_cache_negative(tokio_loop):
+    loop = tokio_loop
+    loop._set_dns_cache_ttl(60)
+
+    host = 'a' + '1' * 50 + '.wat'
+    for _ in range(2):
+        with pytest.raises(socket.gaierror):
+            loop.run_until_complete(loop.getaddrinfo(host, 80))
+
+    assert loop._dns_cache_info() == (1, 1)
+
+
+def test_getaddrinfo_dns_cache_size(tokio_loop):
+    loop = tokio_loop
+    loop._set_dns_cache_ttl(60)
+
+    async def resolve(ports):
+        for port in ports:
+            await loop.getaddrinfo('127.0.0.1', port)
+
+    # cache holds 1024 entries, the one which expires first is evicted
+    loop.run_until_complete(resolve(range(1, 1026)))
+    assert loop._dns_cache_info() == (0, 1025)
+
+    loop.run_until_complete(resolve([1025]))
+    assert loop._dns_cache_info() == (1, 1025)
+
+    loop.run_until_complete(resolve([1]))
+    assert loop._dns_cache_info() == (1, 1026)
+
+
+def test_getaddrinfo_slow_logged(tokio_loop, caplog):
+    loop = tokio_loop
+    caplog.set_level(logging.INFO, logger='asyncio')
+
+    loop.run_until_complete(loop.getaddrinfo('127.0.0.1', 80))
+    assert not caplog.records
+
+    # debug mode, any resolution is slow
+    loop.set_debug(True)
+    loop.slow_callback_duration = 0
+    loop.run_until_complete(loop.getaddrinfo('127.0.0.1', 80))
+
+    assert [rec.name for rec in caplog.records] == ['asyncio']
+    assert caplog.records[0].levelno == logging.INFO
+    assert caplog.records[0].getMessage().startswith(
+        'Getting address info 127.0.0.1:80, flags=')
+
+
+def test_getaddrinfo_single_worker():
+    loop = tokio.new_event_loop(resolver_workers=1)
+    try:
+        assert loop._resolver_info() == (1, 0)
+
+        async def runner():
+            return await asyncio.gather(
+                *[loop.getaddrinfo('localhost', 80 + i) for i in range(5)],
+                loop=loop)
+
+        results = loop.run_until_complete(runner())
+        assert len(results) == 5
+        assert all(results)
+        assert loop._resolver_info() == (1, 0)
+
+        with pytest.raises(ValueError):
+            loop._set_resolver_workers(0)
+    finally:
+        loop.close()
+
+    with pytest.raises(ValueError):
+        tokio.new_event_loop(resolver_workers=0)
@@ -5,6 +5,8 @@
 
 import asyncio
 import concurrent.futures
+import threading
+import time
 
 import pytest
 
@@ -33,3 +35,34 @@ def test_executors_process_pool(loop, pool_factory):
 
     fib10 = [fib(i) for i in range(10)]
     loop.run_until_complete(run())
+
+
+def test_executors_process_pool_unpicklable(tokio_loop):
+    tokio_loop.set_debug(True)
+
+    with concurrent.futures.ProcessPoolExecutor() as pool:
+        with pytest.raises(TypeError) as excinfo:
+            tokio_loop.run_in_executor(pool, lambda: 1)
+        excinfo.match('must be picklable')
+
+        res = tokio_loop.run_until_complete(
+            tokio_loop.run_in_executor(pool, fib, 5))
+        assert res == fib(5)
+
+
+def test_executors_default_max_workers(tokio_loop):
+    def run():
+        time.sleep(0.05)
+        return threading.get_ident()
+
+    with pytest.raises(ValueError):
+        tokio_loop.set_default_executor_max_workers(0)
+
+    tokio_loop.set_default_executor_max_workers(2)
+
+    async def main():
+        coros = [tokio_loop.run_in_executor(None, run) for _ in range(6)]
+        return await asyncio.gather(*coros, loop=tokio_loop)
+
+    idents = tokio_loop.run_until_complete(main())
+    assert len(set(idents)) == 2
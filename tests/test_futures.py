@@ -5,9 +5,11 @@
 
 import asyncio
 import concurrent.futures
+import gc
 import re
 import sys
 import threading
+import weakref
 from asyncio import test_utils
 from test import support
 from unittest import mock
@@ -111,6 +113,32 @@ def test_future_exception_class(create_future):
     assert isinstance(f.exception(), RuntimeError)
 
 
+def test_future_exception_traceback(loop, create_future):
+    def fail():
+        try:
+            raise KeyError('key')
+        except KeyError as exc:
+            raise ValueError('value') from exc
+
+    try:
+        fail()
+    except ValueError as exc:
+        error = exc
+
+    f = create_future()
+    f.set_exception(error)
+
+    async def waiter():
+        await f
+
+    with pytest.raises(ValueError) as excinfo:
+        loop.run_until_complete(waiter())
+
+    assert excinfo.value is error
+    assert isinstance(excinfo.value.__cause__, KeyError)
+    assert 'fail' in [entry.name for entry in excinfo.traceback]
+
+
 def test_future_yield_from_twice(create_future):
     f = create_future()
 
@@ -301,6 +329,29 @@ def test_future_source_traceback(loop):
         __file__, lineno, 'test_future_source_traceback')
 
 
+def test_future_source_traceback_debug(tokio_loop):
+    tokio_loop.set_debug(True)
+    fut = tokio_loop.create_future()
+    lineno = sys._getframe().f_lineno - 1
+
+    frame = fut._source_traceback[-1]
+    assert frame[:3] == (
+        __file__, lineno, 'test_future_source_traceback_debug')
+    assert repr(fut) == '<Future pending created at %s:%s>' % (
+        frame[0], frame[1])
+
+    fut.cancel()
+    assert repr(fut) == '<Future cancelled created at %s:%s>' % (
+        frame[0], frame[1])
+
+    tokio_loop.set_debug(False)
+    fut = tokio_loop.create_future()
+    assert fut._source_traceback is None
+    assert repr(fut) == '<Future pending>'
+    fut.cancel()
+    assert repr(fut) == '<Future cancelled>'
+
+
 @pytest.mark.parametrize('debug', [True, False])
 def check_future_exception_never_retrieved(loop, debug, run_briefly):
     last_ctx = None
@@ -398,6 +449,56 @@ def test_future_callbacks_invoked_on_set_result(
     assert f.result() == 'foo'
 
 
+def test_future_callback_adds_callback(
+        loop, create_future, run_briefly):
+    bag = []
+    f = create_future()
+
+    def cb2(fut):
+        bag.append(2)
+
+    def cb1(fut):
+        bag.append(1)
+        fut.add_done_callback(cb2)
+        # re-entrant set is rejected, callbacks are not scheduled again
+        with pytest.raises(asyncio.InvalidStateError):
+            fut.set_result('bar')
+
+    f.add_done_callback(cb1)
+    f.set_result('foo')
+
+    run_briefly(loop)
+    run_briefly(loop)
+
+    assert bag == [1, 2]
+    assert f.result() == 'foo'
+
+
+def test_future_rust_callback_added_during_dispatch(tokio_loop, run_briefly):
+    afut = asyncio.Future(loop=tokio_loop)
+    fut = tokio_loop._wrap_future(afut)
+    dst = tokio_loop.create_future()
+    done = []
+
+    async def waiter():
+        await fut
+        # task is woken up in place by rust callback of wrapped future,
+        # copy_state_to() adds rust callback while callbacks are dispatched
+        fut.copy_state_to(dst)
+        done.append(dst.done())
+
+    task = tokio_loop.create_task(waiter())
+    run_briefly(tokio_loop)
+
+    afut.set_result(1)
+    tokio_loop.run_until_complete(task)
+
+    # callback is deferred to next reactor turn
+    assert done == [False]
+    run_briefly(tokio_loop)
+    assert dst.result() == 1
+
+
 def test_future_callbacks_invoked_on_set_exception(
         loop, create_future, make_callback, run_briefly):
     bag = []
@@ -448,3 +549,172 @@ def test_future_remove_done_callback(
 
     assert bag == [2]
     assert f.result() == 'foo'
+
+
+def test_future_copy_state_to(tokio_loop, run_briefly):
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    src.copy_state_to(dst)
+    src.set_result(10)
+    run_briefly(tokio_loop)
+    assert dst.result() == 10
+
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    src.copy_state_to(dst)
+    src.set_exception(RuntimeError())
+    run_briefly(tokio_loop)
+    with pytest.raises(RuntimeError):
+        dst.result()
+    with pytest.raises(RuntimeError):
+        src.result()
+
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    src.copy_state_to(dst)
+    src.cancel()
+    run_briefly(tokio_loop)
+    assert dst.cancelled()
+
+
+def test_future_copy_state_to_dst_cancelled(tokio_loop, run_briefly):
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    dst.cancel()
+    src.copy_state_to(dst)
+    assert src.cancelled()
+
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    src.copy_state_to(dst)
+    dst.cancel()
+    run_briefly(tokio_loop)
+    assert src.cancelled()
+
+    src = tokio_loop.create_future()
+    dst = tokio_loop.create_future()
+    src.copy_state_to(dst)
+    dst.set_result(1)
+    src.set_result(2)
+    run_briefly(tokio_loop)
+    assert dst.result() == 1
+
+
+def test_future_wrapped_set_result_twice(tokio_loop, run_briefly):
+    afut = asyncio.Future(loop=tokio_loop)
+    fut = tokio_loop._wrap_future(afut)
+
+    fut.set_result(1)
+    with pytest.raises(asyncio.InvalidStateError):
+        fut.set_result(2)
+    with pytest.raises(asyncio.InvalidStateError):
+        fut.set_exception(ValueError())
+
+    run_briefly(tokio_loop)
+    assert fut.result() == 1
+    assert afut.result() == 1
+
+
+def test_future_result_cycle_collectable(create_future):
+    class Obj:
+        pass
+
+    fut = create_future()
+    obj = Obj()
+    obj.fut = fut
+    fut.set_result(obj)
+
+    ref = weakref.ref(obj)
+    del fut, obj
+    gc.collect()
+    assert ref() is None
+
+
+def test_future_exception_cycle_collectable(create_future):
+    class Error(Exception):
+        pass
+
+    fut = create_future()
+    exc = Error()
+    exc.fut = fut
+    fut.set_exception(exc)
+    fut.exception()
+
+    ref = weakref.ref(exc)
+    del fut, exc
+    gc.collect()
+    assert ref() is None
+
+
+def test_future_exception_cycle_never_retrieved(tokio_loop):
+    contexts = []
+    tokio_loop.set_exception_handler(lambda loop, ctx: contexts.append(ctx))
+
+    class Error(Exception):
+        pass
+
+    fut = tokio_loop.create_future()
+    exc = Error()
+    exc.fut = fut
+    fut.set_exception(exc)
+
+    ref = weakref.ref(exc)
+    del fut, exc
+    gc.collect()
+
+    # reported once, after cycle is broken
+    assert len(contexts) == 1
+    assert contexts[0]['message'] == 'Future exception was never retrieved'
+    assert isinstance(contexts[0]['exception'], Error)
+
+    del contexts[:]
+    gc.collect()
+    assert ref() is None
+
+
+def test_future_weakref(create_future):
+    fut = create_future()
+    ref = weakref.ref(fut)
+    assert ref() is fut
+
+    del fut
+    gc.collect()
+    assert ref() is None
+
+
+def test_future_iter_throw_cancel(tokio_loop):
+    fut = tokio_loop.create_future()
+    it = fut.__await__()
+    assert next(it) is fut
+
+    with pytest.raises(asyncio.CancelledError):
+        it.throw(asyncio.CancelledError)
+    assert fut.cancelled()
+
+    # awaiting coroutine
+    async def coro():
+        await fut2
+
+    fut2 = tokio_loop.create_future()
+    c = coro()
+    assert c.send(None) is fut2
+    with pytest.raises(asyncio.CancelledError):
+        c.throw(asyncio.CancelledError())
+    assert fut2.cancelled()
+
+
+def test_future_create_resolved(tokio_loop, run_briefly):
+    loop = tokio_loop
+
+    fut = loop.create_future_resolved(42)
+    assert not fut.done()
+
+    run_briefly(loop)
+    assert fut.done()
+    assert fut.result() == 42
+
+    # cancelled before loop iteration
+    fut = loop.create_future_resolved(42)
+    fut.cancel()
+    run_briefly(loop)
+    assert fut.cancelled()
@@ -0,0 +1,92 @@
+extern crate bytes;
+extern crate tokio_io;
+extern crate async_tokio;
+
+use bytes::{Bytes, BytesMut};
+use tokio_io::codec::Decoder;
+use async_tokio::http::{BodyCollector, Error, RequestDecoder};
+
+
+// decode single request, returns collected body
+fn collect(data: &'static [u8], max_size: usize) -> Result<Bytes, Error> {
+    let mut codec = RequestDecoder::new();
+    let mut buf = BytesMut::from(data);
+    let mut body = BodyCollector::new(max_size);
+
+    loop {
+        match codec.decode(&mut buf)? {
+            Some(msg) => if let Some(bytes) = body.feed(msg)? {
+                return Ok(bytes)
+            },
+            None => panic!("Request is not completed"),
+        }
+    }
+}
+
+#[test]
+fn test_body_length() {
+    let body = collect(
+        b"POST /form HTTP/1.1\r\n\
+          content-length: 5\r\n\r\n\
+          hello", 1024).unwrap();
+    assert_eq!(body, Bytes::from_static(b"hello"));
+}
+
+#[test]
+fn test_body_chunked() {
+    let body = collect(
+        b"POST /chunked HTTP/1.1\r\n\
+          transfer-encoding: chunked\r\n\r\n\
+          5\r\nhello\r\n1\r\n \r\n5\r\nworld\r\n0\r\n\r\n", 1024).unwrap();
+    assert_eq!(body, Bytes::from_static(b"hello world"));
+}
+
+#[test]
+fn test_body_empty() {
+    let body = collect(b"GET / HTTP/1.1\r\n\r\n", 0).unwrap();
+    assert!(body.is_empty());
+}
+
+#[test]
+fn test_body_max_size() {
+    let body = collect(
+        b"POST /form HTTP/1.1\r\n\
+          content-length: 5\r\n\r\n\
+          hello", 5).unwrap();
+    assert_eq!(body, Bytes::from_static(b"hello"));
+
+    match collect(
+        b"POST /chunked HTTP/1.1\r\n\
+          transfer-encoding: chunked\r\n\r\n\
+          5\r\nhello\r\n5\r\nworld\r\n0\r\n\r\n", 8) {
+        Err(Error::PayloadTooLarge) => (),
+        res => panic!("Expected PayloadTooLarge, got {:?}", res),
+    }
+}
+
+#[test]
+fn test_body_upgrade_remaining() {
+    let mut codec = RequestDecoder::new();
+    let mut buf = BytesMut::from(
+        &b"CONNECT example.com:443 HTTP/1.1\r\n\r\ntunnel data"[..]);
+    let mut body = BodyCollector::new(1024);
+
+    assert_eq!(body.feed(codec.decode(&mut buf).unwrap().unwrap()).unwrap(), None);
+    let res = body.feed(codec.decode(&mut buf).unwrap().unwrap()).unwrap();
+    assert_eq!(res, Some(Bytes::new()));
+    assert_eq!(body.take_remaining(), Some(Bytes::from_static(b"tunnel data")));
+    assert_eq!(body.take_remaining(), None);
+}
+
+#[test]
+fn test_body_collector_reuse() {
+    let mut body = BodyCollector::new(1024);
+    body.push(Bytes::from_static(b"first")).unwrap();
+    body.push(Bytes::from_static(b" body")).unwrap();
+    assert_eq!(body.len(), 10);
+    assert_eq!(body.take(), Bytes::from_static(b"first body"));
+
+    assert_eq!(body.len(), 0);
+    body.push(Bytes::from_static(b"second")).unwrap();
+    assert_eq!(body.take(), Bytes::from_static(b"second"));
+}
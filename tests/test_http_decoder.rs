@@ -5,7 +5,8 @@ extern crate async_tokio;
 use bytes::BytesMut;
 use tokio_io::codec::{Decoder};
 use async_tokio::http::{
-    ConnectionType, ContentCompression, Error, RequestDecoder, RequestMessage, Version};
+    ConnectionType, ContentCompression, Error, Method, RequestDecoder, RequestDecoderBuilder,
+    RequestMessage, TargetForm, UpgradeKind, Version};
 
 macro_rules! test {
     ($name:ident, $($data:expr),+ => |$codec:ident, $buf:ident| $body:expr) => (
@@ -161,6 +162,20 @@ macro_rules! expect_eof_error {
     }
 }
 
+macro_rules! expect_upgrade {
+    ($codec:ident($buf:ident) => $proto:expr, $remaining:expr) => {
+        match $codec.decode(&mut $buf) {
+            Err(err) => assert!(false, format!("Got error: {:?}", err)),
+            Ok(None) => assert!(false, "Did not get any result"),
+            Ok(Some(RequestMessage::Upgrade { protocol, remaining })) => {
+                assert_eq!(protocol, $proto);
+                assert_eq!(remaining.as_ref(), $remaining);
+            },
+            Ok(Some(msg)) => assert!(false, "RequestMessage::Upgrade is expected, got {:?}", msg),
+        }
+    }
+}
+
 macro_rules! expect_eof_none {
     ($codec:ident($buf:ident)) => {
         match $codec.decode_eof(&mut $buf) {
@@ -173,7 +188,7 @@ macro_rules! expect_eof_none {
 
 macro_rules! expect_eof_completed {
     ($codec:ident($buf:ident)) => {
-        match $codec.decode(&mut $buf) {
+        match $codec.decode_eof(&mut $buf) {
             Err(err) => assert!(false, format!("Got error: {:?}", err)),
             Ok(None) => assert!(false, "Did not get any result"),
             Ok(Some(RequestMessage::Completed)) => (),
@@ -214,6 +229,7 @@ test! { test_parse_body,
             expect_status!(msg => codec(buf) => "GET", "/test", Version::Http11);
             expect_headers!(msg => conn:ConnectionType::KeepAlive, chunked:false,
                             ("Content-Length", "4"));
+            assert_eq!(msg.content_length, Some(4));
             expect_body!(codec(buf): "body");
             expect_completed!(codec(buf));
         }}
@@ -356,13 +372,13 @@ test! { test_request_chunked,
             expect_status!(msg => codec(buf) => "GET", "/test", Version::Http11);
             expect_headers!(msg => conn:ConnectionType::KeepAlive, chunked:true,
                             ("transfer-encoding", "chunked"));
+            assert_eq!(msg.content_length, None);
         }}
 
 test! { test_request_chunked_partial,
         "GET /test HTTP/1.1\r\n",
         "transfer-encoding: chunk\r\n\r\n" => |codec, buf| {
-            expect_status!(msg => codec(buf) => "GET", "/test", Version::Http11);
-            expect_headers!(msg => chunked:false, ("transfer-encoding", "chunk"));
+            expect_error!(codec(buf): Error::TransferEncoding);
         }}
 
 test! { test_special_headers_partial,
@@ -452,6 +468,39 @@ test! {test_headers_content_length_err_2,
            expect_error!(codec(buf): Error::ContentLength);
        }}
 
+test! {test_headers_content_length_plus,
+       "GET /test HTTP/1.1\r\n",
+       "content-length: +10\r\n\r\n" => |codec, buf| {
+           expect_error!(codec(buf): Error::ContentLength);
+       }}
+
+test! {test_headers_content_length_leading_zero,
+       "GET /test HTTP/1.1\r\n",
+       "content-length: 010\r\n\r\n" => |codec, buf| {
+           expect_error!(codec(buf): Error::ContentLength);
+       }}
+
+test! {test_headers_content_length_zero,
+       "GET /test HTTP/1.1\r\n",
+       "content-length: 0\r\n\r\n" => |codec, buf| {
+           expect_status!(msg => codec(buf) => "GET", "/test", Version::Http11);
+           assert_eq!(msg.content_length, Some(0));
+           expect_completed!(codec(buf));
+       }}
+
+test! {test_headers_content_length_overflow,
+       "GET /test HTTP/1.1\r\n",
+       "content-length: 18446744073709551616\r\n\r\n" => |codec, buf| {
+           expect_error!(codec(buf): Error::ContentLength);
+       }}
+
+test! {test_headers_content_length_max,
+       "GET /test HTTP/1.1\r\n",
+       "content-length: 1025\r\n\r\n" => |codec, buf| {
+           codec.set_max_content_length(1024);
+           expect_error!(codec(buf): Error::ContentLength);
+       }}
+
 test! {test_invalid_header,
       "GET /test HTTP/1.1\r\n",
       "test line\r\n\r\n" => |codec, buf| {
@@ -464,6 +513,30 @@ test! { test_invalid_name,
             expect_error!(codec(buf): Error::BadHeader);
         }}
 
+test! { test_header_value_nul,
+        "GET /test HTTP/1.1\r\n",
+        "test: li\0ne\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadHeader);
+        }}
+
+test! { test_header_value_cr,
+        "GET /test HTTP/1.1\r\n",
+        "test: li\rne\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadHeader);
+        }}
+
+test! { test_header_value_ctl,
+        "GET /test HTTP/1.1\r\n",
+        "test: li\x7fne\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadHeader);
+        }}
+
+test! { test_header_value_bare_lf,
+        "GET /test HTTP/1.1\r\n",
+        "test: li\nne\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadHeader);
+        }}
+
 test! { test_max_header_name_size,
         "GET /test HTTP/1.1\r\n" => |codec, buf| {
             buf.extend([b't'; 10 * 1024][..].as_ref());
@@ -577,6 +650,48 @@ test! { test_http_request_chunked_payload_and_next_message,
                             ("transfer-encoding", "chunked"));
         }}
 
+test! { test_request_te_gzip_chunked,
+        "POST /test HTTP/1.1\r\n",
+        "transfer-encoding: gzip, chunked\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/test", Version::Http11);
+            expect_headers!(msg => chunked:true, ("transfer-encoding", "gzip, chunked"));
+            assert_eq!(msg.transfer_codings, vec!["gzip".to_owned()]);
+
+            buf.extend(b"4\r\ndata\r\n0\r\n\r\n");
+            expect_body!(codec(buf): "data");
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_te_multiple_headers,
+        "POST /test HTTP/1.1\r\n",
+        "transfer-encoding: Deflate\r\n",
+        "transfer-encoding: gzip;q=1 , chunked\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/test", Version::Http11);
+            assert!(msg.chunked);
+            assert_eq!(msg.transfer_codings, vec!["deflate".to_owned(), "gzip".to_owned()]);
+        }}
+
+test! { test_request_te_chunked_gzip,
+        "POST /test HTTP/1.1\r\n",
+        "transfer-encoding: chunked, gzip\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::TransferEncoding);
+        }}
+
+test! { test_request_te_not_chunked,
+        "POST / HTTP/1.1\r\n",
+        "transfer-encoding: gzip\r\n",
+        "content-length: 5\r\n\r\n",
+        "helloGET /smuggled HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::TransferEncoding);
+        }}
+
+test! { test_request_te_chunked_twice,
+        "POST /test HTTP/1.1\r\n",
+        "transfer-encoding: chunked\r\n",
+        "transfer-encoding: chunked\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::TransferEncoding);
+        }}
+
 test! { test_http_request_chunked_payload_chunks,
         "GET /test HTTP/1.1\r\n",
         "transfer-encoding: chunked\r\n\r\n" => |codec, buf| {
@@ -659,6 +774,41 @@ test! { test_parse_length_payload_eof,
             expect_eof_error!(codec(buf): Error::PayloadNotCompleted);
         }}
 
+test! { test_parse_unsized_payload,
+        "PUT / HTTP/1.0\r\n\r\n" => |codec, buf| {
+            codec.set_unsized_payload(true);
+            expect_status!(msg => codec(buf) => "PUT", "/", Version::Http10);
+            assert_eq!(msg.content_length, None);
+
+            buf.extend(b"da");
+            expect_body!(codec(buf): "da");
+            expect_none!(codec(buf));
+
+            // connection closed in the middle of body
+            buf.extend(b"ta");
+            expect_eof_body!(codec(buf): "ta");
+            expect_eof_completed!(codec(buf));
+            expect_eof_none!(codec(buf));
+        }}
+
+test! { test_parse_unsized_payload_keep_alive,
+        "PUT / HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_unsized_payload(true);
+            expect_status!(msg => codec(buf) => "PUT", "/", Version::Http11);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_parse_headers_eof,
+        "PUT / HTTP/1.1\r\n",
+        "Content-Len" => |codec, buf| {
+            expect_eof_error!(codec(buf): Error::HeadersNotCompleted);
+        }}
+
+test! { test_parse_status_line_eof,
+        "PUT / HT" => |codec, buf| {
+            expect_eof_error!(codec(buf): Error::HeadersNotCompleted);
+        }}
+
 test! { test_parse_chunked_payload_size_error,
         "PUT / HTTP/1.1\r\n",
         "transfer-encoding: chunked\r\n\r\n",
@@ -681,6 +831,29 @@ test! { test_http_payload_parser_length,
         }}
 
 
+test! { test_request_expect_continue,
+        "POST /upload HTTP/1.1\r\n",
+        "Expect: 100-Continue\r\n",
+        "content-length: 4\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/upload", Version::Http11);
+            expect_headers!(msg => ("expect", "100-Continue"));
+            assert!(msg.expect_continue);
+        }}
+
+test! { test_request_expect_other,
+        "POST /upload HTTP/1.1\r\n",
+        "expect: 100-continued\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/upload", Version::Http11);
+            assert!(!msg.expect_continue);
+        }}
+
+test! { test_request_expect_continue_http10,
+        "POST /upload HTTP/1.0\r\n",
+        "expect: 100-continue\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/upload", Version::Http10);
+            assert!(!msg.expect_continue);
+        }}
+
 test! { test_http_request_websocket,
         "GET /path HTTP/1.1\r\n",
         "Upgrade: Websocket\r\n\r\n" => |codec, buf| {
@@ -691,6 +864,44 @@ test! { test_http_request_websocket,
             expect_completed!(codec(buf));
         }}
 
+test! { test_websocket_upgrade,
+        "GET /chat HTTP/1.1\r\n",
+        "Host: server.example.com\r\n",
+        "Upgrade: websocket\r\n",
+        "Connection: keep-alive, Upgrade\r\n",
+        "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+        "Origin: http://example.com\r\n",
+        "Sec-WebSocket-Protocol: chat, superchat\r\n",
+        "Sec-WebSocket-Version: 13\r\n\r\n",
+        "websocket frames" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/chat", Version::Http11);
+            assert!(msg.websocket);
+            assert!(codec.is_websocket_upgrade());
+            expect_upgrade!(codec(buf) => UpgradeKind::WebSocket, b"websocket frames");
+            assert!(codec.is_upgraded());
+
+            // decoder is stopped
+            buf.extend(b"GET / HTTP/1.1\r\n\r\n");
+            expect_none!(codec(buf));
+
+            codec.reset();
+            assert!(!codec.is_upgraded());
+            assert!(!codec.is_websocket_upgrade());
+        }}
+
+test! { test_websocket_upgrade_without_key,
+        "GET /chat HTTP/1.1\r\n",
+        "Upgrade: websocket\r\n",
+        "Connection: Upgrade\r\n\r\n",
+        "GET /next HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/chat", Version::Http11);
+            assert!(codec.is_upgrade());
+            assert!(!codec.is_websocket_upgrade());
+            expect_completed!(codec(buf));
+            assert!(!codec.is_upgraded());
+            expect_status!(msg => codec(buf) => "GET", "/next", Version::Http11);
+        }}
+
 //_comp = zlib.compressobj(wbits=-zlib.MAX_WBITS)
 //_COMPRESSED = b''.join([_comp.compress(b'data'), _comp.flush()])
 
@@ -702,3 +913,538 @@ test! { test_http_request_websocket,
 //p.feed_data(self._COMPRESSED)
 //self.assertEqual(b'data', b''.join(d for d, _ in out._buffer))
 //self.assertTrue(out.is_eof())
+
+test! { test_request_target_origin_form,
+        "GET /path?query HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/path?query", Version::Http11);
+            assert_eq!(msg.target_form(), TargetForm::Origin);
+            assert_eq!(msg.authority(), None);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_target_absolute_form,
+        "GET http://example.com:8080/path HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) =>
+                           "GET", "http://example.com:8080/path", Version::Http11);
+            assert_eq!(msg.target_form(), TargetForm::Absolute);
+            assert_eq!(msg.authority(), Some("example.com:8080"));
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_target_authority_form,
+        "CONNECT example.com:443 HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "CONNECT", "example.com:443", Version::Http11);
+            assert_eq!(msg.target_form(), TargetForm::Authority);
+            assert_eq!(msg.authority(), Some("example.com:443"));
+            expect_upgrade!(codec(buf) => UpgradeKind::Connect, b"");
+        }}
+
+test! { test_connect_remaining,
+        "CONNECT example.com:443 HTTP/1.1\r\n",
+        "Host: example.com:443\r\n",
+        "Content-Length: 3\r\n\r\n",
+        "\x16\x03\x01tunnel data" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "CONNECT", "example.com:443", Version::Http11);
+            expect_upgrade!(codec(buf) => UpgradeKind::Connect, b"\x16\x03\x01tunnel data");
+            assert!(codec.is_upgraded());
+            assert!(buf.is_empty());
+
+            buf.extend(b"more tunnel data");
+            expect_none!(codec(buf));
+            expect_eof_none!(codec(buf));
+        }}
+
+test! { test_request_target_asterisk_form,
+        "OPTIONS * HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "OPTIONS", "*", Version::Http11);
+            assert_eq!(msg.target_form(), TargetForm::Asterisk);
+            assert_eq!(msg.authority(), None);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_target_connect_origin_form,
+        "CONNECT /path HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_request_target_authority_form_not_connect,
+        "GET example.com:443 HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_request_target_asterisk_form_not_options,
+        "GET * HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_request_line_double_space,
+        "GET  /  HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_request_line_ws(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_line_tab,
+        "GET\t/path\tHTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_request_line_ws(true);
+            expect_status!(msg => codec(buf) => "GET", "/path", Version::Http11);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_request_line_double_space_default,
+        "GET  /  HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_strict_request_line_double_space,
+        "GET  /  HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_strict_request_line_tab,
+        "GET\t/path\tHTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BadStatusLine);
+        }}
+
+test! { test_strict_bare_lf,
+        "GET / HTTP/1.1\nHost: x\n\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_bare_lf_prefix,
+        "\nGET / HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_bare_lf_header_value,
+        "GET / HTTP/1.1\r\n",
+        "Host: x\nTest: y\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_bare_lf_headers_end,
+        "GET / HTTP/1.1\r\n",
+        "Host: x\r\n\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_bare_lf_chunk_size,
+        "GET / HTTP/1.1\r\n",
+        "transfer-encoding: chunked\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            buf.extend(b"4\ndata\r\n0\r\n\r\n");
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_bare_lf_chunk_data,
+        "GET / HTTP/1.1\r\n",
+        "transfer-encoding: chunked\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            buf.extend(b"4\r\ndata\n0\r\n\r\n");
+            expect_body!(codec(buf): "data");
+            expect_error!(codec(buf): Error::BareLf);
+        }}
+
+test! { test_strict_crlf,
+        "GET / HTTP/1.1\r\n",
+        "Host: x\r\n\r\n" => |codec, buf| {
+            codec.set_strict(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            expect_headers!(msg => ("Host", "x"));
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_headers_obs_text,
+        "GET / HTTP/1.1\r\n" => |codec, buf| {
+            buf.extend(b"Test: caf\xe9\r\nName: value\r\n\r\n");
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            expect_headers!(msg => ("Name", "value"));
+            assert_eq!(msg.headers.get("test"), None);
+            assert_eq!(msg.headers.get_bytes("test"), Some(&b"caf\xe9"[..]));
+            assert_eq!(msg.headers.get_case_bytes("test"), Some(&b"caf\xe9"[..]));
+            assert_eq!(msg.headers.name_bytes("TEST"), Some(&b"Test"[..]));
+            assert!(msg.headers.headers().contains(
+                &(String::from("Test"), String::from("caf\u{fffd}"))));
+        }}
+
+test! { test_codec_http10_close,
+        "GET / HTTP/1.0\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http10);
+            assert!(!codec.is_keep_alive());
+            assert!(!codec.is_upgrade());
+            assert!(!codec.is_chunked());
+            assert_eq!(codec.content_length(), None);
+            expect_completed!(codec(buf));
+            assert!(!codec.is_keep_alive());
+        }}
+
+test! { test_codec_http11_connection_close,
+        "POST / HTTP/1.1\r\n",
+        "connection: close\r\n",
+        "content-length: 4\r\n\r\n",
+        "data" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/", Version::Http11);
+            assert!(!codec.is_keep_alive());
+            assert!(!codec.is_chunked());
+            assert_eq!(codec.content_length(), Some(4));
+            expect_body!(codec(buf): "data");
+            expect_completed!(codec(buf));
+            assert!(!codec.is_keep_alive());
+            assert_eq!(codec.content_length(), None);
+        }}
+
+test! { test_codec_http11_keep_alive_chunked,
+        "POST / HTTP/1.1\r\n",
+        "transfer-encoding: chunked\r\n\r\n",
+        "4\r\ndata\r\n0\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/", Version::Http11);
+            assert!(codec.is_keep_alive());
+            assert!(codec.is_chunked());
+            assert_eq!(codec.content_length(), None);
+            expect_body!(codec(buf): "data");
+            expect_completed!(codec(buf));
+            assert!(codec.is_keep_alive());
+            assert!(!codec.is_chunked());
+        }}
+
+test! { test_method_too_long,
+        "GETGETGETGETGETGETGETGETGETGETGET / HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::MethodTooLong);
+        }}
+
+test! { test_method_too_long_partial,
+        "GETGETGETGETGETGETGETGETGETGETGET" => |codec, buf| {
+            expect_error!(codec(buf): Error::MethodTooLong);
+        }}
+
+test! { test_method_max_size,
+        "PROPFIND / HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_max_method_size(4);
+            expect_error!(codec(buf): Error::MethodTooLong);
+        }}
+
+test! { test_uri_too_long,
+        "GET /0123456789 HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_max_uri_size(10);
+            expect_error!(codec(buf): Error::UriTooLong);
+        }}
+
+test! { test_uri_too_long_partial,
+        "GET /0123456789" => |codec, buf| {
+            codec.set_max_uri_size(10);
+            expect_error!(codec(buf): Error::UriTooLong);
+        }}
+
+test! { test_uri_max_size,
+        "GET /012345678 HTTP/1.1\r\n\r\n" => |codec, buf| {
+            codec.set_max_uri_size(10);
+            expect_status!(msg => codec(buf) => "GET", "/012345678", Version::Http11);
+        }}
+
+test! { test_headers_get_all,
+        "GET / HTTP/1.1\r\n",
+        "Set-Cookie: a=1\r\n",
+        "Host: x\r\n",
+        "set-cookie: b=2\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            let cookies: Vec<&str> = msg.headers.get_all("Set-Cookie").collect();
+            assert_eq!(cookies, vec!["a=1", "b=2"]);
+            assert_eq!(msg.headers.get_all("SET-COOKIE").count(), 2);
+            assert_eq!(msg.headers.get_all("host").collect::<Vec<_>>(), vec!["x"]);
+            assert_eq!(msg.headers.get_all("cookie").count(), 0);
+            assert_eq!(msg.headers.headers().len(), 3);
+        }}
+
+test! { test_headers_contains,
+        "GET / HTTP/1.1\r\n",
+        "Content-Type: text/plain\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            assert!(msg.headers.contains("content-type"));
+            assert!(msg.headers.contains("Content-Type"));
+            assert!(!msg.headers.contains("content-length"));
+            assert_eq!(msg.headers.get("content-type"), Some("text/plain"));
+            assert_eq!(msg.headers.get("CONTENT-TYPE"), Some("text/plain"));
+        }}
+
+test! { test_codec_reset,
+        "GET /first HTTP/1.1\r\n",
+        "host: example.com\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/first", Version::Http11);
+            expect_completed!(codec(buf));
+            codec.reset();
+
+            buf.extend(b"GET /second HTTP/1.1\r\nconnection: close\r\n\r\n");
+            expect_status!(msg => codec(buf) => "GET", "/second", Version::Http11);
+            expect_headers!(msg => conn:ConnectionType::Close);
+            assert_eq!(msg.headers.get("host"), None);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_codec_reset_partial,
+        "POST /first HTTP/1.1\r\n",
+        "content-length: 4\r\n",
+        "transfer-enc" => |codec, buf| {
+            expect_none!(codec(buf));
+
+            // connection is broken, parse next request from new buffer
+            codec.reset();
+            let mut buf = BytesMut::from("GET /second HTTP/1.1\r\nhost: a\r\n\r\n");
+            expect_status!(msg => codec(buf) => "GET", "/second", Version::Http11);
+            expect_headers!(msg => ("host", "a"));
+            assert_eq!(msg.headers.get("content-length"), None);
+            assert_eq!(codec.content_length(), None);
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_host,
+        "GET / HTTP/1.1\r\n",
+        "Hostname: other\r\n",
+        "Host: example.com:8080\r\n\r\n" => |codec, buf| {
+            codec.set_require_host(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            assert_eq!(msg.host(), Some("example.com:8080"));
+            assert_eq!(msg.host_bytes(), Some(&b"example.com:8080"[..]));
+            expect_completed!(codec(buf));
+        }}
+
+test! { test_host_missing,
+        "GET / HTTP/1.1\r\n\r\n" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+            assert_eq!(msg.host(), None);
+            expect_completed!(codec(buf));
+
+            buf.extend(b"GET / HTTP/1.1\r\n\r\n");
+            codec.set_require_host(true);
+            expect_error!(codec(buf): Error::MissingHost);
+        }}
+
+test! { test_host_missing_http10,
+        "GET / HTTP/1.0\r\n\r\n" => |codec, buf| {
+            codec.set_require_host(true);
+            expect_status!(msg => codec(buf) => "GET", "/", Version::Http10);
+            assert_eq!(msg.host(), None);
+        }}
+
+test! { test_host_duplicate,
+        "GET / HTTP/1.1\r\n",
+        "Host: example.com\r\n",
+        "host: evil.com\r\n\r\n" => |codec, buf| {
+            expect_error!(codec(buf): Error::DuplicateHost);
+        }}
+
+#[test]
+fn test_host_large_headers() {
+    // header positions do not fit u16, Host value would be truncated
+    let mut codec = RequestDecoder::new();
+    let mut buf = BytesMut::from("GET / HTTP/1.1\r\n");
+    for idx in 0..10 {
+        buf.extend(format!("x-header-{}: {}\r\n", idx, "x".repeat(7000)).as_bytes());
+    }
+    buf.extend(b"Host: example.com\r\n\r\n");
+    expect_error!(codec(buf): Error::LineTooLong);
+}
+
+test! { test_codec_pipelined,
+        "POST /first HTTP/1.1\r\n",
+        "content-length: 4\r\n\r\n",
+        "data",
+        "GET /second HTTP/1.1\r\n\r\n",
+        "POST /third HTTP/1.1\r\n",
+        "transfer-encoding: chunked\r\n\r\n",
+        "5\r\nchunk\r\n0\r\n\r\n",
+        "PUT /fourth HTTP/1.1\r\n",
+        "host: example.com\r\n",
+        "content-length: 3\r\n\r\n",
+        "end" => |codec, buf| {
+            expect_status!(msg => codec(buf) => "POST", "/first", Version::Http11);
+            expect_headers!(msg => ("content-length", "4"));
+            expect_body!(codec(buf): "data");
+            expect_completed!(codec(buf));
+
+            expect_status!(msg => codec(buf) => "GET", "/second", Version::Http11);
+            assert_eq!(msg.headers.get("content-length"), None);
+            assert_eq!(msg.content_length, None);
+            assert_eq!(codec.content_length(), None);
+            expect_completed!(codec(buf));
+
+            expect_status!(msg => codec(buf) => "POST", "/third", Version::Http11);
+            expect_headers!(msg => chunked:true, ("transfer-encoding", "chunked"));
+            assert_eq!(msg.headers.get("content-length"), None);
+            expect_body!(codec(buf): "chunk");
+            expect_completed!(codec(buf));
+
+            expect_status!(msg => codec(buf) => "PUT", "/fourth", Version::Http11);
+            expect_headers!(msg => ("host", "example.com"), ("content-length", "3"));
+            assert_eq!(msg.headers.get("transfer-encoding"), None);
+            expect_body!(codec(buf): "end");
+            expect_completed!(codec(buf));
+
+            assert!(buf.is_empty());
+            expect_none!(codec(buf));
+        }}
+
+// RFC 7230, tchar
+fn is_tchar(ch: u8) -> bool {
+    (ch >= b'0' && ch <= b'9') || (ch >= b'a' && ch <= b'z') || (ch >= b'A' && ch <= b'Z') ||
+        b"!#$%&'*+-.^_`|~".contains(&ch)
+}
+
+// RFC 7230, field-vchar / obs-text / OWS
+fn is_field_char(ch: u8) -> bool {
+    (ch >= 0x21 && ch <= 0x7e) || ch >= 0x80 || ch == b' ' || ch == b'\t'
+}
+
+fn parse_head(head: &[u8]) -> bool {
+    let mut codec = RequestDecoder::new();
+    let mut buf = BytesMut::from(&b"GET /test HTTP/1.1\r\n"[..]);
+    buf.extend(head);
+
+    match codec.decode(&mut buf) {
+        Ok(Some(RequestMessage::Message(..))) => true,
+        Ok(Some(msg)) => panic!(format!("Unexpected message: {:?}", msg)),
+        Ok(None) => panic!("Did not get any result"),
+        Err(..) => false,
+    }
+}
+
+#[test]
+fn test_header_name_all_bytes() {
+    for ch in 0..256 {
+        let ch = ch as u8;
+        if ch == b':' {
+            // name delimiter
+            continue
+        }
+        let mut head = vec![b'a', ch, b'z'];
+        head.extend_from_slice(b": value\r\n\r\n");
+        assert_eq!(parse_head(&head), is_tchar(ch), "header name byte: {}", ch);
+    }
+}
+
+#[test]
+fn test_header_value_all_bytes() {
+    for ch in 0..256 {
+        let ch = ch as u8;
+        let mut head = b"test: a".to_vec();
+        head.push(ch);
+        head.extend_from_slice(b"z\r\n\r\n");
+        assert_eq!(parse_head(&head), is_field_char(ch), "header value byte: {}", ch);
+    }
+}
+
+#[test]
+fn test_method_kind() {
+    let methods = [
+        ("GET", Method::Get),
+        ("POST", Method::Post),
+        ("PUT", Method::Put),
+        ("DELETE", Method::Delete),
+        ("HEAD", Method::Head),
+        ("OPTIONS", Method::Options),
+        ("PATCH", Method::Patch),
+        ("TRACE", Method::Trace),
+        ("PROPFIND", Method::Other("PROPFIND")),
+        // method is case-sensitive
+        ("get", Method::Other("get")),
+    ];
+    for &(name, kind) in methods.iter() {
+        let mut codec = RequestDecoder::new();
+        let mut buf = BytesMut::from(format!("{} /path HTTP/1.1\r\n\r\n", name));
+        expect_status!(msg => codec(buf) => name, "/path", Version::Http11);
+        assert_eq!(msg.method_kind(), kind);
+    }
+
+    // CONNECT requires authority-form target
+    let mut codec = RequestDecoder::new();
+    let mut buf = BytesMut::from("CONNECT example.com:443 HTTP/1.1\r\n\r\n");
+    expect_status!(msg => codec(buf) => "CONNECT", "example.com:443", Version::Http11);
+    assert_eq!(msg.method_kind(), Method::Connect);
+}
+
+// chunk-size line terminated by bare LF, front-end and back-end
+// could disagree on where chunk data starts
+const SMUGGLING: &'static str =
+    "POST /path HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n\
+     4\ndata\r\n0\r\n\r\n";
+
+// extra request line whitespace, no Host header
+const MALFORMED: &'static str = "GET  /path  HTTP/1.1\r\n\r\n";
+
+#[test]
+fn test_builder_strict() {
+    let mut codec = RequestDecoderBuilder::new().strict().build();
+    let mut buf = BytesMut::from(SMUGGLING);
+    expect_status!(msg => codec(buf) => "POST", "/path", Version::Http11);
+    expect_error!(codec(buf): Error::BareLf);
+
+    let mut codec = RequestDecoderBuilder::new().strict().build();
+    let mut buf = BytesMut::from(MALFORMED);
+    expect_error!(codec(buf): Error::BadStatusLine);
+
+    let mut codec = RequestDecoderBuilder::new().strict().build();
+    let mut buf = BytesMut::from("GET /path HTTP/1.1\r\n\r\n");
+    expect_error!(codec(buf): Error::MissingHost);
+}
+
+#[test]
+fn test_builder_lenient() {
+    let mut codec = RequestDecoderBuilder::new().strict().lenient().build();
+    let mut buf = BytesMut::from(MALFORMED);
+    expect_status!(msg => codec(buf) => "GET", "/path", Version::Http11);
+    expect_completed!(codec(buf));
+
+    let mut buf = BytesMut::from("GET /path HTTP/1.1\r\n\r\n");
+    expect_status!(msg => codec(buf) => "GET", "/path", Version::Http11);
+    expect_completed!(codec(buf));
+
+    // chunked framing is strict in lenient mode too
+    let mut buf = BytesMut::from(SMUGGLING);
+    expect_status!(msg => codec(buf) => "POST", "/path", Version::Http11);
+    expect_error!(codec(buf): Error::TransferEncoding);
+
+    let mut codec = RequestDecoderBuilder::new().lenient().build();
+    let mut buf = BytesMut::from(
+        "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4;a=b\ndata\r\n0\r\n\r\n");
+    expect_status!(msg => codec(buf) => "POST", "/", Version::Http11);
+    expect_error!(codec(buf): Error::TransferEncoding);
+}
+
+#[test]
+fn test_builder_limits() {
+    let mut codec = RequestDecoderBuilder::new().max_headers(2).build();
+    let mut buf = BytesMut::from("GET / HTTP/1.1\r\na: 1\r\nb: 2\r\n\r\n");
+    expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+    expect_completed!(codec(buf));
+
+    buf.extend(b"GET / HTTP/1.1\r\na: 1\r\nb: 2\r\nc: 3\r\n\r\n");
+    expect_error!(codec(buf): Error::TooManyHeaders);
+
+    let mut codec = RequestDecoderBuilder::new().max_line_size(16).build();
+    let mut buf = BytesMut::from("GET /long/path/to/resource HTTP/1.1\r\n\r\n");
+    expect_error!(codec(buf): Error::LineTooLong);
+
+    let mut codec = RequestDecoderBuilder::new().max_content_length(4).build();
+    let mut buf = BytesMut::from("POST / HTTP/1.1\r\ncontent-length: 5\r\n\r\n");
+    expect_error!(codec(buf): Error::ContentLength);
+}
+
+#[test]
+fn test_builder_record_compress() {
+    let mut codec = RequestDecoderBuilder::new().build();
+    let mut buf = BytesMut::from("GET / HTTP/1.1\r\ncontent-encoding: gzip\r\n\r\n");
+    expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+    expect_headers!(msg => compress:ContentCompression::Gzip);
+
+    let mut codec = RequestDecoderBuilder::new().record_compress(false).build();
+    let mut buf = BytesMut::from("GET / HTTP/1.1\r\ncontent-encoding: gzip\r\n\r\n");
+    expect_status!(msg => codec(buf) => "GET", "/", Version::Http11);
+    expect_headers!(msg => compress:ContentCompression::Default);
+}
@@ -0,0 +1,65 @@
+import pytest
+
+import tokio
+
+
+def test_feed_data_in_chunks():
+    parser = tokio.new_request_parser()
+
+    events = parser.feed_data(
+        b'POST /test?q=1 HTTP/1.1\r\n'
+        b'Host: example.com\r\n'
+        b'X-Test: 1\r\n')
+    assert events == []
+
+    events = parser.feed_data(
+        b'x-test: 2\r\n'
+        b'content-length: 4\r\n\r\n'
+        b'data')
+    assert events == [
+        ('message', 'POST', '/test?q=1', (1, 1),
+         {'host': 'example.com',
+          'x-test': '1, 2',
+          'content-length': '4'}),
+        ('body', b'data'),
+        ('completed',),
+    ]
+
+
+def test_feed_data_keep_alive():
+    parser = tokio.new_request_parser()
+
+    events = parser.feed_data(
+        b'GET /first HTTP/1.0\r\nconnection: keep-alive\r\n\r\n'
+        b'GET /second HTTP/1.1\r\n\r\nGET /thi')
+    assert events == [
+        ('message', 'GET', '/first', (1, 0), {'connection': 'keep-alive'}),
+        ('completed',),
+        ('message', 'GET', '/second', (1, 1), {}),
+        ('completed',),
+    ]
+
+    events = parser.feed_data(b'rd HTTP/1.1\r\n\r\n')
+    assert events == [
+        ('message', 'GET', '/third', (1, 1), {}),
+        ('completed',),
+    ]
+
+
+def test_feed_data_error():
+    parser = tokio.new_request_parser()
+
+    with pytest.raises(ValueError):
+        parser.feed_data(b'GET /test HTTP/1.1\r\ntest[]: line\r\n\r\n')
+
+
+def test_feed_data_upgrade():
+    parser = tokio.new_request_parser()
+
+    events = parser.feed_data(
+        b'CONNECT example.com:443 HTTP/1.1\r\n\r\ntunnel')
+    assert events == [
+        ('message', 'CONNECT', 'example.com:443', (1, 1), {}),
+        ('upgrade', 'connect', b'tunnel'),
+    ]
+    assert parser.feed_data(b'GET / HTTP/1.1\r\n\r\n') == []
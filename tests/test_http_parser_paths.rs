@@ -0,0 +1,140 @@
+//! Unchecked and bounds checked parser paths must produce same events
+//! for same input, see `RequestDecoder::set_checked`.
+//!
+//! With `safe_parser` feature both decoders use checked path, which allows
+//! to run parser under Miri, i.e. `cargo +nightly miri test --features safe_parser`
+extern crate bytes;
+extern crate tokio_io;
+extern crate async_tokio;
+
+use bytes::BytesMut;
+use tokio_io::codec::Decoder;
+use async_tokio::http::{RequestDecoder, RequestDecoderBuilder, RequestMessage};
+
+
+const REQUESTS: &'static [u8] =
+    b"GET /index.html HTTP/1.1\r\n\
+      host: example.com\r\n\r\n\
+      POST /form HTTP/1.1\r\n\
+      content-length: 5\r\n\r\n\
+      hello\
+      PUT /chunked HTTP/1.1\r\n\
+      transfer-encoding: chunked\r\n\r\n\
+      5\r\nworld\r\n3;ext=1\r\n!!!\r\n0\r\n\r\n\
+      GET /last HTTP/1.1\r\n\r\n\
+      GET /bad HTTP/1.1\r\n\
+      content-length: 01\r\n\r\n";
+
+fn expected() -> Vec<String> {
+    vec![
+        r#"GET /index.html Http11 [("host", "example.com")]"#.to_owned(),
+        r#"completed """#.to_owned(),
+        r#"POST /form Http11 [("content-length", "5")]"#.to_owned(),
+        r#"completed "hello""#.to_owned(),
+        r#"PUT /chunked Http11 [("transfer-encoding", "chunked")]"#.to_owned(),
+        r#"completed "world!!!""#.to_owned(),
+        r#"GET /last Http11 []"#.to_owned(),
+        r#"completed """#.to_owned(),
+        r#"error ContentLength"#.to_owned(),
+    ]
+}
+
+// decode all available messages, returns false after error
+fn decode(codec: &mut RequestDecoder, buf: &mut BytesMut,
+          body: &mut Vec<u8>, trace: &mut Vec<String>) -> bool {
+    loop {
+        match codec.decode(buf) {
+            Ok(Some(RequestMessage::Message(msg))) =>
+                trace.push(format!("{} {} {:?} {:?}",
+                                   msg.method(), msg.path(), msg.version,
+                                   msg.headers.headers())),
+            Ok(Some(RequestMessage::Body(chunk))) =>
+                body.extend_from_slice(&chunk),
+            Ok(Some(RequestMessage::Completed)) |
+            Ok(Some(RequestMessage::Upgrade { .. })) => {
+                trace.push(format!("completed {:?}", String::from_utf8_lossy(body)));
+                body.clear();
+            },
+            Ok(None) => return true,
+            Err(err) => {
+                trace.push(format!("error {:?}", err));
+                return false
+            },
+        }
+    }
+}
+
+// malformed and smuggling attempts, parsing stops at error
+const MALFORMED: &'static [&'static [u8]] = &[
+    b"GET /a HTTP/1.1\r\ncontent-length: 1\r\ncontent-length: 2\r\n\r\nab",
+    b"POST /a HTTP/1.1\r\ntransfer-encoding: chunked\r\n\r\n5\nhello\r\n0\r\n\r\n",
+    b"POST /a HTTP/1.1\r\ntransfer-encoding: chunked, gzip\r\n\r\n",
+    b"GET /a HTTP/1.1\nhost: a\n\n",
+    b"GET  /a  HTTP/1.1\r\n\r\n",
+    b"GET /a HTTP/1.1\r\nhost: a\r\n folded\r\n\r\n",
+    b"GET /\x7f HTTP/1.1\r\n\r\n",
+    b"GET /a HTTP/2.0\r\n\r\n",
+];
+
+fn decoder(checked: bool) -> RequestDecoder {
+    RequestDecoderBuilder::new().checked(checked).build()
+}
+
+// events of whole input at once
+fn parse_batch(mut codec: RequestDecoder, data: &[u8]) -> Vec<String> {
+    let mut buf = BytesMut::from(data);
+    let mut body = Vec::new();
+    let mut trace = Vec::new();
+
+    decode(&mut codec, &mut buf, &mut body, &mut trace);
+    trace
+}
+
+// events of input fed byte by byte
+fn parse_byte_by_byte(mut codec: RequestDecoder, data: &[u8]) -> Vec<String> {
+    let mut buf = BytesMut::new();
+    let mut body = Vec::new();
+    let mut trace = Vec::new();
+
+    for ch in data.iter() {
+        buf.extend_from_slice(&[*ch]);
+        if !decode(&mut codec, &mut buf, &mut body, &mut trace) {
+            break
+        }
+    }
+    trace
+}
+
+#[test]
+fn test_parse_batch() {
+    let unchecked = parse_batch(decoder(false), REQUESTS);
+    let checked = parse_batch(decoder(true), REQUESTS);
+
+    assert_eq!(unchecked, checked);
+    assert_eq!(checked, expected());
+}
+
+#[test]
+fn test_parse_batch_byte_by_byte() {
+    let unchecked = parse_byte_by_byte(decoder(false), REQUESTS);
+    let checked = parse_byte_by_byte(decoder(true), REQUESTS);
+
+    assert_eq!(unchecked, checked);
+    assert_eq!(checked, expected());
+}
+
+#[test]
+fn test_parse_malformed() {
+    for data in MALFORMED {
+        for &strict in &[false, true] {
+            let make = |checked| {
+                let mut codec = decoder(checked);
+                codec.set_strict(strict);
+                codec
+            };
+            assert_eq!(parse_batch(make(false), data), parse_batch(make(true), data));
+            assert_eq!(parse_byte_by_byte(make(false), data),
+                       parse_byte_by_byte(make(true), data));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+extern crate bytes;
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+extern crate async_tokio;
+
+use std::io::Write;
+use std::net;
+use std::thread;
+
+use futures::{Future, Stream};
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Core;
+use async_tokio::http::{Error, HttpRequest, RequestStream, UpgradeKind, Version};
+
+
+fn serve(data: &'static [u8]) -> Vec<(String, String, Version, Vec<u8>)> {
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut sock = net::TcpStream::connect(addr).unwrap();
+        sock.write_all(data).unwrap();
+    });
+
+    let requests = listener.incoming().into_future()
+        .map_err(|(err, _)| Error::from(err))
+        .and_then(|(conn, _)| {
+            let (sock, _) = conn.unwrap();
+
+            RequestStream::new(sock).and_then(|req| {
+                let HttpRequest { message, payload } = req;
+                payload.collect().map(move |chunks| {
+                    let body = chunks.iter().fold(Vec::new(), |mut body, chunk| {
+                        body.extend_from_slice(chunk);
+                        body
+                    });
+                    (message.method().to_owned(), message.path().to_owned(),
+                     message.version, body)
+                })
+            }).collect()
+        });
+
+    let result = core.run(requests).unwrap();
+    client.join().unwrap();
+    result
+}
+
+#[test]
+fn test_request_stream_keep_alive() {
+    let requests = serve(
+        b"POST /first HTTP/1.1\r\n\
+          content-length: 5\r\n\r\n\
+          hello\
+          GET /second HTTP/1.1\r\n\
+          transfer-encoding: chunked\r\n\r\n\
+          5\r\nworld\r\n0\r\n\r\n");
+
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0], ("POST".to_owned(), "/first".to_owned(),
+                             Version::Http11, b"hello".to_vec()));
+    assert_eq!(requests[1], ("GET".to_owned(), "/second".to_owned(),
+                             Version::Http11, b"world".to_vec()));
+}
+
+#[test]
+fn test_request_stream_skip_payload() {
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut sock = net::TcpStream::connect(addr).unwrap();
+        sock.write_all(b"POST /first HTTP/1.1\r\n\
+                         content-length: 4\r\n\r\n\
+                         data\
+                         GET /second HTTP/1.1\r\n\r\n").unwrap();
+    });
+
+    // payload is not consumed
+    let paths = listener.incoming().into_future()
+        .map_err(|(err, _)| Error::from(err))
+        .and_then(|(conn, _)| {
+            let (sock, _) = conn.unwrap();
+            RequestStream::new(sock)
+                .map(|req| req.message.path().to_owned())
+                .collect()
+        });
+
+    let paths = core.run(paths).unwrap();
+    client.join().unwrap();
+    assert_eq!(paths, vec!["/first".to_owned(), "/second".to_owned()]);
+}
+
+#[test]
+fn test_request_stream_upgrade() {
+    let mut core = Core::new().unwrap();
+    let handle = core.handle();
+
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let client = thread::spawn(move || {
+        let mut sock = net::TcpStream::connect(addr).unwrap();
+        sock.write_all(b"GET /chat HTTP/1.1\r\n\
+                         upgrade: websocket\r\n\
+                         connection: upgrade\r\n\
+                         sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n\
+                         frame data").unwrap();
+    });
+
+    let data = listener.incoming().into_future()
+        .map_err(|(err, _)| Error::from(err))
+        .and_then(|(conn, _)| {
+            let (sock, _) = conn.unwrap();
+            RequestStream::new(sock).into_future().map_err(|(err, _)| err)
+        })
+        .and_then(|(req, stream)| {
+            assert_eq!(req.unwrap().message.path(), "/chat");
+            stream.into_future().map_err(|(err, _)| err)
+        })
+        .and_then(|(req, stream)| {
+            // stream ends, connection belongs to new protocol
+            assert!(req.is_none());
+            let upgraded = match stream.into_upgraded() {
+                Ok(upgraded) => upgraded,
+                Err(_) => panic!("Stream is not upgraded"),
+            };
+            assert_eq!(upgraded.protocol, UpgradeKind::WebSocket);
+            tokio_io::io::read_to_end(upgraded.io, upgraded.remaining.to_vec())
+                .map_err(Error::from)
+        });
+
+    let (_, data) = core.run(data).unwrap();
+    client.join().unwrap();
+    assert_eq!(data, b"frame data".to_vec());
+}
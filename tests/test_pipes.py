@@ -6,7 +6,9 @@
 import asyncio
 import io
 import os
+import tempfile
 
+import pytest
 from asyncio import test_utils
 
 
@@ -245,3 +247,42 @@ def test_write_pty(loop):
     proto.transport.close()
     loop.run_until_complete(proto.done)
     assert 'CLOSED' == proto.state
+
+
+def test_read_pipe_nonblocking(loop):
+    rpipe, wpipe = os.pipe()
+    pipeobj = io.open(rpipe, 'rb', 1024)
+    assert os.get_blocking(rpipe)
+
+    proto = MyReadPipeProto(loop=loop)
+    transport, _ = loop.run_until_complete(
+        loop.connect_read_pipe(lambda: proto, pipeobj))
+    assert not os.get_blocking(rpipe)
+
+    os.close(wpipe)
+    loop.run_until_complete(proto.done)
+
+
+def test_write_pipe_nonblocking(loop):
+    rpipe, wpipe = os.pipe()
+    pipeobj = io.open(wpipe, 'wb', 1024)
+    assert os.get_blocking(wpipe)
+
+    proto = MyWritePipeProto(loop=loop)
+    transport, _ = loop.run_until_complete(
+        loop.connect_write_pipe(lambda: proto, pipeobj))
+    assert not os.get_blocking(wpipe)
+
+    transport.close()
+    loop.run_until_complete(proto.done)
+    os.close(rpipe)
+
+
+def test_pipe_regular_file(loop):
+    with tempfile.TemporaryFile() as f:
+        with pytest.raises(ValueError):
+            loop.run_until_complete(
+                loop.connect_read_pipe(asyncio.Protocol, f))
+        with pytest.raises(ValueError):
+            loop.run_until_complete(
+                loop.connect_write_pipe(asyncio.BaseProtocol, f))
@@ -12,9 +12,12 @@ import time
 
 import pytest
 
-pytestmark = pytest.mark.skip("not fully implemented")
+import tokio
 
+not_implemented = pytest.mark.skip("not fully implemented")
 
+
+@not_implemented
 def test_process_env_1(loop2):
     async def test():
         cmd = 'echo $FOO$BAR'
@@ -33,6 +36,7 @@ def test_process_env_1(loop2):
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_cwd_1(loop2):
     async def test():
         cmd = 'pwd'
@@ -53,6 +57,7 @@ def test_process_cwd_1(loop2):
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_preexec_fn_1(loop2):
     # Copied from CPython/test_suprocess.py
 
@@ -75,6 +80,7 @@ def test_process_preexec_fn_1(loop2):
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_preexec_fn_2(loop2):
     # Copied from CPython/test_suprocess.py
 
@@ -106,6 +112,7 @@ def test_process_preexec_fn_2(loop2):
         assert False, 'exception in preexec_fn did not kill the child process'
 
 
+@not_implemented
 def test_process_executable_1(loop2):
     async def test():
         proc = await asyncio.create_subprocess_exec(
@@ -120,6 +127,7 @@ def test_process_executable_1(loop2):
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_pid_1(loop2):
     async def test():
         prog = '''\
@@ -143,6 +151,7 @@ print(os.getpid())
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_send_signal_1(loop2):
     async def test():
         prog = '''\
@@ -185,6 +194,7 @@ exit(11)
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_streams_basic_1(loop2):
     async def test():
         prog = '''\
@@ -237,6 +247,7 @@ while True:
     loop2.run_until_complete(test())
 
 
+@not_implemented
 def test_process_streams_stderr_to_stdout(loop2):
     async def test():
         prog = '''\
@@ -256,3 +267,129 @@ print('err', file=sys.stderr, flush=True)
         assert out == b'out\nerr\n'
 
     loop2.run_until_complete(test())
+
+
+def test_process_shell_cmd_type(loop2):
+    with pytest.raises(ValueError) as excinfo:
+        loop2.run_until_complete(
+            loop2.subprocess_shell(asyncio.SubprocessProtocol, 1))
+    excinfo.match('cmd must be a string')
+
+
+def test_process_exec_args_type(loop2):
+    with pytest.raises(TypeError) as excinfo:
+        loop2.run_until_complete(
+            loop2.subprocess_exec(asyncio.SubprocessProtocol,
+                                  sys.executable, '-c', 1))
+    excinfo.match('program arguments must be a bytes or text string')
+
+
+def test_process_stdio_pipe(loop2):
+    class Proto(asyncio.SubprocessProtocol):
+        def __init__(self):
+            self.data = {1: b'', 2: b''}
+            self.exited = asyncio.Future(loop=loop2)
+
+        def pipe_data_received(self, fd, data):
+            self.data[fd] += data
+
+        def process_exited(self):
+            self.exited.set_result(None)
+
+    async def test():
+        transport, proto = await loop2.subprocess_exec(
+            Proto, sys.executable, '-c',
+            'import sys; data = sys.stdin.read(); '
+            'sys.stdout.write(data); sys.stderr.write(data)',
+            stdin=subprocess.PIPE,
+            stdout=subprocess.PIPE,
+            stderr=subprocess.PIPE)
+
+        stdin = transport.get_pipe_transport(0)
+        stdin.write(b'data')
+        stdin.close()
+
+        await proto.exited
+        transport.close()
+        return proto.data
+
+    data = loop2.run_until_complete(test())
+    assert data == {1: b'data', 2: b'data'}
+
+
+@pytest.mark.parametrize('name', ['stdin', 'stdout'])
+def test_process_stdio_stdout_invalid(loop2, name):
+    # asyncio passes STDOUT to Popen as file descriptor
+    if isinstance(loop2, tokio._tokio.TokioEventLoop):
+        error = ValueError
+    else:
+        error = OSError
+
+    with pytest.raises(error):
+        loop2.run_until_complete(
+            loop2.subprocess_shell(asyncio.SubprocessProtocol, 'true',
+                                   **{name: subprocess.STDOUT}))
+
+
+def _children():
+    # pids of direct children, zombies included until they are reaped
+    pids = set()
+    for tid in os.listdir('/proc/self/task'):
+        with open('/proc/self/task/{}/children'.format(tid)) as f:
+            pids.update(int(pid) for pid in f.read().split())
+    return pids
+
+
+@pytest.mark.skipif(not os.path.exists('/proc/self/task/{}/children'.format(
+    os.getpid())), reason='linux only')
+def test_process_cancel_creation(tokio_loop):
+    loop = tokio_loop
+
+    async def test():
+        fut = loop.subprocess_exec(
+            asyncio.SubprocessProtocol, sys.executable, '-c',
+            'import time; time.sleep(30)',
+            stdin=subprocess.PIPE,
+            stdout=subprocess.PIPE)
+
+        # child is spawned, pipes are not connected yet
+        await asyncio.sleep(0, loop=loop)
+        fut.cancel()
+
+        with pytest.raises(asyncio.CancelledError):
+            await fut
+
+        for _ in range(100):
+            if not _children():
+                break
+            await asyncio.sleep(0.05, loop=loop)
+
+    loop.run_until_complete(test())
+    assert not _children()
+
+
+def test_process_child_watcher_returncode(tokio_loop):
+    loop = tokio_loop
+
+    class Proto(asyncio.SubprocessProtocol):
+        def __init__(self):
+            self.exited = loop.create_future()
+
+        def process_exited(self):
+            self.exited.set_result(None)
+
+    async def run(prog):
+        transport, proto = await loop.subprocess_exec(
+            Proto, sys.executable, '-c', prog)
+        await proto.exited
+        transport.close()
+        return transport.get_returncode()
+
+    async def test():
+        # several children could exit with single SIGCHLD
+        progs = ['import sys; sys.exit({})'.format(code) for code in range(5)]
+        progs.append('import os, signal; os.kill(os.getpid(), signal.SIGKILL)')
+        return await asyncio.gather(*[run(prog) for prog in progs])
+
+    returncodes = loop.run_until_complete(test())
+    assert returncodes == [0, 1, 2, 3, 4, -signal.SIGKILL]
@@ -4,6 +4,8 @@
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
 import signal
+import subprocess
+import sys
 
 import pytest
 
@@ -22,3 +24,182 @@ def test_signals_coro_callback(loop):
         loop.add_signal_handler(signal.SIGHUP, coro)
 
     excinfo.match('coroutines cannot be used')
+
+
+PROG_RUN_FOREVER = '''\
+import sys
+import tokio
+
+loop = tokio.new_event_loop()
+loop.set_stop_signals({enabled})
+loop.call_soon(lambda: print('READY', flush=True))
+loop.run_forever()
+loop.close()
+print('STOPPED', flush=True)
+'''
+
+
+@pytest.mark.parametrize('sig', [signal.SIGTERM, signal.SIGHUP])
+def test_signals_stop_run_forever(sig):
+    proc = subprocess.Popen(
+        [sys.executable, '-c', PROG_RUN_FOREVER.format(enabled=True)],
+        stdout=subprocess.PIPE)
+    try:
+        assert proc.stdout.readline() == b'READY\n'
+        proc.send_signal(sig)
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == 0
+    assert out == b'STOPPED\n'
+
+
+def test_signals_stop_disabled():
+    proc = subprocess.Popen(
+        [sys.executable, '-c', PROG_RUN_FOREVER.format(enabled=False)],
+        stdout=subprocess.PIPE)
+    try:
+        assert proc.stdout.readline() == b'READY\n'
+        proc.send_signal(signal.SIGTERM)
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == -signal.SIGTERM
+    assert out == b''
+
+
+PROG_AFTER_RUN = '''\
+import time
+import tokio
+
+loop = tokio.new_event_loop()
+loop.set_stop_signals(True)
+loop.call_soon(loop.stop)
+loop.run_forever()
+print('READY', flush=True)
+time.sleep(10)
+print('NOT TERMINATED', flush=True)
+'''
+
+
+@pytest.mark.parametrize('sig', [signal.SIGTERM, signal.SIGHUP])
+def test_signals_default_after_run(sig):
+    proc = subprocess.Popen(
+        [sys.executable, '-c', PROG_AFTER_RUN],
+        stdout=subprocess.PIPE)
+    try:
+        assert proc.stdout.readline() == b'READY\n'
+        proc.send_signal(sig)
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == -sig
+    assert out == b''
+
+
+def test_signals_stop_disabled_by_default():
+    proc = subprocess.Popen(
+        [sys.executable, '-c',
+         PROG_RUN_FOREVER.replace('loop.set_stop_signals({enabled})\n', '')],
+        stdout=subprocess.PIPE)
+    try:
+        assert proc.stdout.readline() == b'READY\n'
+        proc.send_signal(signal.SIGTERM)
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == -signal.SIGTERM
+    assert out == b''
+
+
+PROG_USER_HANDLER = '''\
+import signal
+import tokio
+
+loop = tokio.new_event_loop()
+loop.set_stop_signals(True)
+
+def handler():
+    print('HANDLED', flush=True)
+    loop.stop()
+
+loop.add_signal_handler(signal.SIGTERM, handler)
+loop.call_soon(lambda: print('READY', flush=True))
+loop.run_forever()
+print('STOPPED', flush=True)
+
+# handler added while loop is running
+loop.remove_signal_handler(signal.SIGTERM)
+
+def ready():
+    loop.add_signal_handler(signal.SIGTERM, handler)
+    print('READY', flush=True)
+
+loop.call_soon(ready)
+loop.run_forever()
+print('STOPPED', flush=True)
+
+# handler is kept after run
+loop.call_soon(lambda: print('READY', flush=True))
+loop.run_forever()
+print('STOPPED', flush=True)
+loop.close()
+'''
+
+
+def test_signals_stop_keeps_user_handler():
+    proc = subprocess.Popen(
+        [sys.executable, '-c', PROG_USER_HANDLER],
+        stdout=subprocess.PIPE)
+    try:
+        for _ in range(3):
+            assert proc.stdout.readline() == b'READY\n'
+            proc.send_signal(signal.SIGTERM)
+            assert proc.stdout.readline() == b'HANDLED\n'
+            assert proc.stdout.readline() == b'STOPPED\n'
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == 0
+    assert out == b''
+
+
+PROG_RUN_UNTIL_COMPLETE = '''\
+import asyncio
+import tokio
+
+loop = tokio.new_event_loop()
+task = loop.create_task(asyncio.sleep(10, loop=loop))
+loop.call_soon(lambda: print('READY', flush=True))
+try:
+    loop.run_until_complete(task)
+except KeyboardInterrupt:
+    print('INTERRUPTED', task.cancelling(), flush=True)
+loop.close()
+'''
+
+
+def test_signals_sigint_run_until_complete():
+    proc = subprocess.Popen(
+        [sys.executable, '-c', PROG_RUN_UNTIL_COMPLETE],
+        stdout=subprocess.PIPE)
+    try:
+        assert proc.stdout.readline() == b'READY\n'
+        proc.send_signal(signal.SIGINT)
+        out, _ = proc.communicate(timeout=10)
+    finally:
+        if proc.returncode is None:
+            proc.kill()
+
+    assert proc.returncode == 0
+    assert out == b'INTERRUPTED 1\n'
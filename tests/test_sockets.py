@@ -4,6 +4,8 @@
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
 import asyncio
+import errno
+import os
 import socket
 import sys
 
@@ -94,6 +96,38 @@ def test_socket_failed_connect(loop):
     loop.run_until_complete(run())
 
 
+def test_socket_connect_host_name(tokio_loop):
+    loop = tokio_loop
+
+    sock = socket.socket()
+    with sock:
+        sock.bind(('127.0.0.1', 0))
+        bad_addr = sock.getsockname()
+
+    async def run():
+        srv = socket.socket()
+        with srv:
+            srv.bind(('127.0.0.1', 0))
+            srv.listen(2)
+            good_addr = srv.getsockname()
+
+            # host name gets resolved
+            sock = socket.socket()
+            with sock:
+                sock.setblocking(False)
+                await loop.sock_connect(sock, ('localhost', good_addr[1]))
+                assert sock.getpeername() == good_addr
+
+            # resolved address refuses connection
+            sock = socket.socket()
+            with sock:
+                sock.setblocking(False)
+                with pytest.raises(ConnectionRefusedError):
+                    await loop.sock_connect(sock, ('localhost', bad_addr[1]))
+
+    loop.run_until_complete(run())
+
+
 def test_socket_blocking_error(loop):
     loop.set_debug(True)
     sock = socket.socket()
@@ -118,3 +152,234 @@ def test_socket_blocking_error(loop):
             loop.run_until_complete(loop.sock_connect(sock, (b'', 0)))
 
         excinfo.match('must be non-blocking')
+
+
+def test_socket_sendall_large_payload(loop):
+    data = bytes(range(256)) * (4 * _SIZE // 256)
+
+    async def run():
+        rsock, wsock = socket.socketpair()
+        with rsock, wsock:
+            rsock.setblocking(False)
+            wsock.setblocking(False)
+            wsock.setsockopt(socket.SOL_SOCKET, socket.SO_SNDBUF, 4096)
+            rsock.setsockopt(socket.SOL_SOCKET, socket.SO_RCVBUF, 4096)
+
+            async def reader():
+                buf = bytearray()
+                while len(buf) < len(data):
+                    buf += await loop.sock_recv(rsock, 65536)
+                return bytes(buf)
+
+            task = loop.create_task(reader())
+            await loop.sock_sendall(wsock, bytearray(data))
+            assert await task == data
+
+    loop.run_until_complete(run())
+
+
+def test_socket_accept_result_shape(loop):
+    async def run():
+        sock = socket.socket()
+        sock.setblocking(False)
+
+        with sock:
+            sock.bind(('127.0.0.1', 0))
+            sock.listen()
+
+            client = socket.socket()
+            with client:
+                client.connect(sock.getsockname())
+
+                result = await loop.sock_accept(sock)
+                assert isinstance(result, tuple)
+                assert len(result) == 2
+
+                conn, addr = result
+                with conn:
+                    assert conn.gettimeout() == 0
+                    assert addr == client.getsockname()
+
+    loop.run_until_complete(run())
+
+
+def test_socket_accept_fd_exhausted(tokio_loop):
+    class Socket:
+        def __init__(self, sock):
+            self.sock = sock
+            self.failures = 1
+
+        def fileno(self):
+            return self.sock.fileno()
+
+        def gettimeout(self):
+            return self.sock.gettimeout()
+
+        def accept(self):
+            if self.failures:
+                self.failures -= 1
+                raise OSError(errno.EMFILE, 'Too many open files')
+            return self.sock.accept()
+
+    async def run():
+        sock = socket.socket()
+        sock.setblocking(False)
+
+        with sock:
+            sock.bind(('127.0.0.1', 0))
+            sock.listen()
+            wrapped = Socket(sock)
+
+            client = socket.socket()
+            with client:
+                client.connect(sock.getsockname())
+
+                conn, addr = await asyncio.wait_for(
+                    tokio_loop.sock_accept(wrapped), 5, loop=tokio_loop)
+                with conn:
+                    assert wrapped.failures == 0
+                    assert addr == client.getsockname()
+
+    tokio_loop.run_until_complete(run())
+
+
+@pytest.mark.parametrize('socktype', [socket.SOCK_DGRAM, socket.SOCK_SEQPACKET])
+def test_socket_recv_unix_dgram(loop, socktype):
+    async def run():
+        rsock, wsock = socket.socketpair(socket.AF_UNIX, socktype)
+        with rsock, wsock:
+            rsock.setblocking(False)
+            wsock.setblocking(False)
+
+            wsock.send(b'first')
+            wsock.send(b'second')
+            wsock.send(b'third')
+
+            # one datagram per call, never accumulated
+            assert await loop.sock_recv(rsock, 1024) == b'first'
+            assert await loop.sock_recv(rsock, 1024) == b'second'
+
+            # datagram is truncated to requested size
+            assert await loop.sock_recv(rsock, 2) == b'th'
+
+            reader = asyncio.ensure_future(loop.sock_recv(rsock, 1024), loop=loop)
+            await asyncio.sleep(0.01, loop=loop)
+            assert not reader.done()
+            await loop.sock_sendall(wsock, b'last')
+            assert await reader == b'last'
+
+    loop.run_until_complete(run())
+
+
+def test_socket_recv_cancel_deregisters_fd(tokio_loop):
+    async def run():
+        rsock, wsock = socket.socketpair()
+        with rsock, wsock:
+            rsock.setblocking(False)
+
+            reader = tokio_loop.sock_recv(rsock, 1024)
+            await asyncio.sleep(0.01, loop=tokio_loop)
+            reader.cancel()
+            await asyncio.sleep(0, loop=tokio_loop)
+
+            # fd is not registered with reactor anymore,
+            # so it can be registered again
+            tokio_loop.add_reader(rsock.fileno(), lambda: None)
+            assert tokio_loop.remove_reader(rsock.fileno())
+
+    tokio_loop.run_until_complete(run())
+
+
+def test_socket_recv_send_sequential(tokio_loop):
+    async def run():
+        rsock, wsock = socket.socketpair()
+        with rsock, wsock:
+            rsock.setblocking(False)
+            wsock.setblocking(False)
+
+            # registration is reused by sequential calls
+            for i in range(1000):
+                await tokio_loop.sock_sendall(wsock, b'x')
+                data = await tokio_loop.sock_recv(rsock, 1024)
+                assert data == b'x'
+
+            # cached registration is dropped for reader callbacks
+            tokio_loop.add_reader(rsock.fileno(), lambda: None)
+            assert tokio_loop.remove_reader(rsock.fileno())
+
+            await tokio_loop.sock_sendall(wsock, b'y')
+            assert await tokio_loop.sock_recv(rsock, 1024) == b'y'
+
+    tokio_loop.run_until_complete(run())
+
+
+def test_socket_recv_fd_reused(tokio_loop):
+    async def run():
+        rsock, wsock = socket.socketpair()
+        rsock.setblocking(False)
+        await tokio_loop.sock_sendall(wsock, b'1')
+        assert await tokio_loop.sock_recv(rsock, 1024) == b'1'
+        fd = rsock.fileno()
+        rsock.close()
+        wsock.close()
+
+        # new socket most likely gets same fd number
+        rsock, wsock = socket.socketpair()
+        with rsock, wsock:
+            rsock.setblocking(False)
+            if rsock.fileno() != fd:
+                wsock, rsock = rsock, wsock
+                rsock.setblocking(False)
+
+            reader = tokio_loop.sock_recv(rsock, 1024)
+            await asyncio.sleep(0.01, loop=tokio_loop)
+            wsock.send(b'2')
+            assert await asyncio.wait_for(
+                reader, 1, loop=tokio_loop) == b'2'
+
+    tokio_loop.run_until_complete(run())
+
+
+def test_socket_raw_fd_pipe(tokio_loop):
+    async def run():
+        rfd, wfd = os.pipe()
+        try:
+            os.set_blocking(rfd, False)
+            os.set_blocking(wfd, False)
+
+            reader = tokio_loop.sock_recv(rfd, 1024)
+            await asyncio.sleep(0.01, loop=tokio_loop)
+            assert not reader.done()
+
+            await tokio_loop.sock_sendall(wfd, b'data')
+            assert await reader == b'data'
+
+            # fd is not cached, it can be used with reader callback
+            tokio_loop.add_reader(rfd, lambda: None)
+            assert tokio_loop.remove_reader(rfd)
+        finally:
+            os.close(rfd)
+            os.close(wfd)
+
+    tokio_loop.run_until_complete(run())
+
+
+def test_socket_raw_fd_socketpair(tokio_loop):
+    async def run():
+        rsock, wsock = socket.socketpair()
+        with rsock, wsock:
+            rsock.setblocking(False)
+            wsock.setblocking(False)
+
+            # large payload, write gets blocked
+            data = b'x' * 1024 * 1024
+            sender = tokio_loop.sock_sendall(wsock.fileno(), data)
+
+            received = b''
+            while len(received) < len(data):
+                received += await tokio_loop.sock_recv(rsock.fileno(), 65536)
+
+            await sender
+            assert received == data
+
+    tokio_loop.run_until_complete(run())
@@ -7,6 +7,8 @@ import asyncio
 import socket
 import ssl
 
+import pytest
+
 import _testbase as tb
 
 
@@ -207,3 +209,163 @@ def test_create_connection_ssl_1(loop):
 
     run(client)
     run(client_sock)
+
+
+def test_start_tls(tokio_loop):
+    loop = tokio_loop
+
+    sslctx = create_server_ssl_context(ONLYCERT, ONLYKEY)
+    client_sslctx = create_client_ssl_context()
+
+    def server():
+        data = yield tb.read(8)
+        assert data == b'STARTTLS'
+        yield tb.write(b'OK')
+
+        yield tb.starttls(sslctx, server_side=True)
+
+        data = yield tb.read(4)
+        assert data == b'PING'
+        yield tb.write(b'PONG')
+
+        yield tb.close()
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.data = bytearray()
+            self.waiter = None
+            self.lost = loop.create_future()
+
+        def data_received(self, data):
+            self.data.extend(data)
+            if self.waiter is not None and not self.waiter.done():
+                self.waiter.set_result(None)
+
+        def connection_lost(self, exc):
+            self.lost.set_result(exc)
+
+        async def read(self, size):
+            while len(self.data) < size:
+                self.waiter = loop.create_future()
+                await self.waiter
+            data = bytes(self.data[:size])
+            del self.data[:size]
+            return data
+
+    async def client(addr):
+        tr, proto = await loop.create_connection(Proto, *addr)
+
+        with pytest.raises(TypeError):
+            await loop.start_tls(tr, proto, object())
+
+        tr.write(b'STARTTLS')
+        assert await proto.read(2) == b'OK'
+
+        new_tr = await loop.start_tls(
+            tr, proto, client_sslctx, server_hostname='')
+        assert new_tr is not tr
+        assert new_tr.get_protocol() is proto
+
+        new_tr.write(b'PING')
+        assert await proto.read(4) == b'PONG'
+
+        new_tr.close()
+        await proto.lost
+
+    with tb.tcp_server(server) as srv:
+        loop.run_until_complete(
+            asyncio.wait_for(client(srv.addr), 10, loop=loop))
+
+
+def test_transport_start_tls_sniffing(tokio_loop):
+    loop = tokio_loop
+
+    sslctx = create_server_ssl_context(ONLYCERT, ONLYKEY)
+    client_sslctx = create_client_ssl_context()
+
+    class SniffProto(asyncio.Protocol):
+        # tls record starts with handshake content type 0x16
+        def __init__(self):
+            self.transport = None
+            self.sniffed = False
+
+        def connection_made(self, tr):
+            self.transport = tr
+
+        def data_received(self, data):
+            if not self.sniffed:
+                self.sniffed = True
+                if data[:1] == b'\x16':
+                    fut = self.transport.start_tls(sslctx, data=data)
+                    fut.add_done_callback(self.upgraded)
+                    return
+            self.transport.write(b'ECHO:' + data)
+
+        def upgraded(self, fut):
+            self.transport = fut.result()
+            self.transport.write(b'HELLO')
+
+    async def client():
+        srv = await loop.create_server(
+            SniffProto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        reader, writer = await asyncio.open_connection(*addr)
+        writer.write(b'PING')
+        assert await reader.readexactly(9) == b'ECHO:PING'
+        writer.close()
+
+        reader, writer = await asyncio.open_connection(
+            *addr, ssl=client_sslctx, server_hostname='')
+        assert await reader.readexactly(5) == b'HELLO'
+        writer.write(b'PING')
+        assert await reader.readexactly(9) == b'ECHO:PING'
+        writer.close()
+
+        srv.close()
+
+    loop.run_until_complete(asyncio.wait_for(client(), 10))
+
+
+def test_create_connection_ssl_handshake_timeout(tokio_loop):
+    loop = tokio_loop
+    client_sslctx = create_client_ssl_context()
+
+    class SrvProto(asyncio.Protocol):
+        # accepts tcp connection, never answers handshake
+        def __init__(self):
+            self.lost = loop.create_future()
+
+        def connection_lost(self, exc):
+            self.lost.set_result(exc)
+
+    srv_proto = SrvProto()
+
+    async def client():
+        srv = await loop.create_server(
+            lambda: srv_proto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        with pytest.raises(ValueError):
+            await loop.create_connection(
+                asyncio.Protocol, *addr, ssl_handshake_timeout=1)
+
+        with pytest.raises(ValueError):
+            await loop.create_connection(
+                asyncio.Protocol, *addr, ssl=client_sslctx,
+                server_hostname='', ssl_handshake_timeout=-1)
+
+        with pytest.raises(TimeoutError):
+            await asyncio.wait_for(
+                loop.create_connection(
+                    asyncio.Protocol, *addr, ssl=client_sslctx,
+                    server_hostname='', ssl_handshake_timeout=0.1),
+                10, loop=loop)
+
+        # client socket is closed
+        await asyncio.wait_for(srv_proto.lost, 10, loop=loop)
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(client())
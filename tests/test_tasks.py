@@ -4,6 +4,8 @@
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
 import asyncio
+import gc
+import weakref
 from asyncio import test_utils
 
 import pytest
@@ -79,6 +81,98 @@ def test_task_repr(loop):
     assert repr(t) == "<Task finished %s result='abc'>" % coro
 
 
+def test_task_name(tokio_loop):
+    async def coro():
+        pass
+
+    t1 = tokio_loop.create_task(coro())
+    t2 = tokio_loop.create_task(coro())
+    assert t1.get_name().startswith('Task-')
+    assert t1.get_name() != t2.get_name()
+    assert "name=%r" % t1.get_name() in repr(t1)
+
+    t3 = tokio_loop.create_task(coro(), name='worker')
+    assert t3.get_name() == 'worker'
+    assert "<Task pending name='worker' " in repr(t3)
+
+    t3.set_name(42)
+    assert t3.get_name() == '42'
+
+    tokio_loop.run_until_complete(asyncio.gather(t1, t2, t3, loop=tokio_loop))
+    assert "<Task finished name='42' " in repr(t3)
+
+
+def test_task_cancelling(tokio_loop):
+    async def coro():
+        await asyncio.sleep(10, loop=tokio_loop)
+
+    t = tokio_loop.create_task(coro())
+    assert t.cancelling() == 0
+
+    assert t.cancel()
+    assert t.cancel()
+    assert t.cancelling() == 2
+
+    assert t.uncancel() == 1
+    assert t.cancelling() == 1
+
+    with pytest.raises(asyncio.CancelledError):
+        tokio_loop.run_until_complete(t)
+
+    # done task does not count cancel requests
+    assert not t.cancel()
+    assert t.cancelling() == 1
+
+    assert t.uncancel() == 0
+    assert t.uncancel() == 0
+    assert t.cancelling() == 0
+
+
+def test_all_tasks(tokio_loop):
+    loop = tokio_loop
+    current = []
+
+    async def coro(delay):
+        current.append(loop.current_task())
+        await asyncio.sleep(delay, loop=loop)
+
+    assert loop.all_tasks() == set()
+    assert loop.current_task() is None
+
+    t1 = loop.create_task(coro(0))
+    t2 = loop.create_task(coro(0.01))
+    t3 = loop.create_task(coro(10))
+    assert loop.all_tasks() == {t1, t2, t3}
+
+    loop.run_until_complete(t2)
+    assert loop.all_tasks() == {t3}
+    assert current == [t1, t2, t3]
+    assert loop.current_task() is None
+
+    t3.cancel()
+    with pytest.raises(asyncio.CancelledError):
+        loop.run_until_complete(t3)
+    assert loop.all_tasks() == set()
+
+
+def test_all_tasks_weak(tokio_loop):
+    loop = tokio_loop
+
+    async def coro():
+        pass
+
+    task = loop.create_task(coro())
+    ref = weakref.ref(task)
+    assert loop.all_tasks() == {task}
+    loop.run_until_complete(task)
+
+    # registry does not keep tasks alive
+    del task
+    gc.collect()
+    assert ref() is None
+    assert loop.all_tasks() == set()
+
+
 def test_task_basics(loop):
     @asyncio.coroutine
     def outer():
@@ -4,7 +4,11 @@
 # Portions copyright (c) 2015-present MagicStack Inc.  http://magic.io
 
 import asyncio
+import builtins
+import errno
 import socket
+import ssl
+import struct
 import sys
 import threading
 
@@ -231,6 +235,119 @@ def test_create_server_5(loop, port):
     loop.run_until_complete(runner())
 
 
+@pytest.mark.skipif(not socket.has_ipv6, reason='IPv6 is not available')
+def test_create_server_multiple_hosts(loop, port):
+
+    async def runner():
+        srv = await loop.create_server(
+            asyncio.Protocol, ['127.0.0.1', '::1', '127.0.0.1'], port)
+
+        families = sorted(s.family for s in srv.sockets)
+        assert families == sorted([socket.AF_INET, socket.AF_INET6])
+
+        for sock in srv.sockets:
+            assert sock.getsockname()[1] == port
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_create_server_multiple_hosts_in_use(loop):
+    sock = socket.socket()
+    sock.bind(('127.0.0.1', 0))
+
+    with sock:
+        port = sock.getsockname()[1]
+
+        with pytest.raises(OSError) as excinfo:
+            loop.run_until_complete(
+                loop.create_server(object, ['localhost', '127.0.0.1'], port))
+
+        excinfo.match("in use")
+
+
+def test_create_server_port_zero(loop):
+
+    async def runner():
+        srv = await loop.create_server(asyncio.Protocol, '127.0.0.1', 0)
+
+        assert len(srv.sockets) == 1
+        port = srv.sockets[0].getsockname()[1]
+        assert port != 0
+
+        _, writer = await asyncio.open_connection(
+            '127.0.0.1', port, loop=loop)
+        writer.close()
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_create_server_sock_list(loop):
+    socks = []
+    for _ in range(2):
+        sock = socket.socket()
+        sock.bind(('127.0.0.1', 0))
+        socks.append(sock)
+    addrs = sorted(sock.getsockname() for sock in socks)
+
+    async def runner():
+        srv = await loop.create_server(asyncio.Protocol, sock=socks)
+
+        assert sorted(s.getsockname() for s in srv.sockets) == addrs
+
+        for addr in addrs:
+            _, writer = await asyncio.open_connection(*addr, loop=loop)
+            writer.close()
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+@pytest.mark.skipif(not hasattr(socket, 'AF_UNIX'), reason='no UNIX sockets')
+def test_create_server_sock_list_unix(tokio_loop):
+    tcp = socket.socket()
+    tcp.bind(('127.0.0.1', 0))
+    unix = socket.socket(socket.AF_UNIX)
+
+    with tcp, unix:
+        with pytest.raises(ValueError) as excinfo:
+            tokio_loop.run_until_complete(
+                tokio_loop.create_server(asyncio.Protocol, sock=[tcp, unix]))
+
+        excinfo.match('UNIX domain socket')
+
+
+def test_create_server_sock_list_ownership(tokio_loop):
+    loop = tokio_loop
+    good = socket.socket()
+    good.bind(('127.0.0.1', 0))
+    addr = good.getsockname()
+    closed = socket.socket()
+    closed.close()
+
+    with good:
+        with pytest.raises(OSError):
+            loop.run_until_complete(
+                loop.create_server(asyncio.Protocol, sock=[good, closed]))
+
+        # failed call leaves fd with python socket
+        assert good.getsockname() == addr
+
+        srv = loop.run_until_complete(
+            loop.create_server(asyncio.Protocol, sock=[good]))
+        assert good.fileno() == -1
+
+        srv.close()
+        loop.run_until_complete(srv.wait_closed())
+
+
 @pytest.mark.skipif(not hasattr(socket, 'SO_REUSEPORT'),
                     reason='The system does not support SO_REUSEPORT')
 @pytest.mark.skipif(sys.version_info[:3] < (3, 5, 1),
@@ -258,6 +375,74 @@ def test_create_server_6(loop, port):
     loop.run_until_complete(runner())
 
 
+def test_create_server_reuse_port_default(tokio_loop):
+    loop = tokio_loop
+
+    srv1 = loop.run_until_complete(
+        loop.create_server(asyncio.Protocol, '127.0.0.1', 0))
+    port = srv1.sockets[0].getsockname()[1]
+
+    # SO_REUSEPORT is not set by default, port is busy
+    with pytest.raises(OSError):
+        loop.run_until_complete(
+            loop.create_server(asyncio.Protocol, '127.0.0.1', port))
+
+    srv1.close()
+    loop.run_until_complete(srv1.wait_closed())
+
+
+def test_create_server_reuse_port_unsupported(loop, monkeypatch):
+    monkeypatch.delattr(socket, 'SO_REUSEPORT', raising=False)
+
+    with pytest.raises(ValueError) as excinfo:
+        loop.run_until_complete(
+            loop.create_server(
+                asyncio.Protocol, '127.0.0.1', 0, reuse_port=True))
+    excinfo.match('reuse_port')
+
+
+def test_create_server_debug_ssl_validation(tokio_loop):
+    loop = tokio_loop
+
+    loop.set_debug(False)
+    srv = loop.run_until_complete(
+        loop.create_server(asyncio.Protocol, '127.0.0.1', 0, ssl=object()))
+    srv.close()
+
+    loop.set_debug(True)
+    with pytest.raises(TypeError) as excinfo:
+        loop.run_until_complete(
+            loop.create_server(asyncio.Protocol, '127.0.0.1', 0, ssl=object()))
+    excinfo.match('SSLContext')
+
+
+def test_create_connection_debug_ssl_validation(tokio_loop, port):
+    loop = tokio_loop
+    sslctx = ssl.create_default_context()
+
+    loop.set_debug(False)
+    with pytest.raises(OSError):
+        loop.run_until_complete(
+            loop.create_connection(
+                asyncio.Protocol, '127.0.0.1', port,
+                ssl=sslctx, server_hostname=b'localhost'))
+
+    loop.set_debug(True)
+    with pytest.raises(TypeError) as excinfo:
+        loop.run_until_complete(
+            loop.create_connection(
+                asyncio.Protocol, '127.0.0.1', port,
+                ssl=sslctx, server_hostname=b'localhost'))
+    excinfo.match('server_hostname')
+
+    with pytest.raises(TypeError) as excinfo:
+        loop.run_until_complete(
+            loop.create_connection(
+                asyncio.Protocol, '127.0.0.1', port,
+                ssl=object(), server_hostname='localhost'))
+    excinfo.match('SSLContext')
+
+
 def test_create_connection_1(loop):
     CNT = 0
     TOTAL_CNT = 100
@@ -348,6 +533,81 @@ def test_create_connection_2(loop):
     loop.run_until_complete(runner())
 
 
+def test_create_connection_refused_errno(loop):
+    sock = socket.socket()
+    with sock:
+        sock.bind(('127.0.0.1', 0))
+        addr = sock.getsockname()
+
+    with pytest.raises(ConnectionRefusedError) as excinfo:
+        loop.run_until_complete(
+            loop.create_connection(asyncio.Protocol, *addr))
+
+    assert excinfo.value.errno == errno.ECONNREFUSED
+    assert excinfo.value.strerror
+
+
+class _ExceptionGroup(Exception):
+    # stand-in for builtin ExceptionGroup of python 3.11+
+    def __init__(self, message, exceptions):
+        super().__init__(message, exceptions)
+        self.exceptions = exceptions
+
+
+def test_create_connection_all_errors(tokio_loop, monkeypatch):
+    loop = tokio_loop
+    if not hasattr(builtins, 'ExceptionGroup'):
+        monkeypatch.setattr(builtins, 'ExceptionGroup', _ExceptionGroup,
+                            raising=False)
+
+    sock = socket.socket()
+    with sock:
+        sock.bind(('127.0.0.1', 0))
+        port = sock.getsockname()[1]
+
+    addrs = socket.getaddrinfo(
+        'localhost', port, type=socket.SOCK_STREAM, flags=socket.AI_PASSIVE)
+    if len(addrs) < 2:
+        pytest.skip('localhost resolves to single address')
+
+    with pytest.raises(builtins.ExceptionGroup) as excinfo:
+        loop.run_until_complete(loop.create_connection(
+            asyncio.Protocol, 'localhost', port,
+            flags=socket.AI_PASSIVE, all_errors=True))
+
+    assert len(excinfo.value.exceptions) == len(addrs)
+    for exc in excinfo.value.exceptions:
+        assert isinstance(exc, OSError)
+
+    # last error is raised by default
+    with pytest.raises(OSError):
+        loop.run_until_complete(loop.create_connection(
+            asyncio.Protocol, 'localhost', port, flags=socket.AI_PASSIVE))
+
+
+def test_create_connection_all_errors_oserror(tokio_loop, monkeypatch):
+    loop = tokio_loop
+    monkeypatch.delattr(builtins, 'ExceptionGroup', raising=False)
+
+    sock = socket.socket()
+    with sock:
+        sock.bind(('127.0.0.1', 0))
+        port = sock.getsockname()[1]
+
+    addrs = socket.getaddrinfo(
+        'localhost', port, type=socket.SOCK_STREAM, flags=socket.AI_PASSIVE)
+    if len(addrs) < 2:
+        pytest.skip('localhost resolves to single address')
+
+    # without ExceptionGroup all errors are combined into single OSError
+    with pytest.raises(OSError) as excinfo:
+        loop.run_until_complete(loop.create_connection(
+            asyncio.Protocol, 'localhost', port,
+            flags=socket.AI_PASSIVE, all_errors=True))
+
+    excinfo.match('^Multiple exceptions: ')
+
+
 def test_create_connection_3(loop):
     CNT = 0
     TOTAL_CNT = 100
@@ -584,6 +844,323 @@ def test_many_small_writes(loop):
     loop.run_until_complete(run())
 
 
+def test_transport_write_buffer_limits(loop):
+    srv_proto = None
+
+    class SrvProto(asyncio.Protocol):
+        def connection_made(self, tr):
+            nonlocal srv_proto
+            srv_proto = self
+            self.transport = tr
+            self.received = 0
+            tr.pause_reading()
+
+        def data_received(self, data):
+            self.received += len(data)
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.paused = loop.create_future()
+            self.resumed = loop.create_future()
+
+        def connection_made(self, tr):
+            self.transport = tr
+
+        def pause_writing(self):
+            self.paused.set_result(self.transport.get_write_buffer_size())
+
+        def resume_writing(self):
+            self.resumed.set_result(self.transport.get_write_buffer_size())
+
+    DATA = b'x' * 16 * 1024 * 1024
+
+    async def runner():
+        srv = await loop.create_server(
+            SrvProto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(Proto, *addr)
+
+        with pytest.raises(ValueError):
+            tr.set_write_buffer_limits(high=1, low=2)
+
+        tr.set_write_buffer_limits(high=1024)
+        assert tr.get_write_buffer_limits() == (256, 1024)
+
+        # peer does not read, write buffer grows
+        tr.write(DATA)
+        size = await asyncio.wait_for(pr.paused, 10, loop=loop)
+        assert size > 1024
+        assert not pr.resumed.done()
+
+        srv_proto.transport.resume_reading()
+        size = await asyncio.wait_for(pr.resumed, 10, loop=loop)
+        assert size <= 256
+
+        tr.close()
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_resume_writing_low_water(tokio_loop):
+    loop = tokio_loop
+    srv_proto = None
+
+    class SrvProto(asyncio.Protocol):
+        def connection_made(self, tr):
+            nonlocal srv_proto
+            srv_proto = self
+            self.transport = tr
+            tr.pause_reading()
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.resumed = loop.create_future()
+
+        def connection_made(self, tr):
+            self.transport = tr
+
+        def resume_writing(self):
+            self.resumed.set_result(self.transport.get_write_buffer_size())
+
+    # much more than socket buffers could hold
+    DATA = b'x' * 64 * 1024 * 1024
+    LOW = 32 * 1024 * 1024
+
+    async def runner():
+        srv = await loop.create_server(
+            SrvProto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(Proto, *addr)
+        tr.set_write_buffer_limits(high=LOW + 8 * 1024 * 1024, low=LOW)
+
+        tr.write(DATA)
+        await asyncio.sleep(0, loop=loop)
+        assert not pr.resumed.done()
+
+        # protocol is resumed before write buffer is fully flushed
+        srv_proto.transport.resume_reading()
+        size = await asyncio.wait_for(pr.resumed, 10, loop=loop)
+        assert 0 < size <= LOW
+
+        tr.close()
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_write_eof(loop):
+
+    class EchoProto(asyncio.Protocol):
+        def connection_made(self, tr):
+            self.transport = tr
+            self.data = []
+
+        def data_received(self, data):
+            self.data.append(data)
+
+        def eof_received(self):
+            # peer closed its write side, reply and close
+            self.transport.writelines(self.data)
+            self.transport.close()
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.data = b''
+            self.done = loop.create_future()
+
+        def data_received(self, data):
+            self.data += data
+
+        def connection_lost(self, exc):
+            self.done.set_result(exc)
+
+    async def runner():
+        srv = await loop.create_server(
+            EchoProto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(Proto, *addr)
+        assert tr.can_write_eof()
+
+        tr.writelines([b'hello', b' ', b'world'])
+        tr.write_eof()
+
+        with pytest.raises(RuntimeError):
+            tr.write(b'data')
+
+        await asyncio.wait_for(pr.done, 10, loop=loop)
+        assert pr.data == b'hello world'
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_writelines_single_write(tokio_loop):
+    loop = tokio_loop
+
+    async def runner():
+        srv = await loop.create_server(
+            asyncio.Protocol, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(asyncio.Protocol, *addr)
+
+        # chunks are joined before write, nothing is sent on error
+        with pytest.raises(TypeError):
+            tr.writelines([b'hello', 1])
+        assert tr.get_write_buffer_size() == 0
+
+        tr.writelines([b'hello', bytearray(b' '), memoryview(b'world')])
+        assert tr.get_write_buffer_size() == 11
+
+        tr.close()
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_pause_reading(tokio_loop):
+    loop = tokio_loop
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.data = b''
+            self.received = loop.create_future()
+
+        def connection_made(self, tr):
+            self.transport = tr
+
+        def data_received(self, data):
+            self.data += data
+            if not self.received.done():
+                self.received.set_result(None)
+
+    srv_proto = Proto()
+
+    async def runner():
+        srv = await loop.create_server(
+            lambda: srv_proto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(asyncio.Protocol, *addr)
+        tr.write(b'hello')
+        await asyncio.wait_for(srv_proto.received, 10, loop=loop)
+
+        srv_tr = srv_proto.transport
+        assert srv_tr.is_reading()
+        srv_tr.pause_reading()
+        srv_tr.pause_reading()
+        assert not srv_tr.is_reading()
+
+        # data stays in socket while reading is paused
+        tr.write(b' world')
+        await asyncio.sleep(0.1, loop=loop)
+        assert srv_proto.data == b'hello'
+
+        srv_proto.received = loop.create_future()
+        srv_tr.resume_reading()
+        assert srv_tr.is_reading()
+        await asyncio.wait_for(srv_proto.received, 10, loop=loop)
+        assert srv_proto.data == b'hello world'
+
+        tr.close()
+        srv_tr.close()
+        assert not srv_tr.is_reading()
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_abort(tokio_loop):
+    loop = tokio_loop
+
+    class SrvProto(asyncio.Protocol):
+        def __init__(self):
+            self.received = 0
+            self.done = loop.create_future()
+
+        def data_received(self, data):
+            self.received += len(data)
+
+        def connection_lost(self, exc):
+            self.done.set_result(self.received)
+
+    srv_proto = SrvProto()
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.lost = loop.create_future()
+
+        def connection_lost(self, exc):
+            self.lost.set_result(exc)
+
+    async def runner():
+        srv = await loop.create_server(
+            lambda: srv_proto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(Proto, *addr)
+
+        # data is buffered, abort drops it without flushing
+        tr.write(b'x' * 1024 * 1024)
+        tr.abort()
+        tr.abort()
+        assert tr.is_closing()
+
+        assert await asyncio.wait_for(pr.lost, 10, loop=loop) is None
+        assert await asyncio.wait_for(srv_proto.done, 10, loop=loop) == 0
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
+def test_transport_get_extra_info(loop):
+    srv_tr = None
+
+    class SrvProto(asyncio.Protocol):
+        def connection_made(self, tr):
+            nonlocal srv_tr
+            srv_tr = tr
+
+    async def runner():
+        srv = await loop.create_server(
+            SrvProto, '127.0.0.1', 0, family=socket.AF_INET)
+        addr = srv.sockets[0].getsockname()
+
+        tr, pr = await loop.create_connection(asyncio.Protocol, *addr)
+        await asyncio.sleep(0.01, loop=loop)
+
+        assert tr.get_extra_info('peername') == addr
+        sockname = tr.get_extra_info('sockname')
+        assert sockname != addr
+        assert srv_tr.get_extra_info('peername') == sockname
+        assert srv_tr.get_extra_info('sockname') == addr
+
+        sock = tr.get_extra_info('socket')
+        assert sock.getpeername() == addr
+        assert tr.get_extra_info('sslcontext') is None
+        assert tr.get_extra_info('unknown', 'default') == 'default'
+
+        tr.close()
+        assert tr.get_extra_info('peername') == addr
+
+        srv.close()
+        await srv.wait_closed()
+
+    loop.run_until_complete(runner())
+
+
 def _test_tcp_handle_abort_in_connection_made(loop):
     async def server(reader, writer):
         try:
@@ -676,6 +1253,19 @@ def test_connect_accepted_socket(loop, server_ssl=None, client_ssl=None):
     tr.close()
 
 
+def test_connect_accepted_socket_not_connected(tokio_loop):
+    loop = tokio_loop
+
+    sock = socket.socket()
+    with sock:
+        with pytest.raises(OSError):
+            loop.run_until_complete(
+                loop.connect_accepted_socket(MyBaseProto, sock))
+
+    # loop is still operational
+    loop.run_until_complete(asyncio.sleep(0, loop=loop))
+
+
 @pytest.mark.skipif(not hasattr(socket, 'AF_UNIX'), reason='no Unix sockets')
 def test_create_connection_wrong_sock(loop):
     sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
@@ -708,3 +1298,245 @@ def test_create_server_stream_bittype(loop):
         srv = loop.run_until_complete(coro)
         srv.close()
         loop.run_until_complete(srv.wait_closed())
+
+
+@pytest.mark.parametrize('abort', [False, True])
+def test_connection_lost_reset(tokio_loop, abort):
+    loop = tokio_loop
+    lost = loop.create_future()
+
+    class Proto(asyncio.Protocol):
+        def connection_lost(self, exc):
+            lost.set_result(exc)
+
+    async def main():
+        with socket.socket() as srv:
+            srv.bind(('127.0.0.1', 0))
+            srv.listen(1)
+            srv.setblocking(False)
+
+            tr, _ = await loop.create_connection(Proto, *srv.getsockname())
+            conn, _ = await loop.sock_accept(srv)
+            if abort:
+                # zero linger timeout, close() sends RST
+                conn.setsockopt(socket.SOL_SOCKET, socket.SO_LINGER,
+                                struct.pack('ii', 1, 0))
+            conn.close()
+
+            exc = await asyncio.wait_for(lost, 5, loop=loop)
+            if abort:
+                assert isinstance(exc, ConnectionResetError)
+            else:
+                assert exc is None
+
+    loop.run_until_complete(main())
+
+
+def test_transport_error_context(tokio_loop):
+    loop = tokio_loop
+    contexts = []
+    loop.set_exception_handler(lambda loop, ctx: contexts.append(ctx))
+
+    class Proto(asyncio.Protocol):
+        def connection_made(self, transport):
+            self.transport = transport
+
+        def data_received(self, data):
+            raise ValueError('data')
+
+    async def main():
+        with socket.socket() as srv:
+            srv.bind(('127.0.0.1', 0))
+            srv.listen(1)
+            srv.setblocking(False)
+
+            tr, proto = await loop.create_connection(
+                Proto, *srv.getsockname())
+            conn, _ = await loop.sock_accept(srv)
+            with conn:
+                await loop.sock_sendall(conn, b'data')
+                for _ in range(100):
+                    if contexts:
+                        break
+                    await asyncio.sleep(0.01, loop=loop)
+            tr.close()
+
+        return tr, proto
+
+    tr, proto = loop.run_until_complete(main())
+    loop.set_exception_handler(None)
+
+    assert len(contexts) == 1
+    ctx = contexts[0]
+    assert isinstance(ctx['exception'], ValueError)
+    assert ctx['transport'] is tr
+    assert ctx['protocol'] is proto
+    assert ctx.get('socket') is tr.get_extra_info('socket')
+
+
+def test_create_server_async_with(tokio_loop):
+    loop = tokio_loop
+
+    async def main():
+        srv = await loop.create_server(asyncio.Protocol, '127.0.0.1', 0)
+        async with srv as server:
+            assert server is srv
+            port = srv.sockets[0].getsockname()[1]
+
+            _, writer = await asyncio.open_connection(
+                '127.0.0.1', port, loop=loop)
+            writer.close()
+
+        await asyncio.sleep(0, loop=loop)
+
+        # listener is closed
+        with pytest.raises(OSError):
+            await asyncio.open_connection('127.0.0.1', port, loop=loop)
+
+    loop.run_until_complete(main())
+
+
+def test_create_connection_resolved_endpoint(tokio_loop):
+    loop = tokio_loop
+    loop._set_dns_cache_ttl(60)
+    connected = []
+
+    class Proto(asyncio.Protocol):
+        def connection_made(self, transport):
+            connected.append(transport.get_extra_info('peername'))
+            transport.close()
+
+    async def main():
+        srv = await loop.create_server(Proto, '127.0.0.1', 0)
+        port = srv.sockets[0].getsockname()[1]
+
+        endpoint = await loop.resolve_endpoint('127.0.0.1', port)
+        assert loop._dns_cache_info() == (0, 1)
+
+        for _ in range(2):
+            tr, _ = await loop.create_connection(
+                asyncio.Protocol, endpoint=endpoint)
+            assert tr.get_extra_info('peername') == ('127.0.0.1', port)
+            tr.close()
+
+        # connections do not resolve address again
+        assert loop._dns_cache_info() == (0, 1)
+
+        with pytest.raises(ValueError):
+            await loop.create_connection(
+                asyncio.Protocol, '127.0.0.1', port, endpoint=endpoint)
+        with pytest.raises(TypeError):
+            await loop.create_connection(asyncio.Protocol, endpoint=object())
+
+        await asyncio.sleep(0.01, loop=loop)
+        srv.close()
+
+    loop.run_until_complete(main())
+    assert len(connected) == 2
+
+
+@pytest.mark.skipif(not hasattr(asyncio, 'BufferedProtocol'),
+                    reason='BufferedProtocol requires python 3.7')
+def test_transport_buffered_protocol(tokio_loop):
+    loop = tokio_loop
+    payload = b'x' * 1000 + b'y' * 10
+
+    class ServerProto(asyncio.Protocol):
+        def connection_made(self, transport):
+            transport.write(payload)
+            transport.close()
+
+    class Proto(asyncio.BufferedProtocol):
+        def __init__(self):
+            self.buf = bytearray(64)
+            self.data = b''
+            self.sizes = []
+            self.hints = []
+            self.done = loop.create_future()
+
+        def get_buffer(self, sizehint):
+            self.hints.append(sizehint)
+            return self.buf
+
+        def buffer_updated(self, nbytes):
+            self.sizes.append(nbytes)
+            self.data += self.buf[:nbytes]
+
+        def eof_received(self):
+            pass
+
+        def connection_lost(self, exc):
+            self.done.set_result(exc)
+
+    async def main():
+        srv = await loop.create_server(ServerProto, '127.0.0.1', 0)
+        port = srv.sockets[0].getsockname()[1]
+
+        tr, proto = await loop.create_connection(Proto, '127.0.0.1', port)
+        assert await proto.done is None
+        srv.close()
+        return proto
+
+    proto = loop.run_until_complete(main())
+    assert proto.data == payload
+    assert sum(proto.sizes) == len(payload)
+    assert all(0 < size <= 64 for size in proto.sizes)
+    assert len(proto.sizes) >= len(payload) // 64
+
+
+@pytest.mark.skipif(not hasattr(asyncio, 'BufferedProtocol'),
+                    reason='BufferedProtocol requires python 3.7')
+def test_transport_switch_to_buffered_protocol(tokio_loop):
+    loop = tokio_loop
+    first, second = b'x' * 100, b'y' * 1000
+
+    class ServerProto(asyncio.Protocol):
+        def connection_made(self, transport):
+            transport.write(first)
+            loop.call_later(0.05, self.send_rest, transport)
+
+        def send_rest(self, transport):
+            transport.write(second)
+            transport.close()
+
+    class BufferedProto(asyncio.BufferedProtocol):
+        def __init__(self, data, done):
+            self.buf = bytearray(64)
+            self.data = data
+            self.done = done
+
+        def get_buffer(self, sizehint):
+            return self.buf
+
+        def buffer_updated(self, nbytes):
+            self.data += self.buf[:nbytes]
+
+        def eof_received(self):
+            pass
+
+        def connection_lost(self, exc):
+            self.done.set_result(exc)
+
+    class Proto(asyncio.Protocol):
+        def __init__(self):
+            self.data = bytearray()
+            self.done = loop.create_future()
+
+        def connection_made(self, transport):
+            self.transport = transport
+
+        def data_received(self, data):
+            self.data += data
+            self.transport.set_protocol(BufferedProto(self.data, self.done))
+
+    async def main():
+        srv = await loop.create_server(ServerProto, '127.0.0.1', 0)
+        port = srv.sockets[0].getsockname()[1]
+
+        tr, proto = await loop.create_connection(Proto, '127.0.0.1', port)
+        assert await proto.done is None
+        srv.close()
+        return proto
+
+    proto = loop.run_until_complete(main())
+    assert bytes(proto.data) == first + second
@@ -5,7 +5,9 @@
 
 import asyncio
 import os
+import pathlib
 import socket
+import sys
 import tempfile
 
 import pytest
@@ -161,6 +163,64 @@ def test_create_unix_server_2(loop):
         excinfo.match('in use')
 
 
+def test_create_unix_server_cleanup(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        sock_name = os.path.join(td, 'sock')
+
+        for _ in range(2):
+            srv = loop.run_until_complete(
+                loop.create_unix_server(asyncio.Protocol, sock_name))
+            assert os.path.exists(sock_name)
+
+            srv.close()
+            loop.run_until_complete(srv.wait_closed())
+            assert not os.path.exists(sock_name)
+
+
+def test_create_unix_server_no_cleanup(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        sock_name = os.path.join(td, 'sock')
+
+        srv = loop.run_until_complete(
+            loop.create_unix_server(
+                asyncio.Protocol, sock_name, cleanup_socket=False))
+        srv.close()
+        loop.run_until_complete(srv.wait_closed())
+        assert os.path.exists(sock_name)
+
+
+def test_create_unix_server_sock_no_cleanup(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        sock_name = os.path.join(td, 'sock')
+        sock = socket.socket(socket.AF_UNIX)
+        sock.bind(sock_name)
+
+        srv = loop.run_until_complete(
+            loop.create_unix_server(asyncio.Protocol, sock=sock))
+        srv.close()
+        loop.run_until_complete(srv.wait_closed())
+        assert os.path.exists(sock_name)
+
+
+def test_create_unix_connection_seqpacket_sock(tokio_loop):
+    loop = tokio_loop
+
+    # SOCK_SEQPACKET shares bits with SOCK_STREAM
+    s1, s2 = socket.socketpair(socket.AF_UNIX, socket.SOCK_SEQPACKET)
+    with s1, s2:
+        with pytest.raises(ValueError) as excinfo:
+            loop.run_until_complete(
+                loop.create_unix_connection(asyncio.Protocol, sock=s2))
+
+        excinfo.match('A UNIX Domain Stream Socket was expected')
+
+
 def test_create_unix_connection_1(loop):
     CNT = 0
     TOTAL_CNT = 100
@@ -267,6 +327,73 @@ def test_create_unix_connection_2(loop):
     loop.run_until_complete(runner())
 
 
+def test_create_unix_connection_missing_path(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        path = os.path.join(td, 'missing')
+
+        with pytest.raises(FileNotFoundError) as excinfo:
+            loop.run_until_complete(
+                loop.create_unix_connection(asyncio.Protocol, path))
+
+        assert excinfo.value.filename == path
+
+
+def test_create_unix_connection_refused(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        path = os.path.join(td, 'sock')
+        sock = socket.socket(socket.AF_UNIX)
+        sock.bind(path)
+
+        with sock:
+            with pytest.raises(ConnectionRefusedError) as excinfo:
+                loop.run_until_complete(
+                    loop.create_unix_connection(asyncio.Protocol, path))
+
+            assert excinfo.value.filename == path
+
+
+def test_create_unix_connection_pathlike(tokio_loop):
+    loop = tokio_loop
+
+    with tempfile.TemporaryDirectory() as td:
+        path = pathlib.Path(td) / 'sock'
+        srv = socket.socket(socket.AF_UNIX)
+        srv.bind(str(path))
+        srv.listen(1)
+
+        with srv:
+            tr, pr = loop.run_until_complete(
+                loop.create_unix_connection(asyncio.Protocol, path))
+            tr.close()
+
+
+@pytest.mark.skipif(not sys.platform.startswith('linux'),
+                    reason='abstract namespace is linux only')
+def test_create_unix_connection_abstract(tokio_loop):
+    loop = tokio_loop
+
+    name = '\0tokio-test-{}'.format(os.getpid())
+    srv = socket.socket(socket.AF_UNIX)
+    srv.bind(name)
+    srv.listen(1)
+
+    with srv:
+        tr, pr = loop.run_until_complete(
+            loop.create_unix_connection(asyncio.Protocol, name))
+
+        conn, _ = srv.accept()
+        with conn:
+            tr.write(b'data')
+            loop.run_until_complete(asyncio.sleep(0.01, loop=loop))
+            assert conn.recv(4) == b'data'
+
+        tr.close()
+
+
 def test_create_unix_connection_3(loop):
     CNT = 0
     TOTAL_CNT = 100
@@ -425,3 +552,41 @@ def test_create_unix_connection_ssl_1(loop):
         assert CNT == TOTAL_CNT
 
     run(client)
+
+
+def test_create_unix_connection_ssl_handshake_timeout(tokio_loop):
+    loop = tokio_loop
+    client_sslctx = create_client_ssl_context()
+
+    class SrvProto(asyncio.Protocol):
+        # accepts uds connection, never answers handshake
+        def __init__(self):
+            self.lost = loop.create_future()
+
+        def connection_lost(self, exc):
+            self.lost.set_result(exc)
+
+    srv_proto = SrvProto()
+
+    async def client(path):
+        srv = await loop.create_unix_server(lambda: srv_proto, path)
+
+        with pytest.raises(ValueError):
+            await loop.create_unix_connection(
+                asyncio.Protocol, path, ssl_handshake_timeout=1)
+
+        with pytest.raises(TimeoutError):
+            await asyncio.wait_for(
+                loop.create_unix_connection(
+                    asyncio.Protocol, path, ssl=client_sslctx,
+                    server_hostname='', ssl_handshake_timeout=0.1),
+                10, loop=loop)
+
+        # client socket is closed
+        await asyncio.wait_for(srv_proto.lost, 10, loop=loop)
+
+        srv.close()
+        await srv.wait_closed()
+
+    with tempfile.TemporaryDirectory() as td:
+        loop.run_until_complete(client(os.path.join(td, 'sock')))
@@ -3,16 +3,85 @@ __version__ = '0.2.0'
 # import os
 # os.environ['RUST_LOG'] = 'async_tokio=debug'  # noqa
 
+import asyncio
+from asyncio import events
 from asyncio.events import AbstractEventLoop
 from asyncio.unix_events import DefaultEventLoopPolicy
 
 from . import _tokio
 
-__all__ = ('new_event_loop', 'EventLoopPolicy')
+__all__ = ('new_event_loop', 'new_request_parser', 'run',
+           'run_coroutine_threadsafe', 'EventLoopPolicy')
 
 
-def new_event_loop():
-    return _tokio.new_event_loop()
+def new_event_loop(*, resolver_workers=None):
+    if resolver_workers is None:
+        return _tokio.new_event_loop()
+    return _tokio.new_event_loop(resolver_workers)
+
+
+def new_request_parser():
+    """Create http request parser.
+
+    Parser buffers incomplete data, ``feed_data()`` returns list
+    of parsed ('message', ...), ('body', ...) and ('completed',) events.
+    """
+    return _tokio.new_request_parser()
+
+
+def run_coroutine_threadsafe(coro, loop):
+    """Submit coroutine to tokio event loop from any thread.
+
+    Return concurrent.futures.Future, cancellation of either
+    the future or the task cancels the other one.
+    """
+    return _tokio.run_coroutine_threadsafe(coro, loop)
+
+
+def run(coro, *, debug=False):
+    """Run coroutine in new event loop and return result.
+
+    Tasks left after coroutine completion get cancelled,
+    asynchronous generators get closed, loop gets closed at the end.
+    """
+    if events._get_running_loop() is not None:
+        raise RuntimeError(
+            "run() cannot be called from a running event loop")
+    if not asyncio.iscoroutine(coro):
+        raise ValueError("a coroutine was expected, got {!r}".format(coro))
+
+    loop = new_event_loop()
+    try:
+        loop.set_debug(debug)
+        return loop.run_until_complete(coro)
+    finally:
+        try:
+            _cancel_all_tasks(loop)
+            loop.run_until_complete(loop.shutdown_asyncgens())
+        finally:
+            loop.close()
+
+
+def _cancel_all_tasks(loop):
+    tasks = loop.all_tasks()
+    if not tasks:
+        return
+
+    for task in tasks:
+        task.cancel()
+
+    loop.run_until_complete(
+        asyncio.gather(*tasks, loop=loop, return_exceptions=True))
+
+    for task in tasks:
+        if task.cancelled():
+            continue
+        if task.exception() is not None:
+            loop.call_exception_handler({
+                'message': 'unhandled exception during tokio.run() shutdown',
+                'exception': task.exception(),
+                'task': task,
+            })
 
 
 class EventLoopPolicy(DefaultEventLoopPolicy):
@@ -1,5 +1,12 @@
+import os
 import reprlib
-from asyncio import events
+import socket
+from asyncio import events, protocols, tasks, unix_events
+from asyncio.log import logger
+
+# python 3.6 reports socket flags as part of socket type
+_SOCK_FLAGS = (getattr(socket, 'SOCK_NONBLOCK', 0) |
+               getattr(socket, 'SOCK_CLOEXEC', 0))
 
 
 def _format_callbacks(cb):
@@ -22,12 +29,14 @@ def _format_callbacks(cb):
     return 'cb=[%s]' % cb
 
 
-def future_repr(name, future):
-    # (Future) -> str
+def _future_repr_info(future):
+    # (Future) -> list of str
     """helper function for Future.__repr__"""
     info = []
 
-    if future.done():
+    if future.cancelled():
+        info.append('cancelled')
+    elif future.done():
         info.append('finished')
         if future._exception is not None:
             info.append('exception={!r}'.format(future._exception))
@@ -36,8 +45,6 @@ def future_repr(name, future):
             # for very long strings
             result = reprlib.repr(future._result)
             info.append('result={}'.format(result))
-    elif future.cancelled():
-        info.append('cancelled')
     else:
         info.append('pending')
 
@@ -46,5 +53,148 @@ def future_repr(name, future):
     if future._source_traceback:
         frame = future._source_traceback[-1]
         info.append('created at %s:%s' % (frame[0], frame[1]))
+    return info
+
 
+def future_repr(name, future):
+    # (Future) -> str
+    """helper function for Future.__repr__"""
+    info = _future_repr_info(future)
     return '<%s %s>' % (name, ' '.join(info))
+
+
+def task_repr(task):
+    # (Task) -> str
+    """helper function for Task.__repr__"""
+    info = _future_repr_info(task)
+    info.insert(1, 'name=%r' % task.get_name())
+    return '<Task %s>' % ' '.join(info)
+
+
+def set_result_unless_cancelled(fut, result):
+    """helper for create_future_resolved()"""
+    if not fut.cancelled():
+        fut.set_result(result)
+
+
+def feed_data(protocol, data):
+    """helper for transport.start_tls(), replays already received data"""
+    buffered = getattr(protocols, 'BufferedProtocol', None)  # python 3.7+
+    if buffered is not None and isinstance(protocol, buffered):
+        protocols._feed_data_to_buffered_proto(protocol, data)
+    else:
+        protocol.data_received(data)
+
+
+async def sock_connect(loop, sock, address):
+    """helper for sock_connect(), resolves host name of address"""
+    host, port = address[:2]
+    infos = await loop.getaddrinfo(
+        host, port, family=sock.family, type=sock.type & ~_SOCK_FLAGS,
+        proto=sock.proto)
+    if not infos:
+        raise OSError('getaddrinfo() returned empty list')
+
+    await sock_connect_addrs(loop, sock, [info[4] for info in infos])
+
+
+def _reset_socket(loop, sock):
+    """Replace failed socket with fresh one, socket object and fd are kept
+
+    connect() can not be retried on the same socket on some platforms.
+    Options and local address of original socket are not preserved.
+    """
+    loop._sock_forget(sock)
+    fresh = socket.socket(sock.family, sock.type & ~_SOCK_FLAGS, sock.proto)
+    try:
+        os.dup2(fresh.fileno(), sock.fileno(), inheritable=False)
+    finally:
+        fresh.close()
+    sock.setblocking(False)
+
+
+async def sock_connect_addrs(loop, sock, addrs):
+    """Connect sock to first address that accepts connection
+
+    Errors are aggregated the same way as in loop.create_connection()
+    """
+    exceptions = []
+    for idx, addr in enumerate(addrs):
+        if idx:
+            _reset_socket(loop, sock)
+        try:
+            await loop._sock_connect(sock, addr)
+            return
+        except OSError as exc:
+            exceptions.append(exc)
+
+    if len(exceptions) == 1:
+        raise exceptions[0]
+
+    model = str(exceptions[0])
+    if all(str(exc) == model for exc in exceptions):
+        raise exceptions[0]
+    raise OSError('Multiple exceptions: {}'.format(
+        ', '.join(str(exc) for exc in exceptions)))
+
+
+async def make_subprocess_transport(loop, protocol, args, shell,
+                                    stdin, stdout, stderr, bufsize,
+                                    extra=None, **kwargs):
+    """helper for subprocess_exec() and subprocess_shell()
+
+    Same as _UnixSelectorEventLoop._make_subprocess_transport, but child
+    exit is reported by loop's own SIGCHLD handler instead of
+    asyncio child watcher.
+    """
+    waiter = loop.create_future()
+    transp = unix_events._UnixSubprocessTransport(
+        loop, protocol, args, shell, stdin, stdout, stderr, bufsize,
+        waiter=waiter, extra=extra, **kwargs)
+
+    loop._add_child_handler(
+        transp.get_pid(), loop._child_watcher_callback, transp)
+    try:
+        await waiter
+    except (SystemExit, KeyboardInterrupt):
+        raise
+    except BaseException:
+        transp.close()
+        await transp._wait()
+        raise
+
+    return transp
+
+
+async def shutdown_asyncgens(loop, agens):
+    """helper for loop.shutdown_asyncgens(), closes generators concurrently"""
+    if not agens:
+        return
+
+    results = await tasks.gather(
+        *[agen.aclose() for agen in agens], return_exceptions=True)
+
+    for result, agen in zip(results, agens):
+        if isinstance(result, Exception):
+            loop.call_exception_handler({
+                'message': 'an error occurred during closing of '
+                           'asynchronous generator {!r}'.format(agen),
+                'exception': result,
+                'asyncgen': agen,
+            })
+
+
+def cancel_on_concurrent_cancel(loop, task, fut):
+    """helper for run_coroutine_threadsafe(), cancelled
+    concurrent.futures.Future cancels task in its loop"""
+    def _check_cancel(fut):
+        if fut.cancelled():
+            loop.call_soon_threadsafe(task.cancel)
+
+    fut.add_done_callback(_check_cancel)
+
+
+def log_slow_getaddrinfo(msg, elapsed):
+    """helper for getaddrinfo() in debug mode, same as asyncio
+    logs slow address resolution"""
+    logger.info('Getting address info %s took %.3fms', msg, elapsed * 1e3)
@@ -124,6 +124,13 @@ def loop2(request):
         yield _loop
 
 
+@pytest.fixture
+def tokio_loop():
+    """Return an instance of the tokio event loop."""
+    with loop_context(tokio.EventLoopPolicy, fast=False) as _loop:
+        yield _loop
+
+
 @pytest.fixture(params=LOOP_FACTORIES, ids=LOOP_FACTORY_IDS)
 def other_loop(request):
     """Return an instance of the event loop."""